@@ -0,0 +1,183 @@
+//! Credential backends
+//!
+//! Defines the `Authenticator` trait implemented by each credential store and
+//! a `ChainAuthenticator` that consults several stores in priority order.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::credentials::CREDENTIALS;
+use super::password::verify_password;
+use crate::config::StartupConfig;
+use crate::error::AuthError;
+
+/// A source of user credentials.
+pub trait Authenticator: Send + Sync {
+    /// Returns whether this backend has an account for `username`.
+    fn has_user(&self, username: &str) -> bool;
+
+    /// Checks `password` against the account for `username`.
+    fn authenticate(&self, username: &str, password: &str) -> Result<(), AuthError>;
+}
+
+/// Credential backends that can be listed in `auth_backends`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthBackend {
+    /// Accounts read from `credentials_file`
+    File,
+    /// The built-in demo accounts
+    Builtin,
+}
+
+/// The built-in demo accounts.
+pub struct BuiltinAuthenticator;
+
+impl Authenticator for BuiltinAuthenticator {
+    fn has_user(&self, username: &str) -> bool {
+        CREDENTIALS.contains_key(username)
+    }
+
+    fn authenticate(&self, username: &str, password: &str) -> Result<(), AuthError> {
+        match CREDENTIALS.get(username) {
+            Some(stored) if *stored == password => Ok(()),
+            Some(_) => Err(AuthError::InvalidPassword(username.to_string())),
+            None => Err(AuthError::UserNotFound(username.to_string())),
+        }
+    }
+}
+
+/// Accounts loaded from a file of `username:password` lines.
+///
+/// The password is normally an Argon2 hash written by `add_user`, but a
+/// plaintext password is accepted too. Blank lines and lines starting with
+/// `#` are ignored.
+pub struct FileAuthenticator {
+    users: HashMap<String, String>,
+}
+
+impl FileAuthenticator {
+    /// Loads the accounts in `path`.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let users = fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once(':'))
+            .map(|(user, password)| (user.trim().to_string(), password.to_string()))
+            .collect();
+        Ok(Self { users })
+    }
+}
+
+impl Authenticator for FileAuthenticator {
+    fn has_user(&self, username: &str) -> bool {
+        self.users.contains_key(username)
+    }
+
+    fn authenticate(&self, username: &str, password: &str) -> Result<(), AuthError> {
+        match self.users.get(username) {
+            Some(stored) if verify_password(stored, password) => Ok(()),
+            Some(_) => Err(AuthError::InvalidPassword(username.to_string())),
+            None => Err(AuthError::UserNotFound(username.to_string())),
+        }
+    }
+}
+
+/// Tries a list of backends in order until one accepts the credentials.
+///
+/// Failures are reported the same way whichever backends rejected the login,
+/// so clients cannot tell which store holds an account.
+#[derive(Default)]
+pub struct ChainAuthenticator {
+    backends: Vec<Box<dyn Authenticator>>,
+}
+
+impl ChainAuthenticator {
+    pub fn new(backends: Vec<Box<dyn Authenticator>>) -> Self {
+        Self { backends }
+    }
+
+    /// Builds the chain described by `auth_backends` in the startup config.
+    ///
+    /// Fails if the credentials file cannot be read, rather than running
+    /// without the accounts it holds.
+    pub fn from_config(config: &StartupConfig) -> std::io::Result<Self> {
+        let mut backends: Vec<Box<dyn Authenticator>> = Vec::new();
+        for backend in &config.auth_backends {
+            match backend {
+                AuthBackend::Builtin => backends.push(Box::new(BuiltinAuthenticator)),
+                AuthBackend::File => {
+                    // validate() insists on a file for this backend
+                    if let Some(path) = config.credentials_file.as_deref() {
+                        let file = FileAuthenticator::load(Path::new(path)).map_err(|e| {
+                            std::io::Error::new(e.kind(), format!("credentials file {path}: {e}"))
+                        })?;
+                        backends.push(Box::new(file));
+                    }
+                }
+            }
+        }
+        Ok(Self::new(backends))
+    }
+}
+
+/// The server's credential chain, built once and swapped for a fresh one on reload
+///
+/// It is derived from the configuration rather than part of it, so any two
+/// compare equal.
+#[derive(Clone, Default)]
+pub struct SharedAuthenticator(Arc<RwLock<Arc<ChainAuthenticator>>>);
+
+impl SharedAuthenticator {
+    /// The chain logins are currently checked against
+    pub fn get(&self) -> Arc<ChainAuthenticator> {
+        Arc::clone(&self.0.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Replaces the chain for every login from now on
+    pub fn replace(&self, chain: ChainAuthenticator) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(chain);
+    }
+}
+
+impl fmt::Debug for SharedAuthenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedAuthenticator")
+    }
+}
+
+impl PartialEq for SharedAuthenticator {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Authenticator for ChainAuthenticator {
+    fn has_user(&self, username: &str) -> bool {
+        self.backends
+            .iter()
+            .any(|backend| backend.has_user(username))
+    }
+
+    fn authenticate(&self, username: &str, password: &str) -> Result<(), AuthError> {
+        if self
+            .backends
+            .iter()
+            .any(|backend| backend.authenticate(username, password).is_ok())
+        {
+            return Ok(());
+        }
+
+        if self.has_user(username) {
+            Err(AuthError::InvalidPassword(username.to_string()))
+        } else {
+            Err(AuthError::UserNotFound(username.to_string()))
+        }
+    }
+}
@@ -2,7 +2,19 @@
 //!
 //! Handles user authentication and credential validation.
 
+pub mod authenticator;
 mod credentials;
+pub mod password;
+pub mod provisioning;
 pub mod validator;
 
-pub use validator::{validate_password, validate_user};
+pub use authenticator::{
+    AuthBackend, Authenticator, BuiltinAuthenticator, ChainAuthenticator, FileAuthenticator,
+    SharedAuthenticator,
+};
+pub use password::{PasswordPolicy, check_password_strength, hash_password, verify_password};
+pub use provisioning::add_user;
+pub use validator::{
+    ANONYMOUS_USER, check_password_format, check_username_format, is_anonymous, validate_password,
+    validate_user,
+};
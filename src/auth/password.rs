@@ -0,0 +1,86 @@
+//! Password storage
+//!
+//! Hashes passwords for the credentials file and checks new ones against the
+//! configured policy before they are hashed.
+
+use argon2::Argon2;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AuthError, ProvisioningError};
+
+/// Minimum password strength required when provisioning accounts.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    /// Minimum number of characters
+    pub min_length: usize,
+    /// Require both lowercase and uppercase letters
+    pub require_mixed_case: bool,
+    /// Require at least one ASCII digit
+    pub require_digit: bool,
+    /// Require at least one character that is not a letter or digit
+    pub require_symbol: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_mixed_case: true,
+            require_digit: true,
+            require_symbol: false,
+        }
+    }
+}
+
+/// Checks a new password against the policy, describing the first rule it breaks.
+pub fn check_password_strength(password: &str, policy: &PasswordPolicy) -> Result<(), AuthError> {
+    if password.chars().count() < policy.min_length {
+        return Err(AuthError::WeakPassword(format!(
+            "must be at least {} characters",
+            policy.min_length
+        )));
+    }
+
+    if policy.require_mixed_case
+        && !(password.chars().any(char::is_lowercase) && password.chars().any(char::is_uppercase))
+    {
+        return Err(AuthError::WeakPassword(
+            "must contain both lowercase and uppercase letters".into(),
+        ));
+    }
+
+    if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        return Err(AuthError::WeakPassword("must contain a digit".into()));
+    }
+
+    if policy.require_symbol && password.chars().all(char::is_alphanumeric) {
+        return Err(AuthError::WeakPassword("must contain a symbol".into()));
+    }
+
+    Ok(())
+}
+
+/// Hashes `password` with Argon2id and a random salt, in PHC string format.
+pub fn hash_password(password: &str) -> Result<String, ProvisioningError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| ProvisioningError::HashFailed(e.to_string()))
+}
+
+/// Checks `password` against a value from the credentials file.
+///
+/// PHC strings such as those written by `hash_password` are verified as
+/// hashes; anything else is a plaintext password written by hand.
+pub fn verify_password(stored: &str, password: &str) -> bool {
+    match PasswordHash::new(stored) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => stored == password,
+    }
+}
@@ -0,0 +1,59 @@
+//! Account provisioning helpers
+//!
+//! Adds accounts to the credentials file read by the `file` backend.
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use super::authenticator::{Authenticator, FileAuthenticator};
+use super::password::{check_password_strength, hash_password};
+use super::validator::{check_password_format, check_username_format, is_anonymous};
+use crate::config::StartupConfig;
+use crate::error::{AuthError, ProvisioningError};
+
+/// Adds an account to a credentials file read by the `file` backend.
+///
+/// The username and password must be ones a client could log in with, and
+/// the password must satisfy `password_policy` before it is hashed and stored.
+/// The file is created if it does not exist yet, and an existing account is
+/// never overwritten.
+pub fn add_user(
+    credentials_file: &Path,
+    username: &str,
+    password: &str,
+    config: &StartupConfig,
+) -> Result<(), ProvisioningError> {
+    check_username_format(username, config.max_username_length)?;
+    // Accounts are stored as `username:hash` lines, read back trimmed
+    if is_anonymous(username) || username.trim() != username || username.contains(':') {
+        return Err(AuthError::InvalidUsername(username.to_string()).into());
+    }
+    check_password_format(password, config.max_username_length)?;
+    if password.trim() != password {
+        return Err(AuthError::MalformedInput("Invalid password format".into()).into());
+    }
+    check_password_strength(password, &config.password_policy)?;
+
+    let existing = match fs::read_to_string(credentials_file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if FileAuthenticator::load(credentials_file).is_ok_and(|file| file.has_user(username)) {
+        return Err(ProvisioningError::UserExists(username.to_string()));
+    }
+    let hash = hash_password(password)?;
+
+    let separator = if existing.is_empty() || existing.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(credentials_file)?
+        .write_all(format!("{separator}{username}:{hash}\n").as_bytes())?;
+    Ok(())
+}
@@ -1,29 +1,59 @@
 //! Authentication validator
 //!
 //! Implements FTP user authentication logic, including username and password validation.
-//! Uses a static in-memory credential store for demonstration purposes.
+//! Credentials are checked against the backends configured in `auth_backends`.
 
-use super::credentials::CREDENTIALS;
+use super::authenticator::Authenticator;
 use crate::config::StartupConfig;
 use crate::error::AuthError;
 
+/// The username clients send for anonymous FTP
+pub const ANONYMOUS_USER: &str = "anonymous";
+
+/// Returns whether `username` asks for anonymous access.
+pub fn is_anonymous(username: &str) -> bool {
+    username.eq_ignore_ascii_case(ANONYMOUS_USER)
+}
+
 /// Performs basic input sanitation to check for malicious or malformed usernames/passwords.
 fn is_valid_input(input: &str, max_length: usize) -> bool {
     !input.trim().is_empty() && input.len() <= max_length && !input.contains(['\r', '\n', '\0'])
 }
 
-/// Validates that the given username exists in the credential store.
-pub fn validate_user(username: &str, config: &StartupConfig) -> Result<(), AuthError> {
+/// Checks that `username` is in a form USER accepts, whether or not it has an account.
+pub fn check_username_format(username: &str, max_length: usize) -> Result<(), AuthError> {
     // Check for invalid username characters/format
     if username.contains(['@', '#', ',', '%']) || username.starts_with(char::is_numeric) {
         return Err(AuthError::InvalidUsername(username.to_string()));
     }
 
-    if !is_valid_input(username, config.max_username_length) {
+    if !is_valid_input(username, max_length) {
         return Err(AuthError::MalformedInput("Invalid username format".into()));
     }
+    Ok(())
+}
+
+/// Checks that `password` is in a form PASS accepts.
+pub fn check_password_format(password: &str, max_length: usize) -> Result<(), AuthError> {
+    if !is_valid_input(password, max_length) {
+        return Err(AuthError::MalformedInput("Invalid password format".into()));
+    }
+    Ok(())
+}
 
-    if CREDENTIALS.contains_key(username) {
+/// Validates that the given username exists in the credential store.
+pub fn validate_user(username: &str, config: &StartupConfig) -> Result<(), AuthError> {
+    check_username_format(username, config.max_username_length)?;
+
+    if is_anonymous(username) {
+        return if config.anonymous_login {
+            Ok(())
+        } else {
+            Err(AuthError::AnonymousDisabled)
+        };
+    }
+
+    if config.authenticator.get().has_user(username) {
         Ok(())
     } else {
         Err(AuthError::UserNotFound(username.to_string()))
@@ -31,18 +61,25 @@ pub fn validate_user(username: &str, config: &StartupConfig) -> Result<(), AuthE
 }
 
 /// Validates that the provided password matches the stored password for the username.
+///
+/// Anonymous logins accept any password; by convention it is the client's email
+/// address, which is logged when `log_anonymous_passwords` is set.
 pub fn validate_password(
     username: &str,
     password: &str,
     config: &StartupConfig,
 ) -> Result<(), AuthError> {
-    if !is_valid_input(password, config.max_username_length) {
-        return Err(AuthError::MalformedInput("Invalid password format".into()));
-    }
+    check_password_format(password, config.max_username_length)?;
 
-    match CREDENTIALS.get(username) {
-        Some(stored) if stored == &password => Ok(()),
-        Some(_) => Err(AuthError::InvalidPassword(username.to_string())),
-        None => Err(AuthError::UserNotFound(username.to_string())),
+    if is_anonymous(username) {
+        if !config.anonymous_login {
+            return Err(AuthError::AnonymousDisabled);
+        }
+        if config.log_anonymous_passwords {
+            log::info!("Anonymous login identified as {password:?}");
+        }
+        return Ok(());
     }
+
+    config.authenticator.get().authenticate(username, password)
 }
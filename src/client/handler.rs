@@ -1,37 +1,120 @@
-use log::{error, info};
-use std::collections::HashMap;
+use log::{debug, error, info, warn};
+use std::collections::{HashMap, VecDeque};
 use std::future::Future;
+use std::io::Cursor;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinHandle;
 
-use crate::client::Client;
+use crate::client::{Client, CommandRateLimiter, ErrorCounter};
 use crate::config::{SharedRuntimeConfig, StartupConfig};
-use crate::protocol::handle_command;
-use crate::protocol::{CommandStatus, parse_command};
-use crate::transfer::ChannelRegistry;
+use crate::logging;
+use crate::protocol::{
+    Command, CommandResult, CommandStatus, TransferFinish, command_spec, decode_command_line,
+    encode_for_client, parse_command,
+};
+use crate::protocol::{handle_command, responses};
+use crate::server::Metrics;
+use crate::transfer::{CancelToken, ChannelRegistry};
+
+/// Maximum number of command lines read ahead of the one being handled
+const COMMAND_QUEUE_LENGTH: usize = 16;
+
+/// A command line read ahead by `spawn_command_reader`
+struct QueuedLine {
+    line: Vec<u8>,
+    transfer: TransferClaim,
+}
+
+/// How a queued line relates to the session's one data transfer at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransferClaim {
+    /// Not a data transfer command
+    None,
+    /// A data transfer command, holding the transfer flag until it is handled
+    Claimed,
+    /// A data transfer command sent while another was queued or running
+    Busy,
+}
+
+/// A data transfer running while the session goes on reading commands
+struct InFlight {
+    command: Command,
+    /// Who started it, in case the session logs out before it finishes
+    user: Option<String>,
+    started: Instant,
+    _transfer_flag: Option<TransferFlagGuard>,
+    finish: Pin<Box<dyn Future<Output = TransferFinish> + Send>>,
+}
+
+/// What the session waits for next
+enum Event {
+    Line(Option<QueuedLine>),
+    TransferDone(TransferFinish),
+}
+
+/// Clears the transfer flag when the command that claimed it has been handled.
+struct TransferFlagGuard(Arc<AtomicBool>);
+
+impl Drop for TransferFlagGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
 
 /// Handles FTP client session using Tokio async runtime.
 ///
-/// - Uses BufReader to read command lines from the client.
-/// - Dispatches commands using `handle_command`.
+/// - Reads command lines on a separate task (see `spawn_command_reader`),
+///   starting with any the client sent right behind its login that
+///   `cmd_stream` has already buffered.
+/// - Dispatches commands using `handle_command`, buffering the replies to
+///   each one and flushing them before waiting for the next.
+/// - Runs data transfers alongside the session: PWD, NOOP and STAT are
+///   answered while one runs, data commands are refused with 450, and other
+///   commands wait until it has finished.
 /// - Manages client state from shared `client_registry` and `channel_registry`.
+/// - Records how long each command takes to handle in `metrics`.
+/// - Disconnects clients after `max_consecutive_errors` error replies in a
+///   row, and after `max_login_attempts` refused USER and PASS commands,
+///   counting those refused before the session started. Refused USER and
+///   PASS count as errors only when there is no login limit.
 pub async fn handle_client(
-    cmd_stream: TcpStream,
+    cmd_stream: BufReader<TcpStream>,
     clients: Arc<Mutex<HashMap<SocketAddr, Client>>>,
     client_addr: SocketAddr,
     channel_registry: Arc<Mutex<ChannelRegistry>>,
     startup_config: Arc<StartupConfig>,
     runtime_config: SharedRuntimeConfig,
+    metrics: Arc<Metrics>,
 ) {
-    let (read_half, write_half) = cmd_stream.into_split();
-    let mut reader = BufReader::new(read_half);
-    let mut line = String::new();
+    let pending = cmd_stream.buffer().to_vec();
+    let (read_half, write_half) = cmd_stream.into_inner().into_split();
+    let (cancel, mut deadline) = match clients.lock().await.get(&client_addr) {
+        Some(client) => (
+            client.transfer_cancel().clone(),
+            client.session_deadline(&startup_config),
+        ),
+        None => (CancelToken::default(), None),
+    };
+    let transfer_active = Arc::new(AtomicBool::new(false));
+    let (mut lines, reader_task) = spawn_command_reader(
+        pending,
+        read_half,
+        client_addr,
+        cancel.clone(),
+        transfer_active.clone(),
+    );
+    let mut rate_limiter = CommandRateLimiter::new(startup_config.max_commands_per_second);
+    let mut errors = ErrorCounter::new(startup_config.max_consecutive_errors);
 
-    let write_half = Arc::new(Mutex::new(write_half));
+    let write_half = Arc::new(Mutex::new(BufWriter::new(write_half)));
 
     let send_intermediate = {
         let write_half = write_half.clone();
@@ -39,124 +122,278 @@ pub async fn handle_client(
             let write_half = write_half.clone();
             let msg_owned = msg.to_string();
             Box::pin(async move {
+                // Sent right away, since the command carries on after it
                 let mut writer = write_half.lock().await;
-                writer.write_all(msg_owned.as_bytes()).await
+                writer.write_all(msg_owned.as_bytes()).await?;
+                writer.flush().await
             }) as Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>
         }
     };
+    let mut in_flight: Option<InFlight> = None;
+    // Commands read during a transfer that have to wait for it to finish
+    let mut deferred: VecDeque<(Command, Option<TransferFlagGuard>)> = VecDeque::new();
     loop {
-        line.clear();
-        match reader.read_line(&mut line).await {
-            Ok(0) => {
-                // Client closed the connection
-                info!("Connection closed by client {client_addr}");
-                break;
-            }
-            Ok(_) => {
-                if line.len() > startup_config.max_command_length {
-                    error!(
-                        "Command too long ({} chars) from client {}",
-                        line.len(),
-                        client_addr
-                    );
-                    {
+        // Everything written while handling a command goes out in one go,
+        // before waiting for the next
+        if let Err(e) = write_half.lock().await.flush().await {
+            error!("Failed to send response to {client_addr}: {e}");
+            break;
+        }
+
+        // `finished` holds the outcome of a transfer that has just run; other
+        // commands have yet to be executed
+        let (command, _transfer_flag, finished) = if in_flight.is_none()
+            && let Some((command, transfer_flag)) = deferred.pop_front()
+        {
+            (command, transfer_flag, None)
+        } else {
+            let event = tokio::select! {
+                received = lines.recv() => Event::Line(received),
+                finish = transfer_done(&mut in_flight) => Event::TransferDone(finish),
+                () = wait_until(deadline) => {
+                    info!("Client {client_addr} reached the session time limit, disconnecting");
+                    let mut writer = write_half.lock().await;
+                    if let Err(e) = writer.write_all(b"421 Session time limit reached\r\n").await {
+                        error!("Failed to send session limit response to {client_addr}: {e}");
+                    }
+                    break;
+                }
+            };
+
+            match event {
+                Event::TransferDone(finish) => {
+                    let Some(running) = in_flight.take() else {
+                        continue;
+                    };
+                    let handled = finish_transfer(
+                        finish,
+                        &running.command,
+                        running.user,
+                        running.started,
+                        &clients,
+                        client_addr,
+                        &startup_config,
+                    )
+                    .await;
+                    (running.command, running._transfer_flag, Some(handled))
+                }
+                // Client closed the connection, or it failed; logged by the reader
+                Event::Line(None) => break,
+                Event::Line(Some(QueuedLine { line, transfer })) => {
+                    // Held until this command's replies are written, so a client
+                    // that has seen them can start the next transfer
+                    let transfer_flag = (transfer == TransferClaim::Claimed)
+                        .then(|| TransferFlagGuard(transfer_active.clone()));
+
+                    if !rate_limiter.check(Instant::now()) {
+                        warn!(
+                            "Client {client_addr} exceeded {} commands per second, disconnecting",
+                            startup_config.max_commands_per_second
+                        );
                         let mut writer = write_half.lock().await;
-                        if let Err(e) = writer.write_all(b"500 Command too long\r\n").await {
-                            error!("Failed to send error response to {client_addr}: {e}");
-                            break;
+                        if let Err(e) = writer
+                            .write_all(b"421 Too many commands, closing control connection\r\n")
+                            .await
+                        {
+                            error!("Failed to send flood response to {client_addr}: {e}");
                         }
+                        break;
                     }
-                    continue;
-                }
 
-                let trimmed = line.trim_end_matches("\r\n");
-                let command = parse_command(trimmed);
-                info!("Received from {}: {:?}", client_addr, &command);
-
-                let mut clients_guard = clients.lock().await;
-                let mut channel_registry_guard = channel_registry.lock().await;
-
-                match clients_guard.get_mut(&client_addr) {
-                    Some(client) => {
-                        let result = handle_command(
-                            client,
-                            &command,
-                            &mut channel_registry_guard,
-                            &startup_config,
-                            &runtime_config,
-                            &send_intermediate,
-                        )
-                        .await;
-
-                        match result.status {
-                            CommandStatus::CloseConnection => {
-                                if let Some(msg) = result.message {
-                                    // CHANGE: Use Arc<Mutex> for quit response
-                                    let mut writer = write_half.lock().await;
-                                    if let Err(e) = writer.write_all(msg.as_bytes()).await {
-                                        error!(
-                                            "Failed to send quit response to {client_addr}: {e}"
-                                        );
-                                    }
-                                }
-                                info!("Client {client_addr} requested to quit");
+                    if line.len() > startup_config.max_command_length {
+                        error!(
+                            "Command too long ({} chars) from client {}",
+                            line.len(),
+                            client_addr
+                        );
+                        {
+                            let mut writer = write_half.lock().await;
+                            if let Err(e) = writer.write_all(b"500 Command too long\r\n").await {
+                                error!("Failed to send error response to {client_addr}: {e}");
                                 break;
                             }
-                            CommandStatus::Success => {
-                                if let Some(msg) = result.message {
-                                    info!(
-                                        "Sending success response to client {}: {}",
-                                        client_addr,
-                                        msg.trim()
-                                    );
-                                    // CHANGE: Use Arc<Mutex> for success response
-                                    let mut writer = write_half.lock().await;
-                                    if let Err(e) = writer.write_all(msg.as_bytes()).await {
-                                        error!(
-                                            "Failed to send success response to {client_addr}: {e}"
-                                        );
-                                        break;
-                                    }
-                                }
-                            }
-                            CommandStatus::Failure(ref reason) => {
-                                info!("Command failed for client {client_addr}: {reason}");
-                                if let Some(msg) = result.message {
-                                    // CHANGE: Use Arc<Mutex> for error response
-                                    let mut writer = write_half.lock().await;
-                                    if let Err(e) = writer.write_all(msg.as_bytes()).await {
-                                        error!(
-                                            "Failed to send error response to {client_addr}: {e}"
-                                        );
-                                        break;
-                                    }
-                                }
-                            }
                         }
+                        if !errors.record("500") {
+                            disconnect_after_errors(&write_half, client_addr, &startup_config)
+                                .await;
+                            break;
+                        }
+                        continue;
                     }
-                    None => {
-                        error!(
-                            "Client {client_addr} not found in clients map - terminating connection"
-                        );
-                        // CHANGE: Use Arc<Mutex> for session error
+
+                    let latin1 = startup_config.latin1_fallback
+                        && clients
+                            .lock()
+                            .await
+                            .get(&client_addr)
+                            .is_some_and(|client| client.uses_latin1(&startup_config));
+                    let Some(decoded) = decode_command_line(&line, latin1) else {
+                        error!("Invalid command encoding from client {client_addr}");
                         {
                             let mut writer = write_half.lock().await;
                             if let Err(e) =
-                                writer.write_all(b"421 Client session not found\r\n").await
+                                writer.write_all(b"500 Invalid command encoding\r\n").await
                             {
-                                error!("Failed to send session error to {client_addr}: {e}");
+                                error!("Failed to send error response to {client_addr}: {e}");
+                                break;
                             }
                         }
-                        break;
+                        if !errors.record("500") {
+                            disconnect_after_errors(&write_half, client_addr, &startup_config)
+                                .await;
+                            break;
+                        }
+                        continue;
+                    };
+
+                    let trimmed = decoded.trim_end_matches("\r\n");
+                    let command = parse_command(trimmed);
+                    info!("Received from {}: {:?}", client_addr, &command);
+
+                    if transfer == TransferClaim::Busy {
+                        warn!(
+                            "Client {client_addr} sent {} while a transfer was in progress",
+                            command.name()
+                        );
+                        {
+                            let mut writer = write_half.lock().await;
+                            if let Err(e) = writer
+                                .write_all(responses::TRANSFER_IN_PROGRESS.as_bytes())
+                                .await
+                            {
+                                error!("Failed to send error response to {client_addr}: {e}");
+                                break;
+                            }
+                        }
+                        if !errors.record(responses::TRANSFER_IN_PROGRESS) {
+                            disconnect_after_errors(&write_half, client_addr, &startup_config)
+                                .await;
+                            break;
+                        }
+                        continue;
+                    }
+
+                    // ABOR lands here too, to be answered after the 426 of
+                    // the transfer it cancelled
+                    if in_flight.is_some() && !command_spec(&command).answered_during_transfer {
+                        deferred.push_back((command, transfer_flag));
+                        continue;
                     }
+                    (command, transfer_flag, None)
                 }
             }
-            Err(e) => {
-                error!("Failed to read from {client_addr}: {e}");
+        };
+
+        let handled = match finished {
+            Some(handled) => handled,
+            None => match execute_command(
+                &command,
+                &clients,
+                client_addr,
+                &channel_registry,
+                &startup_config,
+                &runtime_config,
+                &send_intermediate,
+            )
+            .await
+            {
+                Some(Executed::Done(handled)) => Some(handled),
+                Some(Executed::Transfer {
+                    user,
+                    started,
+                    finish,
+                }) => {
+                    in_flight = Some(InFlight {
+                        command,
+                        user,
+                        started,
+                        _transfer_flag,
+                        finish,
+                    });
+                    continue;
+                }
+                None => None,
+            },
+        };
+        let Some(Handled {
+            result,
+            latin1,
+            deadline: session_deadline,
+            elapsed,
+        }) = handled
+        else {
+            error!("Client {client_addr} not found in clients map - terminating connection");
+            let mut writer = write_half.lock().await;
+            if let Err(e) = writer.write_all(b"421 Client session not found\r\n").await {
+                error!("Failed to send session error to {client_addr}: {e}");
+            }
+            break;
+        };
+        // USER may have switched to someone with a different limit
+        deadline = session_deadline;
+        metrics.record_command(command.name(), elapsed);
+
+        // Failed logins have their own limit below, if one is set, and
+        // blank keepalive lines neither count nor end a run of errors
+        let uncounted = matches!(command, Command::EMPTY)
+            || (matches!(command, Command::USER(_) | Command::PASS(_))
+                && startup_config.max_login_attempts > 0);
+        let close = matches!(result.status, CommandStatus::CloseConnection);
+        if let CommandStatus::Failure(reason) = &result.status {
+            info!("Command failed for client {client_addr}: {reason}");
+        }
+
+        if let Some(msg) = &result.message {
+            info!("Sending response to client {client_addr}: {}", msg.trim());
+            let mut writer = write_half.lock().await;
+            if let Err(e) = writer.write_all(&encode_for_client(msg, latin1)).await {
+                error!("Failed to send response to {client_addr}: {e}");
                 break;
             }
         }
+        if close {
+            info!("Client {client_addr} requested to quit");
+            break;
+        }
+        // Logging in again doesn't reset the count from before the session
+        if let Some(msg) = &result.message
+            && matches!(command, Command::USER(_) | Command::PASS(_))
+            && msg.starts_with(['4', '5'])
+        {
+            let failed_logins = clients
+                .lock()
+                .await
+                .get_mut(&client_addr)
+                .map_or(0, Client::record_failed_login);
+            if startup_config.max_login_attempts > 0
+                && failed_logins >= startup_config.max_login_attempts
+            {
+                warn!("Client {client_addr} failed to log in {failed_logins} times, disconnecting");
+                let mut writer = write_half.lock().await;
+                if let Err(e) = writer
+                    .write_all(responses::TOO_MANY_LOGIN_ATTEMPTS.as_bytes())
+                    .await
+                {
+                    error!("Failed to send login limit response to {client_addr}: {e}");
+                }
+                break;
+            }
+        }
+        if let Some(msg) = &result.message
+            && !uncounted
+            && !errors.record(msg)
+        {
+            disconnect_after_errors(&write_half, client_addr, &startup_config).await;
+            break;
+        }
+    }
+    // Send the last reply, such as QUIT's, before closing
+    if let Err(e) = write_half.lock().await.flush().await {
+        error!("Failed to send final response to {client_addr}: {e}");
     }
+    reader_task.abort();
+    // Stops a transfer the session is no longer waiting for
+    cancel.cancel();
 
     // Clean up any remaining data channels
     {
@@ -172,10 +409,239 @@ pub async fn handle_client(
     // Clean up client from registry
     {
         let mut clients_guard = clients.lock().await;
-        if clients_guard.remove(&client_addr).is_some() {
-            info!("Client {client_addr} removed from registry and disconnected");
+        if let Some(client) = clients_guard.remove(&client_addr) {
+            info!(
+                event = "disconnect",
+                client_addr:% = client_addr;
+                "Client {client_addr} removed from registry and disconnected"
+            );
+            debug!("Last commands from {client_addr}: {}", client.history());
         } else {
             info!("Client {client_addr} was already removed from registry");
         }
     }
 }
+
+/// What the session needs to reply to a command once it has been handled
+struct Handled {
+    result: CommandResult,
+    /// Whether replies to the client are encoded as Latin-1
+    latin1: bool,
+    /// When the session must end, which USER may have changed
+    deadline: Option<Instant>,
+    /// How long the command took, including any transfer
+    elapsed: Duration,
+}
+
+/// How far `execute_command` got with a command
+enum Executed {
+    Done(Handled),
+    /// The command started a data transfer, finished by `finish_transfer`
+    Transfer {
+        user: Option<String>,
+        started: Instant,
+        finish: Pin<Box<dyn Future<Output = TransferFinish> + Send>>,
+    },
+}
+
+/// Handles one command and records it in the client's history and the log.
+///
+/// A data transfer started by the command is handed back to run after the
+/// shared client and channel registries are released, so a slow transfer only
+/// holds up its own session. Returns `None` if the client is no longer
+/// registered.
+async fn execute_command<F>(
+    command: &Command,
+    clients: &Mutex<HashMap<SocketAddr, Client>>,
+    client_addr: SocketAddr,
+    channel_registry: &Mutex<ChannelRegistry>,
+    startup_config: &Arc<StartupConfig>,
+    runtime_config: &SharedRuntimeConfig,
+    send_intermediate: &F,
+) -> Option<Executed>
+where
+    F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
+{
+    let mut clients_guard = clients.lock().await;
+    let mut channel_registry_guard = channel_registry.lock().await;
+    let client = clients_guard.get_mut(&client_addr)?;
+
+    // Captured up front so QUIT and LOGOUT are still attributed
+    let user = client.username().cloned();
+    let started = Instant::now();
+    let result = handle_command(
+        client,
+        command,
+        &mut channel_registry_guard,
+        startup_config,
+        runtime_config,
+        send_intermediate,
+    )
+    .await;
+
+    if let CommandStatus::Transfer(transfer) = result.status {
+        return Some(Executed::Transfer {
+            user,
+            started,
+            finish: Box::pin(transfer.run(startup_config.clone())),
+        });
+    }
+    Some(Executed::Done(record_command(
+        client,
+        client_addr,
+        command,
+        user,
+        started,
+        result,
+        startup_config,
+    )))
+}
+
+/// Builds the reply to a transfer command once its transfer has run.
+///
+/// Returns `None` if the client is no longer registered.
+async fn finish_transfer(
+    finish: TransferFinish,
+    command: &Command,
+    user: Option<String>,
+    started: Instant,
+    clients: &Mutex<HashMap<SocketAddr, Client>>,
+    client_addr: SocketAddr,
+    startup_config: &StartupConfig,
+) -> Option<Handled> {
+    let mut clients_guard = clients.lock().await;
+    let client = clients_guard.get_mut(&client_addr)?;
+    // An ABOR only cancels the transfer it arrived during
+    client.transfer_cancel().clear_abort();
+    let result = finish(client);
+    Some(record_command(
+        client,
+        client_addr,
+        command,
+        user,
+        started,
+        result,
+        startup_config,
+    ))
+}
+
+/// Records a handled command in the client's history and the log.
+fn record_command(
+    client: &mut Client,
+    client_addr: SocketAddr,
+    command: &Command,
+    user: Option<String>,
+    started: Instant,
+    result: CommandResult,
+    startup_config: &StartupConfig,
+) -> Handled {
+    client.record_command(command.name(), result.message.as_deref());
+    logging::log_command(
+        client_addr,
+        user.as_deref(),
+        command.name(),
+        result.message.as_deref(),
+        started.elapsed(),
+    );
+    Handled {
+        latin1: client.uses_latin1(startup_config),
+        deadline: client.session_deadline(startup_config),
+        elapsed: started.elapsed(),
+        result,
+    }
+}
+
+/// Completes when the transfer in flight has run, or never without one.
+async fn transfer_done(in_flight: &mut Option<InFlight>) -> TransferFinish {
+    match in_flight {
+        Some(running) => running.finish.as_mut().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Reads command lines from the control connection on a separate task.
+///
+/// Transfers run on blocking sockets that can't see the control connection,
+/// so it is watched here: on EOF or a read error, `cancel` aborts any transfer
+/// in progress and the returned channel closes. ABOR read during a transfer
+/// aborts it through `cancel` too, before the ABOR itself is handled.
+/// `pending` holds bytes already read from the connection before the session
+/// started, which are read first.
+///
+/// Data transfer commands claim `transfer_active` until the session has handled
+/// them; one read while it is claimed is marked busy, to be refused with 450.
+fn spawn_command_reader(
+    pending: Vec<u8>,
+    read_half: OwnedReadHalf,
+    client_addr: SocketAddr,
+    cancel: CancelToken,
+    transfer_active: Arc<AtomicBool>,
+) -> (mpsc::Receiver<QueuedLine>, JoinHandle<()>) {
+    let (sender, receiver) = mpsc::channel(COMMAND_QUEUE_LENGTH);
+    let task = tokio::spawn(async move {
+        let mut reader = BufReader::new(Cursor::new(pending).chain(read_half));
+        loop {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) => {
+                    info!("Connection closed by client {client_addr}");
+                    break;
+                }
+                Ok(_) => {
+                    // Only the verb matters here, and it is ASCII in any encoding
+                    let decoded = String::from_utf8_lossy(&line);
+                    let command = parse_command(decoded.trim_end_matches("\r\n"));
+                    // The transfer would hold up the ABOR until it finished
+                    if command == Command::ABOR && transfer_active.load(Ordering::SeqCst) {
+                        info!("Client {client_addr} sent ABOR, cancelling its transfer");
+                        cancel.abort();
+                    }
+                    let transfer = if !command_spec(&command).requires_data_channel {
+                        TransferClaim::None
+                    } else if transfer_active.swap(true, Ordering::SeqCst) {
+                        TransferClaim::Busy
+                    } else {
+                        TransferClaim::Claimed
+                    };
+                    if sender.send(QueuedLine { line, transfer }).await.is_err() {
+                        // The session ended on its own
+                        return;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to read from {client_addr}: {e}");
+                    break;
+                }
+            }
+        }
+        cancel.cancel();
+    });
+    (receiver, task)
+}
+
+/// Tells a client it got too many error replies in a row, before disconnecting it.
+async fn disconnect_after_errors(
+    write_half: &Mutex<BufWriter<OwnedWriteHalf>>,
+    client_addr: SocketAddr,
+    startup_config: &StartupConfig,
+) {
+    warn!(
+        "Client {client_addr} got {} error replies in a row, disconnecting",
+        startup_config.max_consecutive_errors
+    );
+    let mut writer = write_half.lock().await;
+    if let Err(e) = writer
+        .write_all(responses::TOO_MANY_ERRORS.as_bytes())
+        .await
+    {
+        error!("Failed to send error limit response to {client_addr}: {e}");
+    }
+}
+
+/// Completes at `deadline`, or never without one.
+async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,45 @@
+//! Recent command history
+//!
+//! Keeps the last few commands of a session, with the reply code each got, so
+//! an operator can see what sequence led up to an error. Only the verb is
+//! kept: arguments such as PASS's password never make it into the history.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Commands remembered per session
+pub const HISTORY_SIZE: usize = 20;
+
+/// Ring buffer of a session's most recent commands and their reply codes.
+#[derive(Debug, Default)]
+pub struct CommandHistory {
+    entries: VecDeque<String>,
+}
+
+impl CommandHistory {
+    /// Records `verb` with the code of its reply, dropping the oldest entry when full.
+    pub fn record(&mut self, verb: &str, reply: Option<&str>) {
+        if self.entries.len() == HISTORY_SIZE {
+            self.entries.pop_front();
+        }
+        let code = reply.and_then(|reply| reply.get(..3)).unwrap_or("---");
+        self.entries.push_back(format!("{verb} {code}"));
+    }
+
+    /// The recorded commands, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+}
+
+impl fmt::Display for CommandHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, entry) in self.entries().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(entry)?;
+        }
+        Ok(())
+    }
+}
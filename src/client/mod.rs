@@ -3,7 +3,11 @@
 //! Handles client connections, state management, and session lifecycle.
 
 pub mod handler;
+pub mod history;
+pub mod rate_limit;
 pub mod state;
 
 pub use handler::handle_client;
-pub use state::Client;
+pub use history::CommandHistory;
+pub use rate_limit::{CommandRateLimiter, ErrorCounter};
+pub use state::{Client, SessionStats};
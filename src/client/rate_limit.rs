@@ -0,0 +1,77 @@
+//! Control channel flood protection
+//!
+//! Tracks the arrival times of a client's most recent commands to catch tight
+//! command loops within a one-second sliding window, and counts error replies
+//! in a row to cut off clients probing for commands that work.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Sliding one-second window over a connection's command timestamps.
+#[derive(Debug)]
+pub struct CommandRateLimiter {
+    max_per_second: usize,
+    recent: VecDeque<Instant>,
+}
+
+impl CommandRateLimiter {
+    /// Creates a limiter allowing `max_per_second` commands in any one-second window.
+    pub fn new(max_per_second: usize) -> Self {
+        Self {
+            max_per_second,
+            recent: VecDeque::with_capacity(max_per_second),
+        }
+    }
+
+    /// Records a command arriving at `now`.
+    ///
+    /// Returns `false` if it exceeds the allowed rate.
+    pub fn check(&mut self, now: Instant) -> bool {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&oldest| now.duration_since(oldest) >= WINDOW)
+        {
+            self.recent.pop_front();
+        }
+
+        if self.recent.len() >= self.max_per_second {
+            return false;
+        }
+
+        self.recent.push_back(now);
+        true
+    }
+}
+
+/// Counts a connection's consecutive error replies.
+#[derive(Debug)]
+pub struct ErrorCounter {
+    max_consecutive: usize,
+    consecutive: usize,
+}
+
+impl ErrorCounter {
+    /// Creates a counter allowing fewer than `max_consecutive` errors in a row.
+    pub fn new(max_consecutive: usize) -> Self {
+        Self {
+            max_consecutive,
+            consecutive: 0,
+        }
+    }
+
+    /// Records the reply sent for a command; a 4xx or 5xx code counts as an
+    /// error and anything else resets the count.
+    ///
+    /// Returns `false` once the client has had too many errors in a row.
+    pub fn record(&mut self, reply: &str) -> bool {
+        if reply.starts_with(['4', '5']) {
+            self.consecutive += 1;
+        } else {
+            self.consecutive = 0;
+        }
+        self.consecutive < self.max_consecutive
+    }
+}
@@ -3,8 +3,95 @@
 //! Defines the `Client` struct and associated methods to manage FTP client state,
 //! including authentication status, connection address, and data channel initialization.
 
+use crate::client::CommandHistory;
 use crate::config::StartupConfig;
+use crate::server::hooks::Hooks;
+use crate::storage::FileMetadata;
+use crate::storage::facts::FactSet;
+use crate::storage::hash::HashAlgorithm;
+use crate::transfer::TransferMode;
+use crate::transfer::{CancelToken, TransferType};
+use std::fmt;
 use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a cached SIZE/MDTM lookup may be reused.
+///
+/// Other sessions can change the file at any time, so the cache only covers
+/// the burst of queries a client sends back-to-back before a transfer.
+const METADATA_CACHE_TTL: Duration = Duration::from_secs(2);
+
+/// Transfer statistics accumulated over the lifetime of a control connection.
+pub struct SessionStats {
+    started_at: Instant,
+    files_uploaded: u64,
+    files_downloaded: u64,
+    bytes_uploaded: u64,
+    bytes_downloaded: u64,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            files_uploaded: 0,
+            files_downloaded: 0,
+            bytes_uploaded: 0,
+            bytes_downloaded: 0,
+        }
+    }
+}
+
+impl SessionStats {
+    /// Returns the number of files uploaded during the session.
+    pub fn files_uploaded(&self) -> u64 {
+        self.files_uploaded
+    }
+
+    /// Returns the number of files downloaded during the session.
+    pub fn files_downloaded(&self) -> u64 {
+        self.files_downloaded
+    }
+
+    /// Returns the number of files transferred either way during the session.
+    pub fn files_transferred(&self) -> u64 {
+        self.files_uploaded + self.files_downloaded
+    }
+
+    /// Returns the total bytes received from the client over data connections.
+    pub fn bytes_uploaded(&self) -> u64 {
+        self.bytes_uploaded
+    }
+
+    /// Returns the total bytes sent to the client over data connections.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.bytes_downloaded
+    }
+
+    /// Returns when the control connection was opened.
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    /// Returns how long the session has been open.
+    pub fn duration(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+impl fmt::Display for SessionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} file(s) uploaded ({} bytes), {} file(s) downloaded ({} bytes), duration {}s",
+            self.files_uploaded,
+            self.bytes_uploaded,
+            self.files_downloaded,
+            self.bytes_downloaded,
+            self.duration().as_secs()
+        )
+    }
+}
 
 /// Represents the state of a connected FTP client.
 ///
@@ -16,7 +103,25 @@ pub struct Client {
     current_virtual_path: String,
     is_user_valid: bool,
     is_logged_in: bool,
+    needs_account: bool,
+    account: Option<String>,
     is_data_channel_init: bool,
+    epsv_all: bool,
+    utf8: bool,
+    transfer_type: TransferType,
+    transfer_mode: TransferMode,
+    mlst_facts: FactSet,
+    hash_algorithm: HashAlgorithm,
+    mdtm_millis: bool,
+    restart_offset: Option<u64>,
+    pending_mtime: Option<(String, SystemTime)>,
+    metadata_cache: Option<(Instant, FileMetadata)>,
+    storage_failures: u32,
+    failed_logins: usize,
+    transfer_cancel: CancelToken,
+    history: CommandHistory,
+    stats: SessionStats,
+    hooks: Hooks,
 }
 
 impl Default for Client {
@@ -27,7 +132,25 @@ impl Default for Client {
             current_virtual_path: "/".to_string(),
             is_user_valid: false,
             is_logged_in: false,
+            needs_account: false,
+            account: None,
             is_data_channel_init: false,
+            epsv_all: false,
+            utf8: false,
+            transfer_type: TransferType::default(),
+            transfer_mode: TransferMode::default(),
+            mlst_facts: FactSet::default(),
+            hash_algorithm: HashAlgorithm::default(),
+            mdtm_millis: false,
+            restart_offset: None,
+            pending_mtime: None,
+            metadata_cache: None,
+            storage_failures: 0,
+            failed_logins: 0,
+            transfer_cancel: CancelToken::default(),
+            history: CommandHistory::default(),
+            stats: SessionStats::default(),
+            hooks: Hooks::default(),
         }
     }
 }
@@ -48,12 +171,31 @@ impl Client {
             );
         }
 
-        self.username = None;
         self.client_addr = None;
+        self.reset_session();
+    }
+
+    /// Clears the login and all per-session settings, keeping the connection.
+    ///
+    /// Used when a logged-in client starts over with a new USER, as REIN would,
+    /// so the next login does not inherit the previous user's state.
+    pub fn reset_session(&mut self) {
+        self.username = None;
         self.current_virtual_path = "/".to_string();
         self.is_user_valid = false;
         self.is_logged_in = false;
+        self.needs_account = false;
+        self.account = None;
         self.is_data_channel_init = false;
+        self.epsv_all = false;
+        self.transfer_type = TransferType::default();
+        self.transfer_mode = TransferMode::default();
+        self.mlst_facts = FactSet::default();
+        self.hash_algorithm = HashAlgorithm::default();
+        self.mdtm_millis = false;
+        self.restart_offset = None;
+        self.pending_mtime = None;
+        self.metadata_cache = None;
     }
 
     // --------------------
@@ -67,6 +209,16 @@ impl Client {
         self.is_user_valid
     }
 
+    /// Returns whether the password was accepted but login still awaits ACCT.
+    pub fn needs_account(&self) -> bool {
+        self.needs_account
+    }
+
+    /// Returns the account supplied with ACCT, if any.
+    pub fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
     /// Returns whether the client has successfully logged in (passed authentication).
     pub fn is_logged_in(&self) -> bool {
         self.is_logged_in
@@ -77,6 +229,11 @@ impl Client {
         self.is_data_channel_init
     }
 
+    /// Returns whether the client sent `EPSV ALL`, restricting data channel setup to EPSV.
+    pub fn is_epsv_all(&self) -> bool {
+        self.epsv_all
+    }
+
     /// Returns the username of the client if set.
     pub fn username(&self) -> Option<&String> {
         self.username.as_ref()
@@ -92,6 +249,48 @@ impl Client {
         &self.current_virtual_path
     }
 
+    /// Returns the transfer statistics for this session.
+    pub fn session_stats(&self) -> &SessionStats {
+        &self.stats
+    }
+
+    /// Returns the transfer type negotiated with the TYPE command.
+    pub fn transfer_type(&self) -> TransferType {
+        self.transfer_type
+    }
+
+    /// Whether the client has turned on UTF-8 with `OPTS UTF8 ON`.
+    pub fn utf8(&self) -> bool {
+        self.utf8
+    }
+
+    /// Whether commands and names are exchanged as ISO-8859-1 rather than UTF-8.
+    ///
+    /// Only with `latin1_fallback` set, until the client turns UTF-8 on.
+    pub fn uses_latin1(&self, config: &StartupConfig) -> bool {
+        config.latin1_fallback && !self.utf8
+    }
+
+    /// Returns the transfer mode (MODE S or Z) for the data connection.
+    pub fn transfer_mode(&self) -> TransferMode {
+        self.transfer_mode
+    }
+
+    /// Returns the facts selected with `OPTS MLST` for MLSD and MLST replies.
+    pub fn mlst_facts(&self) -> FactSet {
+        self.mlst_facts
+    }
+
+    /// Returns the algorithm selected with `OPTS HASH` for HASH replies.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Whether MDTM replies include milliseconds, chosen with `OPTS MDTM`.
+    pub fn mdtm_millis(&self) -> bool {
+        self.mdtm_millis
+    }
+
     // --------------------
     // Setter methods
     // --------------------
@@ -103,6 +302,16 @@ impl Client {
         self.is_user_valid = valid;
     }
 
+    /// Sets whether login is waiting for the client to send ACCT.
+    pub fn set_needs_account(&mut self, needs_account: bool) {
+        self.needs_account = needs_account;
+    }
+
+    /// Sets the account supplied with ACCT.
+    pub fn set_account(&mut self, account: Option<String>) {
+        self.account = account;
+    }
+
     /// Sets the login state of the client.
     pub fn set_logged_in(&mut self, logged_in: bool) {
         if logged_in && !self.is_logged_in {
@@ -124,6 +333,151 @@ impl Client {
         self.is_data_channel_init = init;
     }
 
+    /// Records that the client sent `EPSV ALL`; it stays in effect until logout.
+    pub fn set_epsv_all(&mut self) {
+        self.epsv_all = true;
+    }
+
+    /// Sets the transfer type used for subsequent file transfers.
+    pub fn set_transfer_type(&mut self, transfer_type: TransferType) {
+        self.transfer_type = transfer_type;
+    }
+
+    /// Records the client's `OPTS UTF8` choice, which outlives a change of user.
+    pub fn set_utf8(&mut self, utf8: bool) {
+        self.utf8 = utf8;
+    }
+
+    /// Sets the transfer mode for subsequent transfers and listings.
+    pub fn set_transfer_mode(&mut self, transfer_mode: TransferMode) {
+        self.transfer_mode = transfer_mode;
+    }
+
+    /// Selects the facts included in MLSD and MLST replies.
+    pub fn set_mlst_facts(&mut self, facts: FactSet) {
+        self.mlst_facts = facts;
+    }
+
+    /// Selects the algorithm HASH uses.
+    pub fn set_hash_algorithm(&mut self, algorithm: HashAlgorithm) {
+        self.hash_algorithm = algorithm;
+    }
+
+    /// Selects whether MDTM replies include milliseconds.
+    pub fn set_mdtm_millis(&mut self, millis: bool) {
+        self.mdtm_millis = millis;
+    }
+
+    /// Sets the restart offset requested by REST for the next transfer.
+    pub fn set_restart_offset(&mut self, offset: Option<u64>) {
+        self.restart_offset = offset;
+    }
+
+    /// Takes the pending restart offset, clearing it for subsequent transfers.
+    pub fn take_restart_offset(&mut self) -> Option<u64> {
+        self.restart_offset.take()
+    }
+
+    /// Remembers a modification time to apply once `virtual_path` is stored.
+    ///
+    /// Only one pending time is kept; a later call replaces it.
+    pub fn set_pending_mtime(&mut self, virtual_path: String, modified: SystemTime) {
+        self.pending_mtime = Some((virtual_path, modified));
+    }
+
+    /// Takes the pending modification time if it was set for `virtual_path`.
+    pub fn take_pending_mtime(&mut self, virtual_path: &str) -> Option<SystemTime> {
+        match &self.pending_mtime {
+            Some((path, _)) if path == virtual_path => self.pending_mtime.take().map(|(_, t)| t),
+            _ => None,
+        }
+    }
+
+    /// Returns the cached metadata for `virtual_path` if it is still fresh.
+    pub fn cached_metadata(&self, virtual_path: &str) -> Option<&FileMetadata> {
+        match &self.metadata_cache {
+            Some((cached_at, metadata))
+                if metadata.virtual_path == virtual_path
+                    && cached_at.elapsed() < METADATA_CACHE_TTL =>
+            {
+                Some(metadata)
+            }
+            _ => None,
+        }
+    }
+
+    /// Remembers the metadata of the most recently queried file.
+    pub fn cache_metadata(&mut self, metadata: FileMetadata) {
+        self.metadata_cache = Some((Instant::now(), metadata));
+    }
+
+    /// Drops any cached file metadata, e.g. after a write command.
+    pub fn invalidate_metadata_cache(&mut self) {
+        self.metadata_cache = None;
+    }
+
+    /// Returns how many storage operations in a row failed with the backend unreachable.
+    pub fn storage_failures(&self) -> u32 {
+        self.storage_failures
+    }
+
+    /// Counts a storage operation that failed with the backend unreachable, returning the run length.
+    pub fn record_storage_failure(&mut self) -> u32 {
+        self.storage_failures += 1;
+        self.storage_failures
+    }
+
+    /// Resets the run of storage backend failures after a command that did not hit one.
+    pub fn clear_storage_failures(&mut self) {
+        self.storage_failures = 0;
+    }
+
+    /// Counts a refused USER or PASS, returning how many this connection has had.
+    ///
+    /// Kept across logins and logouts, so logging in doesn't buy more attempts.
+    pub fn record_failed_login(&mut self) -> usize {
+        self.failed_logins += 1;
+        self.failed_logins
+    }
+
+    /// Returns the token that aborts this connection's transfers when the control connection is lost.
+    pub fn transfer_cancel(&self) -> &CancelToken {
+        &self.transfer_cancel
+    }
+
+    /// Returns the embedder hooks registered on the server this client connected to.
+    pub fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    /// Returns the connection's most recent commands, kept across logins.
+    pub fn history(&self) -> &CommandHistory {
+        &self.history
+    }
+
+    /// Returns when the session reaches its time limit, if the current user has one.
+    pub fn session_deadline(&self, config: &StartupConfig) -> Option<Instant> {
+        let limit = config.max_session_duration(self.username.as_deref())?;
+        Some(self.stats.started_at + limit)
+    }
+
+    /// Records a completed upload of `bytes` bytes.
+    pub fn record_upload(&mut self, bytes: u64) {
+        self.stats.files_uploaded += 1;
+        self.stats.bytes_uploaded += bytes;
+    }
+
+    /// Records a completed download of `bytes` bytes.
+    pub fn record_download(&mut self, bytes: u64) {
+        self.stats.files_downloaded += 1;
+        self.stats.bytes_downloaded += bytes;
+    }
+
+    /// Adds a command verb and its reply to the history.
+    pub fn record_command(&mut self, verb: &str, reply: Option<&str>) {
+        self.history.record(verb, reply);
+    }
+
     /// Sets the username of the client with validation
     pub fn set_username(
         &mut self,
@@ -165,6 +519,11 @@ impl Client {
         self.client_addr = addr;
     }
 
+    /// Sets the embedder hooks the session runs, such as the upload hook.
+    pub fn set_hooks(&mut self, hooks: Hooks) {
+        self.hooks = hooks;
+    }
+
     /// Sets the current virtual path of the client.
     /// Sets the current virtual path of the client with validation
     pub fn set_current_virtual_path(&mut self, path: String) -> Result<(), String> {
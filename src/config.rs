@@ -3,9 +3,15 @@
 //! Separates startup configuration (requires restart) from runtime configuration
 //! (can be updated via server terminal commands).
 
+use crate::auth::{AuthBackend, ChainAuthenticator, PasswordPolicy, SharedAuthenticator};
+use crate::logging::LogFormat;
+use crate::storage::ListTimeZone;
+use crate::storage::validation::{StorageRoot, validate_path, validate_path_component};
+use crate::transfer::TransferDirection;
 use config::{Config, Environment, File};
-use serde::Deserialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -21,7 +27,7 @@ pub struct ServerConfig {
 
 /// Configuration that requires server restart to take effect
 /// These values are loaded once during server initialization
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct StartupConfig {
     // ═══ NETWORK INFRASTRUCTURE (Environment Override Supported) ═══
     /// IP address to bind the FTP control connection (restart required)
@@ -34,9 +40,45 @@ pub struct StartupConfig {
     pub data_port_min: u16,
     pub data_port_max: u16,
 
+    /// Local port for outgoing active-mode data connections, unset lets the OS choose (restart required)
+    pub active_source_port: Option<u16>,
+
+    /// Maximum simultaneous PASV listeners, defaults to the data port range size (restart required)
+    pub max_passive_listeners: Option<usize>,
+
+    /// Seconds a PASV listener may go unused before it is reaped to make room at max_passive_listeners (restart required)
+    #[serde(default = "default_passive_listener_idle_secs")]
+    pub passive_listener_idle_secs: u64,
+
+    /// Maximum simultaneous RETR/STOR transfers across all clients, unset for no cap (restart required)
+    pub max_concurrent_transfers: Option<usize>,
+
+    /// How long a transfer waits for a free slot before getting 450, 0 to refuse at once (restart required)
+    #[serde(default)]
+    pub transfer_queue_timeout_secs: u64,
+
     /// Root directory for FTP operations (restart required)
     pub server_root: String,
 
+    /// Create `server_root` at startup if it doesn't exist yet (restart required)
+    #[serde(default)]
+    pub auto_create_root: bool,
+
+    /// Directory for partial uploads, instead of next to the destination (restart required)
+    pub temp_upload_dir: Option<String>,
+
+    /// Permission bits cleared on files created by STOR and directories created by MKD,
+    /// e.g. `0o002` for group-writable uploads; Unix only (restart required)
+    pub umask: Option<u32>,
+
+    /// Add the new directory's permission bits to MKD replies, to check the umask (restart required)
+    #[serde(default)]
+    pub mkd_reports_mode: bool,
+
+    /// Create a user's `home` directory at login if it doesn't exist yet (restart required)
+    #[serde(default)]
+    pub auto_create_home: bool,
+
     // ═══ INTERNAL BEHAVIOR (TOML Only) ═══
     /// Buffer size for file transfers (restart required)
     pub buffer_size: usize,
@@ -44,21 +86,186 @@ pub struct StartupConfig {
     /// Connection timeout for data channels (restart required)
     pub connection_timeout_secs: u64,
 
-    /// Maximum retry attempts (restart required)
+    /// Set SO_REUSEADDR on the control listener (restart required)
+    #[serde(default = "default_reuse_address")]
+    pub reuse_address: bool,
+
+    /// Pending connection backlog for the control listener (restart required)
+    #[serde(default = "default_listen_backlog")]
+    pub listen_backlog: u32,
+
+    /// Enable TCP keepalive on accepted control connections (restart required)
+    #[serde(default = "default_tcp_keepalive")]
+    pub tcp_keepalive: bool,
+
+    /// Idle time before the first keepalive probe is sent (restart required)
+    #[serde(default = "default_tcp_keepalive_idle_secs")]
+    pub tcp_keepalive_idle_secs: u64,
+
+    /// Time between unanswered keepalive probes (restart required)
+    #[serde(default = "default_tcp_keepalive_interval_secs")]
+    pub tcp_keepalive_interval_secs: u64,
+
+    /// Retries of a transient storage or data connection failure (restart required)
     pub max_retries: usize,
 
     /// Maximum FTP command length (restart required)
     pub max_command_length: usize,
 
+    /// Close sessions this long after connecting, however active (restart required)
+    pub max_session_duration_secs: Option<u64>,
+
+    /// Maximum commands a client may send within one second (restart required)
+    #[serde(default = "default_max_commands_per_second")]
+    pub max_commands_per_second: usize,
+
+    /// Answer blank lines with 200 instead of ignoring them (restart required)
+    #[serde(default)]
+    pub answer_empty_lines: bool,
+
+    /// Files a session may upload and download in total, 0 for no limit (restart required)
+    #[serde(default)]
+    pub max_transfers_per_session: usize,
+
+    /// Refused USER and PASS commands after which a connection is closed, 0 for no limit (restart required)
+    #[serde(default = "default_max_login_attempts")]
+    pub max_login_attempts: usize,
+
+    /// Consecutive error replies after which a client is disconnected (restart required)
+    #[serde(default = "default_max_consecutive_errors")]
+    pub max_consecutive_errors: usize,
+
     /// Security limits (restart required)
     pub max_directory_depth: usize,
     pub max_username_length: usize,
     pub min_client_port: u16,
+
+    /// Ports refused as PORT targets besides the server's own (restart required)
+    #[serde(default)]
+    pub denied_data_ports: Vec<u16>,
+
+    /// Allow data connections to or from a host other than the control peer (restart required)
+    #[serde(default)]
+    pub allow_fxp: bool,
+
+    /// Leave the software name and version out of the greeting, SYST, STAT and FEAT (restart required)
+    #[serde(default)]
+    pub hide_server_identity: bool,
+
+    /// Treat `*` and `?` in the last component of a LIST or NLST argument as wildcards (restart required)
+    #[serde(default)]
+    pub list_wildcards: bool,
+
+    /// Time zone LIST and STAT show modification times in, `utc` or `local` (restart required)
+    #[serde(default)]
+    pub list_time_zone: ListTimeZone,
+
+    /// Cut directory listings off after this many entries; unset means no limit (restart required)
+    pub max_listing_entries: Option<usize>,
+
+    /// Most entries `STAT <path>` lists on the control connection (restart required)
+    #[serde(default = "default_max_stat_entries")]
+    pub max_stat_entries: usize,
+
+    /// Accept MODE Z (deflate-compressed transfers); needs the `mode-z` feature (restart required)
+    #[serde(default)]
+    pub mode_z: bool,
+
+    /// Decode commands as ISO-8859-1 and encode names in replies and listings the
+    /// same way for clients that haven't sent `OPTS UTF8 ON` (restart required)
+    #[serde(default)]
+    pub latin1_fallback: bool,
+
+    /// File whose lines are sent ahead of the 220 greeting, read once at startup (restart required)
+    pub banner_file: Option<String>,
+
+    /// Contents of `banner_file`, filled in by `load_banner`
+    #[serde(skip)]
+    pub banner: Option<String>,
+
+    /// Refuse write commands (STOR, DELE, MKD, RMD, MFMT) for every user (restart required)
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// Reply code sent when a write command is refused by read-only mode (restart required)
+    #[serde(default = "default_read_only_reply_code")]
+    pub read_only_reply_code: u16,
+
+    /// Commands refused with 502 and left out of HELP and FEAT, e.g. `["DELE"]` (restart required)
+    #[serde(default)]
+    pub disabled_commands: Vec<String>,
+
+    /// Log output format, `text` or `json` (restart required)
+    #[serde(default)]
+    pub log_format: LogFormat,
+
+    /// Accept `USER anonymous` with any password (restart required)
+    #[serde(default)]
+    pub anonymous_login: bool,
+
+    /// Log the password, conventionally an email address, given by anonymous users (restart required)
+    #[serde(default)]
+    pub log_anonymous_passwords: bool,
+
+    /// Credential backends, tried in order until one accepts a login (restart required)
+    #[serde(default = "default_auth_backends")]
+    pub auth_backends: Vec<AuthBackend>,
+
+    /// File of `username:password` lines used by the `file` backend, read at startup and on reload
+    pub credentials_file: Option<String>,
+
+    /// The chain built from `auth_backends`, filled in by `load_authenticator`
+    #[serde(skip)]
+    pub authenticator: SharedAuthenticator,
+
+    /// Password policy enforced when adding accounts with `add-user`
+    #[serde(default)]
+    pub password_policy: PasswordPolicy,
+
+    /// Physical directories mounted at top-level virtual paths, e.g. `/public` (restart required)
+    #[serde(default)]
+    pub mounts: HashMap<String, String>,
+
+    /// Per-user overrides keyed by username (restart required)
+    #[serde(default)]
+    pub users: HashMap<String, UserConfig>,
+}
+
+/// Settings that override the server-wide defaults for a single user
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct UserConfig {
+    /// Maximum directory depth for this user, instead of `max_directory_depth`
+    pub max_directory_depth: Option<usize>,
+
+    /// Account the user must supply with ACCT before login completes
+    pub account: Option<String>,
+
+    /// Whether the user may keep working while the server is in maintenance mode
+    pub admin: bool,
+
+    /// Upload rate cap in KiB/s, shared by all of the user's transfers
+    pub max_upload_kib_per_sec: Option<u64>,
+
+    /// Download rate cap in KiB/s, shared by all of the user's transfers
+    pub max_download_kib_per_sec: Option<u64>,
+
+    /// Refuse write commands for this user, instead of `read_only`
+    pub read_only: Option<bool>,
+
+    /// Session time limit in seconds for this user, instead of `max_session_duration_secs`
+    pub max_session_duration_secs: Option<u64>,
+
+    /// Files this user may transfer per session, instead of `max_transfers_per_session`
+    pub max_transfers_per_session: Option<usize>,
+
+    /// Virtual directory the user starts in after logging in, e.g. `/home/alice`
+    pub home: Option<String>,
 }
 
 /// Configuration that can be updated at runtime via terminal commands
 /// These values can be changed while the server is running
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct RuntimeConfig {
     /// Maximum concurrent clients (runtime updatable)
     /// Environment: RAX_FTP_MAX_CLIENTS
@@ -67,11 +274,94 @@ pub struct RuntimeConfig {
     /// Maximum file upload size in MB (runtime updatable)  
     /// Environment: RAX_FTP_MAX_FILE_SIZE_MB
     pub max_file_size_mb: u64,
+
+    /// Reject non-admin users with a 421 reply (runtime updatable)
+    #[serde(default)]
+    pub maintenance_mode: bool,
+
+    /// Text of the 421 reply sent while in maintenance mode (runtime updatable)
+    #[serde(default = "default_maintenance_message")]
+    pub maintenance_message: String,
+}
+
+fn default_read_only_reply_code() -> u16 {
+    550
+}
+
+fn default_passive_listener_idle_secs() -> u64 {
+    60
+}
+
+fn default_reuse_address() -> bool {
+    true
+}
+
+fn default_listen_backlog() -> u32 {
+    1024
+}
+
+fn default_tcp_keepalive() -> bool {
+    true
+}
+
+fn default_tcp_keepalive_idle_secs() -> u64 {
+    60
+}
+
+fn default_tcp_keepalive_interval_secs() -> u64 {
+    15
+}
+
+fn default_auth_backends() -> Vec<AuthBackend> {
+    vec![AuthBackend::Builtin]
+}
+
+fn default_maintenance_message() -> String {
+    "Server in maintenance mode".into()
+}
+
+fn default_max_commands_per_second() -> usize {
+    50
+}
+
+fn default_max_login_attempts() -> usize {
+    3
+}
+
+fn default_max_consecutive_errors() -> usize {
+    10
+}
+
+fn default_max_stat_entries() -> usize {
+    100
 }
 
 /// Thread-safe runtime configuration wrapper
 pub type SharedRuntimeConfig = Arc<RwLock<RuntimeConfig>>;
 
+/// The settings a configuration reload found changed, by field name
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigChanges {
+    /// Startup settings that differ; they only take effect after a restart
+    pub restart_required: Vec<String>,
+    /// Runtime settings that were applied in place
+    pub applied: Vec<String>,
+}
+
+/// Returns the names of the top-level fields that differ between `old` and `new`,
+/// in name order.
+pub fn changed_fields<T: Serialize>(old: &T, new: &T) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    new.into_iter()
+        .filter(|(name, value)| old.get(name) != Some(value))
+        .map(|(name, _)| name)
+        .collect()
+}
+
 impl ServerConfig {
     /// Load configuration from config.toml with environment overrides
     pub fn load() -> Result<Self, config::ConfigError> {
@@ -101,10 +391,10 @@ impl ServerConfig {
             }
         }
 
-        // If both paths failed, panic with clear message
-        panic!(
+        // If both paths failed, report a clear message
+        Err(config::ConfigError::Message(format!(
             "Failed to load config.toml from any location. Tried: {config_paths:?}. Last error: {last_error:?}"
-        );
+        )))
     }
 
     /// Split into startup (immutable) and runtime (mutable) parts
@@ -114,7 +404,7 @@ impl ServerConfig {
     }
 
     /// Validation for all configuration values
-    fn validate(&self) -> Result<(), config::ConfigError> {
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
         // Validate startup config
         if self.startup.control_port == 0 {
             return Err(config::ConfigError::Message(
@@ -134,6 +424,175 @@ impl ServerConfig {
             ));
         }
 
+        if let Some(port) = self.startup.active_source_port
+            && (port == self.startup.control_port || self.startup.data_port_range().contains(&port))
+        {
+            return Err(config::ConfigError::Message(
+                "active_source_port must differ from control_port and the data port range".into(),
+            ));
+        }
+
+        if self.startup.max_passive_listeners == Some(0) {
+            return Err(config::ConfigError::Message(
+                "max_passive_listeners must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.max_concurrent_transfers == Some(0) {
+            return Err(config::ConfigError::Message(
+                "max_concurrent_transfers must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.max_session_duration_secs == Some(0)
+            || self
+                .startup
+                .users
+                .values()
+                .any(|user| user.max_session_duration_secs == Some(0))
+        {
+            return Err(config::ConfigError::Message(
+                "max_session_duration_secs must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.max_listing_entries == Some(0) {
+            return Err(config::ConfigError::Message(
+                "max_listing_entries must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.max_stat_entries == 0 {
+            return Err(config::ConfigError::Message(
+                "max_stat_entries must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.max_commands_per_second == 0 {
+            return Err(config::ConfigError::Message(
+                "max_commands_per_second must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.max_consecutive_errors == 0 {
+            return Err(config::ConfigError::Message(
+                "max_consecutive_errors must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.listen_backlog == 0 {
+            return Err(config::ConfigError::Message(
+                "listen_backlog must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.tcp_keepalive
+            && (self.startup.tcp_keepalive_idle_secs == 0
+                || self.startup.tcp_keepalive_interval_secs == 0)
+        {
+            return Err(config::ConfigError::Message(
+                "tcp_keepalive_idle_secs and tcp_keepalive_interval_secs must be greater than 0"
+                    .into(),
+            ));
+        }
+
+        if self.startup.auth_backends.is_empty() {
+            return Err(config::ConfigError::Message(
+                "auth_backends must list at least one backend".into(),
+            ));
+        }
+
+        if self.startup.auth_backends.contains(&AuthBackend::File)
+            && self.startup.credentials_file.is_none()
+        {
+            return Err(config::ConfigError::Message(
+                "credentials_file is required when auth_backends includes \"file\"".into(),
+            ));
+        }
+
+        if self.startup.users.values().any(|user| {
+            user.max_upload_kib_per_sec == Some(0) || user.max_download_kib_per_sec == Some(0)
+        }) {
+            return Err(config::ConfigError::Message(
+                "per-user bandwidth caps must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.password_policy.min_length == 0 {
+            return Err(config::ConfigError::Message(
+                "password_policy.min_length must be greater than 0".into(),
+            ));
+        }
+
+        if self.startup.temp_upload_dir.as_deref() == Some("") {
+            return Err(config::ConfigError::Message(
+                "temp_upload_dir cannot be empty".into(),
+            ));
+        }
+
+        if !(400..600).contains(&self.startup.read_only_reply_code) {
+            return Err(config::ConfigError::Message(
+                "read_only_reply_code must be a 4xx or 5xx reply code".into(),
+            ));
+        }
+
+        if self.startup.mode_z && !cfg!(feature = "mode-z") {
+            return Err(config::ConfigError::Message(
+                "mode_z requires the server to be built with the mode-z feature".into(),
+            ));
+        }
+
+        for verb in &self.startup.disabled_commands {
+            if !crate::protocol::dispatch::command_verbs()
+                .any(|known| known.eq_ignore_ascii_case(verb))
+            {
+                return Err(config::ConfigError::Message(format!(
+                    "disabled_commands: unknown command {verb}"
+                )));
+            }
+            if ["USER", "PASS", "QUIT"]
+                .iter()
+                .any(|essential| essential.eq_ignore_ascii_case(verb))
+            {
+                return Err(config::ConfigError::Message(format!(
+                    "disabled_commands: {verb} cannot be disabled"
+                )));
+            }
+        }
+
+        if self.startup.umask.is_some_and(|umask| umask > 0o777) {
+            return Err(config::ConfigError::Message(
+                "umask must be between 0o000 and 0o777".into(),
+            ));
+        }
+
+        for (username, user) in &self.startup.users {
+            if let Some(home) = &user.home
+                && (!home.starts_with('/')
+                    || validate_path(home, self.startup.max_directory_depth_for(Some(username)))
+                        .as_ref()
+                        != Ok(home))
+            {
+                return Err(config::ConfigError::Message(format!(
+                    "users.{username}.home must be a normalized absolute path such as \"/home/{username}\""
+                )));
+            }
+        }
+
+        for (virtual_path, real_path) in &self.startup.mounts {
+            let name = virtual_path.strip_prefix('/').unwrap_or_default();
+            if name.contains('/') || validate_path_component(name).is_err() {
+                return Err(config::ConfigError::Message(format!(
+                    "mount point {virtual_path:?} must be a top-level directory such as \"/public\""
+                )));
+            }
+            if real_path.is_empty() {
+                return Err(config::ConfigError::Message(format!(
+                    "mount point {virtual_path:?} has an empty path"
+                )));
+            }
+        }
+
         if self.startup.server_root.is_empty() {
             return Err(config::ConfigError::Message(
                 "server_root cannot be empty".into(),
@@ -153,6 +612,12 @@ impl ServerConfig {
             ));
         }
 
+        if self.runtime.maintenance_message.contains(['\r', '\n']) {
+            return Err(config::ConfigError::Message(
+                "maintenance_message must be a single line".into(),
+            ));
+        }
+
         Ok(())
     }
 }
@@ -168,16 +633,171 @@ impl StartupConfig {
         self.data_port_min..self.data_port_max
     }
 
+    /// Get the effective cap on simultaneous PASV listeners
+    pub fn max_passive_listeners(&self) -> usize {
+        self.max_passive_listeners
+            .unwrap_or_else(|| self.data_port_range().len())
+    }
+
+    /// Get how long a PASV listener may go unused before it can be reaped
+    pub fn passive_listener_idle(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.passive_listener_idle_secs)
+    }
+
+    /// Get how long a transfer may queue for a free slot
+    pub fn transfer_queue_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.transfer_queue_timeout_secs)
+    }
+
+    /// Get the directory depth limit for a user, applying any per-user override
+    pub fn max_directory_depth_for(&self, username: Option<&str>) -> usize {
+        username
+            .and_then(|name| self.users.get(name))
+            .and_then(|user| user.max_directory_depth)
+            .unwrap_or(self.max_directory_depth)
+    }
+
+    /// Get the account a user must supply with ACCT, if any
+    pub fn required_account(&self, username: &str) -> Option<&str> {
+        self.users
+            .get(username)
+            .and_then(|user| user.account.as_deref())
+    }
+
+    /// Get the virtual directory a user starts in, if one is configured
+    pub fn home_directory(&self, username: &str) -> Option<&str> {
+        self.users
+            .get(username)
+            .and_then(|user| user.home.as_deref())
+    }
+
+    /// Check whether write commands are refused for a user, applying any per-user override
+    pub fn is_read_only(&self, username: Option<&str>) -> bool {
+        username
+            .and_then(|name| self.users.get(name))
+            .and_then(|user| user.read_only)
+            .unwrap_or(self.read_only)
+    }
+
+    /// Check whether a command verb (as sent on the wire, e.g. `DELE`) is disabled
+    pub fn is_command_disabled(&self, verb: &str) -> bool {
+        self.disabled_commands
+            .iter()
+            .any(|disabled| disabled.eq_ignore_ascii_case(verb))
+    }
+
+    /// Check whether a user is flagged as an admin
+    pub fn is_admin(&self, username: &str) -> bool {
+        self.users.get(username).is_some_and(|user| user.admin)
+    }
+
+    /// Get a user's bandwidth cap for one direction in bytes per second, if any
+    pub fn bandwidth_limit(&self, username: &str, direction: TransferDirection) -> Option<u64> {
+        let user = self.users.get(username)?;
+        let kib_per_sec = match direction {
+            TransferDirection::Upload => user.max_upload_kib_per_sec,
+            TransferDirection::Download => user.max_download_kib_per_sec,
+        }?;
+        Some(kib_per_sec * 1024)
+    }
+
+    /// Get the session time limit for a user, falling back to the server-wide limit
+    pub fn max_session_duration(&self, username: Option<&str>) -> Option<std::time::Duration> {
+        username
+            .and_then(|name| self.users.get(name))
+            .and_then(|user| user.max_session_duration_secs)
+            .or(self.max_session_duration_secs)
+            .map(std::time::Duration::from_secs)
+    }
+
+    /// Get how many files a session may transfer, applying any per-user override;
+    /// `None` means unlimited
+    pub fn max_transfers_per_session(&self, username: Option<&str>) -> Option<usize> {
+        let limit = username
+            .and_then(|name| self.users.get(name))
+            .and_then(|user| user.max_transfers_per_session)
+            .unwrap_or(self.max_transfers_per_session);
+        (limit > 0).then_some(limit)
+    }
+
     /// Get server root as PathBuf
     pub fn server_root_path(&self) -> PathBuf {
         PathBuf::from(&self.server_root)
     }
 
+    /// Replace `server_root` with its canonical, absolute form, so a relative
+    /// root doesn't depend on where the process was started.
+    ///
+    /// A missing root is an error, so a mistyped path isn't quietly served as
+    /// an empty tree, unless `auto_create_root` is set.
+    pub fn resolve_server_root(&mut self) -> std::io::Result<()> {
+        if !Path::new(&self.server_root).exists() {
+            if !self.auto_create_root {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "directory does not exist; create it or set auto_create_root = true",
+                ));
+            }
+            std::fs::create_dir_all(&self.server_root)?;
+        }
+        let resolved = std::fs::canonicalize(&self.server_root)?;
+        self.server_root = resolved
+            .into_os_string()
+            .into_string()
+            .map_err(|_| std::io::Error::other("resolved path is not valid UTF-8"))?;
+        Ok(())
+    }
+
+    /// Get the server root together with its mount points
+    pub fn storage_root(&self) -> StorageRoot {
+        StorageRoot::new(&self.server_root_path())
+            .with_mounts(
+                self.mounts
+                    .iter()
+                    .map(|(virtual_path, real_path)| (virtual_path.as_str(), Path::new(real_path))),
+            )
+            .with_max_retries(self.max_retries)
+    }
+
+    /// Get the directory for partial uploads, if one is configured
+    pub fn temp_upload_dir_path(&self) -> Option<PathBuf> {
+        self.temp_upload_dir.as_ref().map(PathBuf::from)
+    }
+
     /// Get server root as string (backward compatibility)
     pub fn server_root_str(&self) -> String {
         self.server_root.clone()
     }
 
+    /// Get the keepalive settings for control connections, or `None` if disabled
+    pub fn tcp_keepalive(&self) -> Option<socket2::TcpKeepalive> {
+        self.tcp_keepalive.then(|| {
+            socket2::TcpKeepalive::new()
+                .with_time(std::time::Duration::from_secs(self.tcp_keepalive_idle_secs))
+                .with_interval(std::time::Duration::from_secs(
+                    self.tcp_keepalive_interval_secs,
+                ))
+        })
+    }
+
+    /// Read `banner_file` into memory, clearing the banner if the file can't be read
+    pub fn load_banner(&mut self) -> std::io::Result<()> {
+        self.banner = None;
+        if let Some(path) = &self.banner_file {
+            self.banner = Some(std::fs::read_to_string(path)?);
+        }
+        Ok(())
+    }
+
+    /// Build the credential chain from `auth_backends`, replacing any earlier one
+    ///
+    /// On failure the chain in use, if any, is kept.
+    pub fn load_authenticator(&self) -> std::io::Result<()> {
+        self.authenticator
+            .replace(ChainAuthenticator::from_config(self)?);
+        Ok(())
+    }
+
     /// Get connection timeout as Duration
     pub fn connection_timeout(&self) -> std::time::Duration {
         std::time::Duration::from_secs(self.connection_timeout_secs)
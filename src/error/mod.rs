@@ -5,4 +5,6 @@
 pub mod handlers;
 pub mod types;
 
-pub use types::{AuthError, NavigateError, StorageError, TransferError};
+pub use types::{
+    AuthError, NavigateError, ProvisioningError, ServerError, StorageError, TransferError,
+};
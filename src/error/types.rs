@@ -13,6 +13,9 @@ pub enum AuthError {
     InvalidPassword(String),
     UserNotFound(String),
     MalformedInput(String),
+    WeakPassword(String),
+    /// `USER anonymous` while `anonymous_login` is off
+    AnonymousDisabled,
 }
 
 impl fmt::Display for AuthError {
@@ -22,12 +25,52 @@ impl fmt::Display for AuthError {
             AuthError::InvalidPassword(u) => write!(f, "Invalid password for user: {u}"),
             AuthError::UserNotFound(u) => write!(f, "User not found: {u}"),
             AuthError::MalformedInput(s) => write!(f, "Malformed input: {s}"),
+            AuthError::WeakPassword(s) => write!(f, "Password too weak: {s}"),
+            AuthError::AnonymousDisabled => write!(f, "Anonymous access not allowed"),
         }
     }
 }
 
 impl std::error::Error for AuthError {}
 
+/// Errors adding an account with `auth::add_user`
+#[derive(Debug)]
+pub enum ProvisioningError {
+    /// The username or password was refused
+    Rejected(AuthError),
+    /// The credentials file already has an account with this name
+    UserExists(String),
+    /// The password could not be hashed
+    HashFailed(String),
+    /// The credentials file could not be read or written
+    IoError(io::Error),
+}
+
+impl fmt::Display for ProvisioningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProvisioningError::Rejected(e) => write!(f, "{e}"),
+            ProvisioningError::UserExists(u) => write!(f, "User already exists: {u}"),
+            ProvisioningError::HashFailed(e) => write!(f, "Failed to hash password: {e}"),
+            ProvisioningError::IoError(e) => write!(f, "Credentials file error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProvisioningError {}
+
+impl From<AuthError> for ProvisioningError {
+    fn from(error: AuthError) -> Self {
+        ProvisioningError::Rejected(error)
+    }
+}
+
+impl From<io::Error> for ProvisioningError {
+    fn from(error: io::Error) -> Self {
+        ProvisioningError::IoError(error)
+    }
+}
+
 /// Storage module errors
 #[derive(Debug)]
 pub enum StorageError {
@@ -36,7 +79,12 @@ pub enum StorageError {
     InvalidPath(String),
     FileAlreadyExists(String),
     NotADirectory(String),
+    /// A file was expected but the path is a directory or other non-regular file
+    NotAFile(String),
+    DirectoryNotEmpty(String),
     PermissionDenied(String),
+    /// The real path is longer than the OS allows, though within the depth limit
+    PathTooLong,
     IoError(io::Error),
     UploadInProgress(String),
 }
@@ -49,7 +97,10 @@ impl fmt::Display for StorageError {
             StorageError::InvalidPath(p) => write!(f, "Invalid path: {p}"),
             StorageError::FileAlreadyExists(p) => write!(f, "File already exists: {p}"),
             StorageError::NotADirectory(p) => write!(f, "Not a directory: {p}"),
+            StorageError::NotAFile(p) => write!(f, "Not a regular file: {p}"),
+            StorageError::DirectoryNotEmpty(p) => write!(f, "Directory not empty: {p}"),
             StorageError::PermissionDenied(p) => write!(f, "Permission denied: {p}"),
+            StorageError::PathTooLong => write!(f, "Path too long"),
             StorageError::IoError(e) => write!(f, "IO error: {e}"),
             StorageError::UploadInProgress(p) => write!(f, "Upload already in progress: {p}"),
         }
@@ -58,9 +109,33 @@ impl fmt::Display for StorageError {
 
 impl std::error::Error for StorageError {}
 
+impl StorageError {
+    /// Whether the error suggests the storage backend itself is gone, such as
+    /// a network mount that dropped, rather than a problem with one path.
+    pub fn is_backend_unavailable(&self) -> bool {
+        let StorageError::IoError(error) = self else {
+            return false;
+        };
+
+        // EIO has no ErrorKind of its own; its errno is 5 on every Unix
+        matches!(
+            error.kind(),
+            io::ErrorKind::NotConnected
+                | io::ErrorKind::StaleNetworkFileHandle
+                | io::ErrorKind::NetworkDown
+                | io::ErrorKind::NetworkUnreachable
+                | io::ErrorKind::HostUnreachable
+        ) || (cfg!(unix) && error.raw_os_error() == Some(5))
+    }
+}
+
 impl From<io::Error> for StorageError {
     fn from(error: io::Error) -> Self {
-        StorageError::IoError(error)
+        // ENAMETOOLONG (and its Windows counterpart) surfaces as InvalidFilename
+        match error.kind() {
+            io::ErrorKind::InvalidFilename => StorageError::PathTooLong,
+            _ => StorageError::IoError(error),
+        }
     }
 }
 
@@ -69,12 +144,15 @@ impl From<io::Error> for StorageError {
 pub enum TransferError {
     PortBindingFailed(SocketAddr, io::Error),
     NoAvailablePort,
+    PassiveListenerLimitReached(usize),
     ListenerConfigurationFailed(io::Error),
     DataChannelSetupFailed(String),
     InvalidPortCommand(String),
     IpMismatch { expected: String, provided: String },
     InvalidPortRange(u16),
+    ForbiddenDataAddress(SocketAddr),
     TransferFailed(io::Error),
+    TooManyTransfers(usize),
 }
 
 impl fmt::Display for TransferError {
@@ -84,6 +162,9 @@ impl fmt::Display for TransferError {
                 write!(f, "Failed to bind to {addr}: {e}")
             }
             TransferError::NoAvailablePort => write!(f, "No available port for data connection"),
+            TransferError::PassiveListenerLimitReached(max) => {
+                write!(f, "Passive listener limit of {max} reached")
+            }
             TransferError::ListenerConfigurationFailed(e) => {
                 write!(f, "Failed to configure listener: {e}")
             }
@@ -97,7 +178,13 @@ impl fmt::Display for TransferError {
             TransferError::InvalidPortRange(port) => {
                 write!(f, "Invalid port {port}: must be between 1024 and 65535")
             }
+            TransferError::ForbiddenDataAddress(addr) => {
+                write!(f, "Forbidden data address {addr}")
+            }
             TransferError::TransferFailed(e) => write!(f, "Transfer failed: {e}"),
+            TransferError::TooManyTransfers(max) => {
+                write!(f, "Concurrent transfer limit of {max} reached")
+            }
         }
     }
 }
@@ -112,6 +199,8 @@ pub enum NavigateError {
     NotADirectory(String),
     PermissionDenied(String),
     PathTraversal(String),
+    /// The real path is longer than the OS allows, though within the depth limit
+    PathTooLong,
 }
 
 impl fmt::Display for NavigateError {
@@ -122,8 +211,45 @@ impl fmt::Display for NavigateError {
             NavigateError::NotADirectory(p) => write!(f, "Not a directory: {p}"),
             NavigateError::PermissionDenied(p) => write!(f, "Permission denied: {p}"),
             NavigateError::PathTraversal(p) => write!(f, "Path traversal attempt: {p}"),
+            NavigateError::PathTooLong => write!(f, "Path too long"),
         }
     }
 }
 
 impl std::error::Error for NavigateError {}
+
+/// Server startup and lifecycle errors
+#[derive(Debug)]
+pub enum ServerError {
+    Config(config::ConfigError),
+    Bind(String, io::Error),
+    Root(String, io::Error),
+    Credentials(io::Error),
+    Io(io::Error),
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Config(e) => write!(f, "Configuration error: {e}"),
+            ServerError::Bind(addr, e) => write!(f, "Failed to bind to {addr}: {e}"),
+            ServerError::Root(root, e) => write!(f, "Failed to resolve server root {root}: {e}"),
+            ServerError::Credentials(e) => write!(f, "Failed to load credentials: {e}"),
+            ServerError::Io(e) => write!(f, "IO error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+impl From<config::ConfigError> for ServerError {
+    fn from(error: config::ConfigError) -> Self {
+        ServerError::Config(error)
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(error: io::Error) -> Self {
+        ServerError::Io(error)
+    }
+}
@@ -0,0 +1,28 @@
+//! RAX FTP Server
+//!
+//! A robust Rust-based FTP server implementing core features of RFC 959.
+//! The binary in `main.rs` is a thin wrapper around [`start_server`]; embedders
+//! can call it directly and handle startup failures themselves.
+
+pub mod auth;
+pub mod client;
+pub mod config;
+pub mod error;
+pub mod logging;
+pub mod navigate;
+pub mod protocol;
+pub mod server;
+pub mod storage;
+pub mod transfer;
+
+pub use error::ServerError;
+pub use server::Server;
+
+/// Loads configuration, binds the control listener and serves clients.
+///
+/// Returns an error if the server fails to start; once running, the accept
+/// loop only returns if the listener itself fails.
+pub async fn start_server() -> Result<(), ServerError> {
+    let server = Server::new().await?;
+    server.start().await
+}
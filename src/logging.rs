@@ -0,0 +1,123 @@
+//! Logging setup for the Rax FTP server
+//!
+//! Logs go through the `log` facade and are written by `env_logger`, either in
+//! its usual text format or as one JSON object per line for log aggregators.
+//! Connection, command and transfer events carry structured key-values
+//! (`event`, `client_addr`, `user`, `command`, `code`, `bytes`, `duration_ms`)
+//! that become top-level fields in JSON output.
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, Record, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+
+/// Output format for log records
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable `env_logger` text
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Installs the global logger, filtered by `RUST_LOG` as usual.
+pub fn init(format: LogFormat) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if format == LogFormat::Json {
+        builder.format(|buf, record| writeln!(buf, "{}", json_line(record)));
+    }
+    builder.init();
+}
+
+/// Logs a completed FTP command along with its reply code and duration.
+pub fn log_command(
+    client_addr: SocketAddr,
+    user: Option<&str>,
+    command: &str,
+    reply: Option<&str>,
+    duration: Duration,
+) {
+    let code = reply.and_then(reply_code).unwrap_or(0);
+    info!(
+        event = "command",
+        client_addr:% = client_addr,
+        user = user.unwrap_or("-"),
+        command = command,
+        code = code,
+        duration_ms = duration.as_millis() as u64;
+        "Client {client_addr} {command} -> {code} in {duration:?}"
+    );
+}
+
+/// Logs a completed file transfer.
+pub fn log_transfer(
+    client_addr: SocketAddr,
+    user: Option<&str>,
+    command: &str,
+    bytes: u64,
+    duration: Duration,
+) {
+    info!(
+        event = "transfer",
+        client_addr:% = client_addr,
+        user = user.unwrap_or("-"),
+        command = command,
+        bytes = bytes,
+        duration_ms = duration.as_millis() as u64;
+        "Client {client_addr} {command} transferred {bytes} bytes in {duration:?}"
+    );
+}
+
+/// Extracts the three-digit code from an FTP reply
+fn reply_code(reply: &str) -> Option<u16> {
+    reply.get(..3)?.parse().ok()
+}
+
+/// Renders a record as a single JSON object
+fn json_line(record: &Record) -> JsonValue {
+    let mut fields = Map::new();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    fields.insert("ts_ms".into(), timestamp.into());
+    fields.insert("level".into(), record.level().as_str().into());
+    fields.insert("target".into(), record.target().into());
+
+    // Plain log lines have no event of their own; tag errors so they can be filtered
+    let event = if record.level() == Level::Error {
+        "error"
+    } else {
+        "log"
+    };
+    fields.insert("event".into(), event.into());
+
+    let _ = record.key_values().visit(&mut JsonFields(&mut fields));
+    fields.insert("message".into(), record.args().to_string().into());
+    JsonValue::Object(fields)
+}
+
+/// Copies a record's key-values into a JSON object
+struct JsonFields<'a>(&'a mut Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(n) = value.to_u64() {
+            n.into()
+        } else if let Some(n) = value.to_i64() {
+            n.into()
+        } else if let Some(b) = value.to_bool() {
+            b.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.as_str().into(), value);
+        Ok(())
+    }
+}
@@ -2,27 +2,67 @@
 //!
 //! A robust Rust-based FTP server implementing core features of RFC 959.
 
-use log::info;
-
-mod auth;
-mod client;
-mod config;
-mod error;
-mod navigate;
-mod protocol;
-mod server;
-mod storage;
-mod transfer;
-
-use server::Server;
+use log::{error, info};
+use rax_ftp_server::config::ServerConfig;
+use rax_ftp_server::{Server, ServerError, auth, logging};
+use std::path::Path;
 
 #[tokio::main]
 async fn main() {
-    // Initialize the logger (env_logger picks up RUST_LOG environment variable)
-    env_logger::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [command, username] = args.as_slice()
+        && command == "add-user"
+    {
+        if let Err(e) = add_user(username) {
+            eprintln!("Failed to add user {username}: {e}");
+            std::process::exit(1);
+        }
+        println!("Added user {username}; send SIGHUP to a running server to load it");
+        return;
+    }
+
+    // Configuration is loaded first so it can pick the log format;
+    // RUST_LOG still controls the level filter
+    let config = ServerConfig::load();
+    logging::init(
+        config
+            .as_ref()
+            .map(|config| config.startup.log_format)
+            .unwrap_or_default(),
+    );
 
     info!("Launching Rax FTP server...");
 
-    let server = Server::new().await;
-    server.start().await;
+    if let Err(e) = run(config).await {
+        error!("Server failed: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(config: Result<ServerConfig, config::ConfigError>) -> Result<(), ServerError> {
+    let server = Server::with_config(config?).await?;
+    server.start().await
+}
+
+/// `rax-ftp-server add-user <username>`: adds an account to `credentials_file`,
+/// reading the password from the first line of standard input.
+fn add_user(username: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = ServerConfig::load()?;
+    let credentials_file = config
+        .startup
+        .credentials_file
+        .as_deref()
+        .ok_or("credentials_file is not set")?;
+
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+
+    auth::add_user(
+        Path::new(credentials_file),
+        username,
+        password,
+        &config.startup,
+    )?;
+    Ok(())
 }
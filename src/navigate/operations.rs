@@ -1,17 +1,16 @@
 //! Navigation operations implementation
 
-use crate::config::StartupConfig;
 use crate::error::NavigateError;
-use std::path::Path;
+use crate::storage::validation::StorageRoot;
 
 /// Changes the working directory for a client
 pub fn change_directory(
-    server_root: &Path,
+    root: &StorageRoot,
     current_virtual_path: &str,
     target_path: &str,
-    config: &StartupConfig,
+    max_depth: usize,
 ) -> Result<String, NavigateError> {
-    use crate::storage::validation::{resolve_cwd_path, virtual_to_real_path};
+    use crate::storage::validation::resolve_cwd_path;
 
     // Validate target path
     if target_path.is_empty() {
@@ -19,24 +18,28 @@ pub fn change_directory(
     }
 
     // Resolve the new virtual path
-    let new_virtual_path = resolve_cwd_path(current_virtual_path, target_path, config)
+    let new_virtual_path = resolve_cwd_path(current_virtual_path, target_path, max_depth)
         .map_err(NavigateError::InvalidPath)?;
 
     // Convert to real path and verify it exists
-    let real_path = virtual_to_real_path(server_root, &new_virtual_path);
+    let (real_path, bounds) = root.real_path(&new_virtual_path);
 
-    if !real_path.exists() {
-        return Err(NavigateError::DirectoryNotFound(new_virtual_path));
+    match real_path.try_exists() {
+        Ok(true) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidFilename => {
+            return Err(NavigateError::PathTooLong);
+        }
+        Ok(false) | Err(_) => return Err(NavigateError::DirectoryNotFound(new_virtual_path)),
     }
 
     if !real_path.is_dir() {
         return Err(NavigateError::NotADirectory(new_virtual_path));
     }
 
-    // Additional security check to ensure path is within server root
+    // Additional security check to ensure path is within server root, or its mount
     match real_path.canonicalize() {
         Ok(canonical_path) => {
-            let server_root_canonical = server_root
+            let server_root_canonical = bounds
                 .canonicalize()
                 .map_err(|_| NavigateError::InvalidPath("Server root invalid".into()))?;
 
@@ -3,6 +3,15 @@
 //! Defines the core FTP command parsing logic and related data structures
 //! used to represent commands, their status, associated data, and results.
 
+use log::error;
+use std::sync::Arc;
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::client::Client;
+use crate::config::StartupConfig;
+use crate::protocol::parser::unquote_path;
+use crate::transfer::TransferType;
+
 /// Represents an FTP command parsed from the client input.
 ///
 /// Each variant corresponds to a standard FTP command or custom extensions.
@@ -11,19 +20,104 @@
 #[allow(clippy::upper_case_acronyms)]
 pub enum Command {
     QUIT,
-    LIST,
+    LIST(Option<String>), // List the current directory, or a given directory or file
+    NLST(Option<String>), // Name list of the current or given directory
+    MLSD(Option<String>), // Machine-readable listing of a directory (RFC 3659)
+    MLST(Option<String>), // Machine-readable facts about one file or directory
     LOGOUT,
     PWD,
-    CWD(String),  // Change working directory
-    USER(String), // Username for login
-    PASS(String), // Password for login
-    RETR(String), // Retrieve/download file
-    STOR(String), // Store/upload file
-    DEL(String),  // Delete file
-    PORT(String), // Active mode data port specification
-    PASV,         // Enter passive mode
-    UNKNOWN,      // Unknown or unsupported command
-    RAX,          // Custom command, e.g., server info or ping
+    CWD(String),          // Change working directory
+    USER(String),         // Username for login
+    PASS(String),         // Password for login
+    ACCT(String),         // Account for login
+    RETR(String),         // Retrieve/download file
+    STOR(String),         // Store/upload file
+    DEL(String),          // Delete file (DELE, or the legacy DEL spelling)
+    MKD(String),          // Make directory
+    RMD(String),          // Remove directory
+    PORT(String),         // Active mode data port specification
+    PASV,                 // Enter passive mode
+    EPSV(Option<String>), // Extended passive mode (RFC 2428), or `EPSV ALL`
+    TYPE(String),         // Set transfer type (representation)
+    REST(String),         // Restart marker (byte offset) for the next RETR
+    ABOR,                 // Abort the current data transfer
+    SYST,                 // Report the system type
+    STAT(Option<String>), // Report session status, or list a path, on the control connection
+    FEAT,                 // List supported extensions
+    NOOP,                 // Do nothing; keeps the control connection alive
+    HELP(Option<String>), // List the available commands, or check a single one
+    MODE(String),         // Set the transfer mode: S (stream) or Z (deflate)
+    STRU(String),         // Set the file structure; only F (file) is supported
+    SITE(String),         // Server-specific subcommands, such as `SITE DISKUSAGE`
+    OPTS(String),         // Set options for another command, such as `OPTS MLST`
+    SIZE(String),         // Get a file's size
+    MDTM(String),         // Get a file's modification time
+    HASH(String),         // Get a file's digest with the algorithm chosen by `OPTS HASH`
+    MFMT(String),         // Set a file's modification time
+    EMPTY,                // Blank line, sent by some clients as a keepalive
+    UNKNOWN,              // Unknown or unsupported command
+    INVALID(String),      // Known command with malformed arguments (holds the verb)
+    UNSUPPORTED(String),  // Recognized command this server does not implement (holds the verb)
+    RAX,                  // Custom command, e.g., server info or ping
+}
+
+impl Command {
+    /// Returns the command verb, as used by the dispatch table.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::QUIT => "QUIT",
+            Command::LIST(_) => "LIST",
+            Command::NLST(_) => "NLST",
+            Command::MLSD(_) => "MLSD",
+            Command::MLST(_) => "MLST",
+            Command::LOGOUT => "LOGOUT",
+            Command::PWD => "PWD",
+            Command::CWD(_) => "CWD",
+            Command::USER(_) => "USER",
+            Command::PASS(_) => "PASS",
+            Command::ACCT(_) => "ACCT",
+            Command::RETR(_) => "RETR",
+            Command::STOR(_) => "STOR",
+            Command::DEL(_) => "DEL",
+            Command::MKD(_) => "MKD",
+            Command::RMD(_) => "RMD",
+            Command::PORT(_) => "PORT",
+            Command::PASV => "PASV",
+            Command::EPSV(_) => "EPSV",
+            Command::TYPE(_) => "TYPE",
+            Command::REST(_) => "REST",
+            Command::ABOR => "ABOR",
+            Command::SYST => "SYST",
+            Command::STAT(_) => "STAT",
+            Command::FEAT => "FEAT",
+            Command::NOOP => "NOOP",
+            Command::HELP(_) => "HELP",
+            Command::MODE(_) => "MODE",
+            Command::STRU(_) => "STRU",
+            Command::SITE(_) => "SITE",
+            Command::OPTS(_) => "OPTS",
+            Command::SIZE(_) => "SIZE",
+            Command::MDTM(_) => "MDTM",
+            Command::HASH(_) => "HASH",
+            Command::MFMT(_) => "MFMT",
+            Command::EMPTY => "EMPTY",
+            Command::UNKNOWN => "UNKNOWN",
+            Command::INVALID(_) => "INVALID",
+            Command::UNSUPPORTED(_) => "UNSUPPORTED",
+            Command::RAX => "RAX",
+        }
+    }
+
+    /// Returns the representation used on the data channel for this command.
+    ///
+    /// Directory listings are always sent as ASCII, while file transfers
+    /// follow the TYPE negotiated for the session.
+    pub fn data_type(&self, session_type: TransferType) -> TransferType {
+        match self {
+            Command::LIST(_) | Command::NLST(_) | Command::MLSD(_) => TransferType::Ascii,
+            _ => session_type,
+        }
+    }
 }
 
 /// Represents the outcome status of executing a command.
@@ -31,6 +125,52 @@ pub enum CommandStatus {
     Success,
     Failure(String),
     CloseConnection,
+    /// The command goes on with a data transfer, which the session runs
+    /// after releasing the shared client and channel registries
+    Transfer(PendingTransfer),
+}
+
+/// Records the outcome of a finished transfer on the client and builds the final reply.
+pub type TransferFinish = Box<dyn FnOnce(&mut Client) -> CommandResult + Send>;
+
+/// The data connection part of a transfer command, ready to run.
+pub struct PendingTransfer {
+    run: Box<dyn FnOnce(&StartupConfig) -> TransferFinish + Send>,
+    /// The transfer slot claimed for it, released once it has run
+    slot: Option<OwnedSemaphorePermit>,
+}
+
+impl PendingTransfer {
+    /// Wraps the blocking part of a transfer, which returns how to finish the command.
+    pub fn new(
+        slot: Option<OwnedSemaphorePermit>,
+        run: impl FnOnce(&StartupConfig) -> TransferFinish + Send + 'static,
+    ) -> Self {
+        Self {
+            run: Box::new(run),
+            slot,
+        }
+    }
+
+    /// Runs the transfer on a blocking thread.
+    pub async fn run(self, startup_config: Arc<StartupConfig>) -> TransferFinish {
+        let Self { run, slot } = self;
+        // The slot goes with the blocking task, which carries on if the
+        // session stops waiting for it
+        let finish = tokio::task::spawn_blocking(move || {
+            let finish = run(&startup_config);
+            drop(slot);
+            finish
+        })
+        .await;
+        finish.unwrap_or_else(|e| {
+            error!("Data transfer task failed: {e}");
+            Box::new(|_| CommandResult {
+                status: CommandStatus::Failure("Transfer task failed".into()),
+                message: Some("451 Requested action aborted: local error in processing\r\n".into()),
+            })
+        })
+    }
 }
 
 /// Struct encapsulating the full result of a command execution.
@@ -42,26 +182,80 @@ pub struct CommandResult {
 /// Parses a raw command string received from a client into the `Command` enum.
 ///
 /// Validates required arguments and returns `UNKNOWN` if a known command is misused.
+/// The legacy `X`-prefixed forms (XPWD, XCWD, XMKD, XRMD) parse to their standard commands,
+/// and SPSV parses to PASV.
+/// A blank line parses to `EMPTY`.
+/// Commands that take a single token (or TYPE's two) return `INVALID` when
+/// extra tokens follow; only filename arguments may contain spaces, and the
+/// file arguments of RETR, STOR, DELE, SIZE, MDTM and HASH may be quoted.
 pub fn parse_command(raw: &str) -> Command {
     let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Command::EMPTY;
+    }
     let mut parts = trimmed.splitn(2, char::is_whitespace);
     let cmd = parts.next().unwrap_or("").to_ascii_uppercase();
     let arg = parts.next().unwrap_or("").trim();
 
+    if let Some(max_tokens) = max_argument_tokens(&cmd)
+        && arg.split_whitespace().count() > max_tokens
+    {
+        return Command::INVALID(cmd);
+    }
+
+    // File arguments are the whole remainder, spaces included, minus any quotes
+    let path = unquote_path(arg);
+
     match cmd.as_str() {
         "QUIT" | "Q" => Command::QUIT,
-        "LIST" => Command::LIST,
+        "LIST" => Command::LIST((!arg.is_empty()).then(|| arg.to_string())),
+        "NLST" => Command::NLST((!arg.is_empty()).then(|| arg.to_string())),
+        "MLSD" => Command::MLSD((!arg.is_empty()).then(|| arg.to_string())),
+        "MLST" => Command::MLST((!arg.is_empty()).then(|| arg.to_string())),
         "LOGOUT" => Command::LOGOUT,
-        "PWD" => Command::PWD,
-        "CWD" if !arg.is_empty() => Command::CWD(arg.to_string()),
+        "PWD" | "XPWD" => Command::PWD,
+        "CWD" | "XCWD" if !arg.is_empty() => Command::CWD(arg.to_string()),
         "USER" if !arg.is_empty() => Command::USER(arg.to_string()),
         "PASS" if !arg.is_empty() => Command::PASS(arg.to_string()),
-        "RETR" if !arg.is_empty() => Command::RETR(arg.to_string()),
-        "STOR" if !arg.is_empty() => Command::STOR(arg.to_string()),
-        "DEL" if !arg.is_empty() => Command::DEL(arg.to_string()),
+        "ACCT" if !arg.is_empty() => Command::ACCT(arg.to_string()),
+        "RETR" if !path.is_empty() => Command::RETR(path),
+        "STOR" if !path.is_empty() => Command::STOR(path),
+        "DELE" | "DEL" if !path.is_empty() => Command::DEL(path),
+        "MKD" | "XMKD" if !arg.is_empty() => Command::MKD(arg.to_string()),
+        "RMD" | "XRMD" if !arg.is_empty() => Command::RMD(arg.to_string()),
         "PORT" if !arg.is_empty() => Command::PORT(arg.to_string()),
-        "PASV" => Command::PASV,
+        // Single-port passive, sent by some older clients, gets the PASV reply
+        "PASV" | "SPSV" => Command::PASV,
+        "EPSV" => Command::EPSV((!arg.is_empty()).then(|| arg.to_string())),
+        "TYPE" if !arg.is_empty() => Command::TYPE(arg.to_string()),
+        "REST" if !arg.is_empty() => Command::REST(arg.to_string()),
+        "ABOR" => Command::ABOR,
+        "SYST" => Command::SYST,
+        "STAT" => Command::STAT((!arg.is_empty()).then(|| arg.to_string())),
+        "FEAT" => Command::FEAT,
+        "NOOP" => Command::NOOP,
+        "MODE" if !arg.is_empty() => Command::MODE(arg.to_string()),
+        "STRU" if !arg.is_empty() => Command::STRU(arg.to_string()),
+        "SITE" if !arg.is_empty() => Command::SITE(arg.to_string()),
+        "HELP" => Command::HELP((!arg.is_empty()).then(|| arg.to_ascii_uppercase())),
+        "OPTS" if !arg.is_empty() => Command::OPTS(arg.to_string()),
+        "SIZE" if !path.is_empty() => Command::SIZE(path),
+        "MDTM" if !path.is_empty() => Command::MDTM(path),
+        "HASH" if !path.is_empty() => Command::HASH(path),
+        "MFMT" if !arg.is_empty() => Command::MFMT(arg.to_string()),
         "RAX" => Command::RAX,
+        // Legacy long address variants (RFC 1639); clients fall back to PASV/PORT
+        "LPSV" | "LPRT" => Command::UNSUPPORTED(cmd),
         _ => Command::UNKNOWN,
     }
 }
+
+/// Returns how many whitespace-separated tokens a command's argument may have,
+/// or `None` if the argument is free-form (such as a filename).
+fn max_argument_tokens(cmd: &str) -> Option<usize> {
+    match cmd {
+        "USER" | "PASS" | "ACCT" | "PORT" | "REST" | "EPSV" | "HELP" | "MODE" | "STRU" => Some(1),
+        "TYPE" => Some(2),
+        _ => None,
+    }
+}
@@ -0,0 +1,163 @@
+//! Command dispatch table
+//!
+//! Declares the session preconditions and properties of every command so the
+//! dispatcher can enforce them in one place before a handler runs, rather than
+//! repeating the checks in each handler.
+
+use crate::protocol::Command;
+
+/// Preconditions and properties declared for a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// The client must have completed USER/PASS
+    pub requires_login: bool,
+    /// A PASV/PORT data channel must have been set up
+    pub requires_data_channel: bool,
+    /// The command modifies the file system
+    pub is_write: bool,
+    /// Answered right away while the session's data transfer is running,
+    /// rather than after it has finished
+    pub answered_during_transfer: bool,
+}
+
+/// Available without logging in
+const PUBLIC: CommandSpec = CommandSpec {
+    requires_login: false,
+    requires_data_channel: false,
+    is_write: false,
+    answered_during_transfer: false,
+};
+
+/// Requires an authenticated session
+const SESSION: CommandSpec = CommandSpec {
+    requires_login: true,
+    ..PUBLIC
+};
+
+/// Requires an authenticated session with a data channel
+const TRANSFER: CommandSpec = CommandSpec {
+    requires_data_channel: true,
+    ..SESSION
+};
+
+/// Every command the server implements, in the order HELP lists them.
+///
+/// Arguments are placeholders; only the variant matters.
+const COMMANDS: &[Command] = &[
+    Command::QUIT,
+    Command::USER(String::new()),
+    Command::PASS(String::new()),
+    Command::ACCT(String::new()),
+    Command::RAX,
+    Command::LIST(None),
+    Command::NLST(None),
+    Command::MLSD(None),
+    Command::MLST(None),
+    Command::PWD,
+    Command::LOGOUT,
+    Command::RETR(String::new()),
+    Command::STOR(String::new()),
+    Command::DEL(String::new()),
+    Command::CWD(String::new()),
+    Command::MKD(String::new()),
+    Command::RMD(String::new()),
+    Command::PASV,
+    Command::EPSV(None),
+    Command::PORT(String::new()),
+    Command::TYPE(String::new()),
+    Command::REST(String::new()),
+    Command::MODE(String::new()),
+    Command::STRU(String::new()),
+    Command::SITE(String::new()),
+    Command::ABOR,
+    Command::SYST,
+    Command::STAT(None),
+    Command::FEAT,
+    Command::HELP(None),
+    Command::NOOP,
+    Command::OPTS(String::new()),
+    Command::SIZE(String::new()),
+    Command::MDTM(String::new()),
+    Command::HASH(String::new()),
+    Command::MFMT(String::new()),
+];
+
+/// Returns the declared preconditions for a command.
+///
+/// The match is exhaustive so a new command can't be added without deciding
+/// whether it needs a login.
+pub fn command_spec(command: &Command) -> CommandSpec {
+    match command {
+        Command::QUIT
+        | Command::USER(_)
+        | Command::PASS(_)
+        | Command::ACCT(_)
+        | Command::RAX
+        | Command::TYPE(_)
+        | Command::SYST
+        | Command::FEAT
+        | Command::HELP(_)
+        | Command::OPTS(_) => PUBLIC,
+        Command::NOOP => CommandSpec {
+            answered_during_transfer: true,
+            ..PUBLIC
+        },
+        // Only answered with an error reply
+        Command::EMPTY | Command::UNKNOWN | Command::INVALID(_) | Command::UNSUPPORTED(_) => PUBLIC,
+        Command::PWD | Command::STAT(_) => CommandSpec {
+            answered_during_transfer: true,
+            ..SESSION
+        },
+        Command::MLST(_)
+        | Command::LOGOUT
+        | Command::CWD(_)
+        | Command::PASV
+        | Command::EPSV(_)
+        | Command::PORT(_)
+        | Command::REST(_)
+        | Command::MODE(_)
+        | Command::STRU(_)
+        | Command::SITE(_)
+        | Command::ABOR
+        | Command::SIZE(_)
+        | Command::MDTM(_)
+        | Command::HASH(_) => SESSION,
+        Command::DEL(_) | Command::MKD(_) | Command::RMD(_) | Command::MFMT(_) => CommandSpec {
+            is_write: true,
+            ..SESSION
+        },
+        Command::LIST(_) | Command::NLST(_) | Command::MLSD(_) | Command::RETR(_) => TRANSFER,
+        Command::STOR(_) => CommandSpec {
+            is_write: true,
+            ..TRANSFER
+        },
+    }
+}
+
+/// Returns the verb a client sends for a command, as listed by HELP.
+///
+/// This differs from `Command::name` only where the variant is named after a
+/// legacy spelling, such as `DEL` for DELE.
+pub fn command_verb(command: &Command) -> &'static str {
+    wire_verb(command.name())
+}
+
+/// Iterates over the verbs of every implemented command.
+pub fn command_verbs() -> impl Iterator<Item = &'static str> {
+    COMMANDS.iter().map(command_verb)
+}
+
+/// Iterates over the verbs of the write commands.
+pub fn write_verbs() -> impl Iterator<Item = &'static str> {
+    COMMANDS
+        .iter()
+        .filter(|command| command_spec(command).is_write)
+        .map(command_verb)
+}
+
+fn wire_verb(name: &'static str) -> &'static str {
+    match name {
+        "DEL" => "DELE",
+        name => name,
+    }
+}
@@ -4,27 +4,41 @@
 //! to domain-specific modules and translating their results to FTP responses.
 //! Updated to support persistent data connections.
 
-use log::info;
+use log::{info, warn};
 use std::future::Future;
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::sync::OwnedSemaphorePermit;
 
 use crate::auth;
 use crate::client::Client;
-use crate::config::{SharedRuntimeConfig, StartupConfig};
+use crate::config::{RuntimeConfig, SharedRuntimeConfig, StartupConfig};
 use crate::error::AuthError;
 use crate::error::TransferError;
+use crate::logging;
 use crate::navigate;
-use crate::protocol::{Command, CommandResult, CommandStatus};
+use crate::protocol::{
+    Command, CommandResult, CommandSpec, CommandStatus, PendingTransfer, command_spec,
+    command_verb, command_verbs, responses, split_list_options, write_verbs,
+};
 use crate::storage;
+use crate::storage::facts::FactSet;
+use crate::storage::hash::HashAlgorithm;
 use crate::transfer::{
-    self, ChannelRegistry, receive_file_upload, send_directory_listing, setup_data_stream,
-    validate_client_and_data_channel,
+    self, BandwidthLimiter, ChannelRegistry, DataConnection, DownloadOptions, TransferDirection,
+    TransferMode, TransferType, UploadOptions, receive_file_upload, send_directory_listing,
 };
 
+/// Consecutive storage backend failures after which the session is closed
+const MAX_STORAGE_FAILURES: u32 = 3;
+
 /// Dispatches a received FTP command to its corresponding handler.
 ///
 /// Acts as an orchestrator, calling appropriate domain modules and translating
-/// their results to FTP protocol responses.
+/// their results to FTP protocol responses. Login and data channel requirements
+/// come from the dispatch table, so individual handlers can assume they hold.
 pub async fn handle_command<F>(
     client: &mut Client,
     command: &Command,
@@ -36,13 +50,53 @@ pub async fn handle_command<F>(
 where
     F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
 {
-    match command {
+    // Only admins may keep working while the server is in maintenance mode
+    if let Some(rejection) = check_maintenance(
+        client,
+        command,
+        startup_config,
+        &*runtime_config.read().await,
+    ) {
+        return rejection;
+    }
+
+    if let Some(rejection) = check_disabled(command, startup_config) {
+        return rejection;
+    }
+
+    // Check the preconditions declared in the dispatch table once, up front
+    let spec = command_spec(command);
+    if let Some(rejection) = check_preconditions(client, spec) {
+        return rejection;
+    }
+
+    if spec.is_write && startup_config.is_read_only(client.username().map(String::as_str)) {
+        return write_rejection(startup_config);
+    }
+
+    // Any write may change what a cached SIZE/MDTM lookup reported
+    if spec.is_write {
+        client.invalidate_metadata_cache();
+    }
+
+    // Listings always go out as ASCII; file transfers follow the session TYPE
+    let data_type = command.data_type(client.transfer_type());
+
+    let storage_failures = client.storage_failures();
+
+    let result = match command {
         Command::QUIT => handle_cmd_quit(client, channel_registry),
-        Command::USER(username) => handle_cmd_user(client, username, startup_config),
+        Command::USER(username) => {
+            end_session(client, channel_registry);
+            handle_cmd_user(client, username, startup_config)
+        }
         Command::PASS(password) => handle_cmd_pass(client, password, startup_config),
-        Command::LIST => {
+        Command::ACCT(account) => handle_cmd_acct(client, account, startup_config),
+        Command::LIST(argument) => {
             handle_cmd_list(
                 client,
+                argument.as_deref(),
+                data_type,
                 startup_config,
                 runtime_config,
                 channel_registry,
@@ -50,12 +104,37 @@ where
             )
             .await
         }
+        Command::NLST(path) => {
+            handle_cmd_nlst(
+                client,
+                path.as_deref(),
+                data_type,
+                startup_config,
+                channel_registry,
+                send_intermediate,
+            )
+            .await
+        }
+        Command::MLSD(path) => {
+            handle_cmd_mlsd(
+                client,
+                path.as_deref(),
+                data_type,
+                startup_config,
+                channel_registry,
+                send_intermediate,
+            )
+            .await
+        }
+        Command::MLST(path) => handle_cmd_mlst(client, path.as_deref(), startup_config),
+        Command::OPTS(args) => handle_cmd_opts(client, args),
         Command::PWD => handle_cmd_pwd(client),
         Command::LOGOUT => handle_cmd_logout(client, channel_registry),
         Command::RETR(filename) => {
             handle_cmd_retr(
                 client,
                 filename,
+                data_type,
                 channel_registry,
                 startup_config,
                 runtime_config,
@@ -67,6 +146,7 @@ where
             handle_cmd_stor(
                 client,
                 filename,
+                data_type,
                 channel_registry,
                 startup_config,
                 runtime_config,
@@ -76,29 +156,245 @@ where
         }
         Command::DEL(filename) => handle_cmd_del(client, filename, startup_config),
         Command::CWD(path) => handle_cmd_cwd(client, path, startup_config),
+        Command::MKD(dirname) => handle_cmd_mkd(client, dirname, startup_config),
+        Command::RMD(dirname) => handle_cmd_rmd(client, dirname, startup_config),
         Command::PASV => handle_cmd_pasv(client, channel_registry, startup_config),
+        Command::EPSV(argument) => handle_cmd_epsv(
+            client,
+            channel_registry,
+            argument.as_deref(),
+            startup_config,
+        ),
         Command::PORT(addr) => handle_cmd_port(client, channel_registry, addr, startup_config),
+        Command::TYPE(type_arg) => handle_cmd_type(client, type_arg),
+        Command::REST(offset) => handle_cmd_rest(client, offset),
+        Command::MODE(mode) => handle_cmd_mode(client, mode, startup_config),
+        Command::STRU(structure) => handle_cmd_stru(structure),
+        Command::SITE(args) => handle_cmd_site(client, args, startup_config).await,
+        Command::ABOR => handle_cmd_abor(client, channel_registry),
+        Command::SYST => handle_cmd_syst(startup_config),
+        Command::STAT(None) => handle_cmd_stat(client, channel_registry, startup_config),
+        Command::STAT(Some(path)) => handle_cmd_stat_path(client, path, startup_config),
+        Command::FEAT => handle_cmd_feat(client, startup_config),
+        Command::NOOP => handle_cmd_noop(),
+        Command::HELP(verb) => handle_cmd_help(client, verb.as_deref(), startup_config),
+        Command::SIZE(filename) => handle_cmd_size(client, filename, startup_config),
+        Command::MDTM(filename) => handle_cmd_mdtm(client, filename, startup_config),
+        Command::HASH(filename) => handle_cmd_hash(client, filename, startup_config),
+        Command::MFMT(args) => handle_cmd_mfmt(client, args, startup_config),
         Command::RAX => handle_cmd_rax(),
+        Command::EMPTY => handle_empty_line(startup_config),
         Command::UNKNOWN => handle_cmd_unknown(),
+        Command::INVALID(verb) => handle_cmd_invalid(verb),
+        Command::UNSUPPORTED(verb) => handle_cmd_unsupported(verb),
+    };
+
+    // Only an unbroken run of backend failures counts towards closing the session
+    if client.storage_failures() == storage_failures {
+        client.clear_storage_failures();
+    }
+
+    result
+}
+
+/// Rejects a command whose declared preconditions are not met by the session
+fn check_preconditions(client: &Client, spec: CommandSpec) -> Option<CommandResult> {
+    if spec.requires_login && !client.is_logged_in() {
+        return Some(CommandResult {
+            status: CommandStatus::Failure("Not logged in".into()),
+            message: Some(responses::NOT_LOGGED_IN.into()),
+        });
+    }
+
+    if spec.requires_data_channel && !client.is_data_channel_init() {
+        return Some(CommandResult {
+            status: CommandStatus::Failure("Data channel not initialized".into()),
+            message: Some(responses::NO_DATA_CHANNEL.into()),
+        });
+    }
+
+    None
+}
+
+/// Refuses a command listed in `disabled_commands` as if it were not implemented
+fn check_disabled(command: &Command, startup_config: &StartupConfig) -> Option<CommandResult> {
+    let verb = command_verb(command);
+    startup_config
+        .is_command_disabled(verb)
+        .then(|| handle_cmd_unsupported(verb))
+}
+
+/// Checks whether HELP and FEAT should offer a command to this client
+///
+/// Disabled commands are never offered, nor are write commands while the
+/// client's user is read-only.
+fn is_command_available(client: &Client, verb: &str, startup_config: &StartupConfig) -> bool {
+    if startup_config.is_command_disabled(verb) {
+        return false;
+    }
+    !(startup_config.is_read_only(client.username().map(String::as_str))
+        && write_verbs().any(|write| write == verb))
+}
+
+/// Moves a client that just logged in to its configured home directory
+///
+/// With `auto_create_home` a missing home is created first. A user whose home
+/// can't be entered is refused rather than left at the root.
+fn enter_home_directory(
+    client: &mut Client,
+    startup_config: &StartupConfig,
+) -> Option<CommandResult> {
+    let username = client.username()?.clone();
+    let home = startup_config.home_directory(&username)?;
+    let root = startup_config.storage_root();
+    let max_depth = startup_config.max_directory_depth_for(Some(&username));
+
+    let entered = if startup_config.auto_create_home
+        && let Err(e) = storage::ensure_home_directory(&root, home, startup_config.umask)
+    {
+        Err(e.to_string())
+    } else {
+        navigate::change_directory(&root, "/", home, max_depth)
+            .map_err(|e| e.to_string())
+            .and_then(|path| client.set_current_virtual_path(path))
+    };
+
+    let error = entered.err()?;
+    warn!("Refusing login for {username}: home directory {home} unavailable: {error}");
+    client.set_logged_in(false);
+    Some(CommandResult {
+        status: CommandStatus::Failure(format!("Home directory unavailable: {error}")),
+        message: Some("530 Home directory unavailable\r\n".into()),
+    })
+}
+
+/// Returns the client's address, which keys its data channel
+///
+/// A logged-in session always has one; without it no data connection can be
+/// set up, so the command is refused with 425 rather than a login error.
+fn data_client_addr(client: &Client) -> Result<SocketAddr, CommandResult> {
+    client.client_addr().copied().ok_or_else(|| CommandResult {
+        status: CommandStatus::Failure("Client address unknown".into()),
+        message: Some(responses::DATA_CONNECTION_FAILED.into()),
+    })
+}
+
+/// Builds the reply for a write command refused by read-only mode
+///
+/// Clients disagree on which code means "not allowed here" (550, 553, 532...),
+/// so the code comes from `read_only_reply_code`.
+fn write_rejection(startup_config: &StartupConfig) -> CommandResult {
+    CommandResult {
+        status: CommandStatus::Failure("Read-only access".into()),
+        message: Some(format!(
+            "{} Permission denied\r\n",
+            startup_config.read_only_reply_code
+        )),
+    }
+}
+
+/// Rejects a command and closes the connection if the server is in maintenance
+/// mode and the command does not come from (or log in) an admin user
+fn check_maintenance(
+    client: &Client,
+    command: &Command,
+    startup_config: &StartupConfig,
+    runtime_config: &RuntimeConfig,
+) -> Option<CommandResult> {
+    if !runtime_config.maintenance_mode {
+        return None;
+    }
+
+    let username = match command {
+        Command::USER(username) => Some(username),
+        _ => client.username(),
+    };
+    if username.is_some_and(|name| startup_config.is_admin(name)) {
+        return None;
     }
+
+    Some(CommandResult {
+        status: CommandStatus::CloseConnection,
+        message: Some(format!("421 {}\r\n", runtime_config.maintenance_message)),
+    })
 }
 
-/// Handles authentication commands during the login phase
+/// Handles commands received before login
+///
+/// Only authentication and a small set of commands that are safe without a
+/// session are accepted; everything else is refused with 530.
 pub fn handle_auth_command(
     client: &mut Client,
     command: &Command,
     startup_config: &StartupConfig,
+    runtime_config: &RuntimeConfig,
 ) -> CommandResult {
+    if let Some(rejection) = check_maintenance(client, command, startup_config, runtime_config) {
+        return rejection;
+    }
+    if let Some(rejection) = check_disabled(command, startup_config) {
+        return rejection;
+    }
+
     match command {
         Command::USER(username) => handle_cmd_user(client, username, startup_config),
         Command::PASS(password) => handle_cmd_pass(client, password, startup_config),
+        Command::ACCT(account) => handle_cmd_acct(client, account, startup_config),
+        // Session settings some clients send as part of their handshake; the
+        // provisional client carries them into the session once logged in
+        Command::TYPE(type_arg) => handle_cmd_type(client, type_arg),
+        Command::SYST => handle_cmd_syst(startup_config),
+        Command::FEAT => handle_cmd_feat(client, startup_config),
+        Command::NOOP => handle_cmd_noop(),
+        Command::EMPTY => handle_empty_line(startup_config),
+        Command::HELP(verb) => handle_cmd_help(client, verb.as_deref(), startup_config),
+        Command::OPTS(args) => handle_cmd_opts(client, args),
+        Command::QUIT => handle_auth_quit(),
+        Command::INVALID(verb) => handle_cmd_invalid(verb),
+        Command::UNSUPPORTED(verb) => handle_cmd_unsupported(verb),
         _ => CommandResult {
-            status: CommandStatus::Failure("Authentication required".into()),
-            message: Some("530 Please login with USER and PASS\r\n".into()),
+            status: CommandStatus::Failure("Not logged in".into()),
+            message: Some(responses::NOT_LOGGED_IN.into()),
         },
     }
 }
 
+/// Returns the directory depth limit that applies to the client's user
+fn max_directory_depth(client: &Client, startup_config: &StartupConfig) -> usize {
+    startup_config.max_directory_depth_for(client.username().map(String::as_str))
+}
+
+/// Returns the bandwidth limiter shared by the client's user, if they are capped
+fn bandwidth_limiter(
+    client: &Client,
+    startup_config: &StartupConfig,
+    direction: TransferDirection,
+) -> Option<Arc<BandwidthLimiter>> {
+    let username = client.username()?;
+    transfer::user_limiter(startup_config, username, direction)
+}
+
+/// Claims a slot for a RETR/STOR transfer, or builds the 450 reply if none frees up in time
+async fn claim_transfer_slot(
+    startup_config: &StartupConfig,
+) -> Result<Option<OwnedSemaphorePermit>, CommandResult> {
+    transfer::acquire_transfer_slot(startup_config)
+        .await
+        .map_err(|error| CommandResult {
+            status: CommandStatus::Failure(error.to_string()),
+            message: Some("450 Too many transfers, try later\r\n".into()),
+        })
+}
+
+/// Refuses a RETR/STOR once the session has transferred as many files as it may
+fn check_transfer_limit(client: &Client, startup_config: &StartupConfig) -> Option<CommandResult> {
+    let limit = startup_config.max_transfers_per_session(client.username().map(String::as_str))?;
+    (client.session_stats().files_transferred() >= limit as u64).then(|| CommandResult {
+        status: CommandStatus::Failure(format!("Session transfer limit of {limit} reached")),
+        message: Some("450 Transfer limit reached for this session\r\n".into()),
+    })
+}
+
 /// Handles the QUIT command
 fn handle_cmd_quit(client: &mut Client, channel_registry: &mut ChannelRegistry) -> CommandResult {
     let client_addr_str = client
@@ -114,17 +410,41 @@ fn handle_cmd_quit(client: &mut Client, channel_registry: &mut ChannelRegistry)
         transfer::cleanup_data_channel(channel_registry, client_addr);
     }
 
+    let summary = client.session_stats().to_string();
+    info!(
+        "Session summary for client {client_addr_str} (user: {}): {summary}",
+        client.username().map(String::as_str).unwrap_or("unknown")
+    );
+
     // Logout the client directly
     client.logout();
 
     info!("Client {client_addr_str} has quit successfully");
 
+    CommandResult {
+        status: CommandStatus::CloseConnection,
+        message: Some(format!("221-Session summary: {summary}\r\n221 Goodbye\r\n")),
+    }
+}
+
+/// Handles QUIT before login, when there is no session or data channel to clean up
+fn handle_auth_quit() -> CommandResult {
     CommandResult {
         status: CommandStatus::CloseConnection,
         message: Some("221 Goodbye\r\n".into()),
     }
 }
 
+/// Ends the current login before a mid-session USER, so the new user starts
+/// clean: no data channel, root directory and default session settings
+fn end_session(client: &mut Client, channel_registry: &mut ChannelRegistry) {
+    if let Some(client_addr) = client.client_addr() {
+        info!("Client {client_addr} sent USER mid-session, resetting session state");
+        transfer::cleanup_data_channel(channel_registry, client_addr);
+    }
+    client.reset_session();
+}
+
 /// Handles the USER command
 fn handle_cmd_user(
     client: &mut Client,
@@ -136,6 +456,14 @@ fn handle_cmd_user(
             // Update client state based on successful validation
             client.set_user_valid(true);
             client.set_logged_in(false);
+            client.set_needs_account(false);
+            client.set_account(None);
+            // One spelling, so [users.anonymous] applies however the client writes it
+            let username = if auth::is_anonymous(username) {
+                auth::ANONYMOUS_USER
+            } else {
+                username
+            };
             let _ = client.set_username(Some(username.to_string()), startup_config);
             CommandResult {
                 status: CommandStatus::Success,
@@ -153,6 +481,8 @@ fn handle_cmd_user(
                 AuthError::UserNotFound(u) => (530, format!("Unknown user '{u}'")),
                 AuthError::MalformedInput(_) => (530, "Malformed input".to_string()),
                 AuthError::InvalidPassword(u) => (530, format!("Invalid password for user: {u}")),
+                AuthError::WeakPassword(_) => (530, "Login incorrect".to_string()),
+                AuthError::AnonymousDisabled => (530, "Anonymous access not allowed".to_string()),
             };
 
             CommandResult {
@@ -188,7 +518,18 @@ fn handle_cmd_pass(
     };
 
     match auth::validate_password(&username, password, startup_config) {
+        Ok(_) if startup_config.required_account(&username).is_some() => {
+            // Login completes once the required account is supplied
+            client.set_needs_account(true);
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some("332 Need account for login\r\n".into()),
+            }
+        }
         Ok(_) => {
+            if let Some(refusal) = enter_home_directory(client, startup_config) {
+                return refusal;
+            }
             // Update client state for successful login
             client.set_logged_in(true);
             CommandResult {
@@ -205,6 +546,8 @@ fn handle_cmd_pass(
                 AuthError::UserNotFound(u) => (530, format!("Unknown user '{u}'")),
                 AuthError::MalformedInput(_) => (530, "Malformed input".to_string()),
                 AuthError::InvalidUsername(u) => (530, format!("Invalid username: {u}")),
+                AuthError::WeakPassword(_) => (530, "Login incorrect".to_string()),
+                AuthError::AnonymousDisabled => (530, "Anonymous access not allowed".to_string()),
             };
 
             CommandResult {
@@ -215,67 +558,96 @@ fn handle_cmd_pass(
     }
 }
 
-/// Handles the LIST command
-async fn handle_cmd_list<F>(
+/// Handles the ACCT command
+fn handle_cmd_acct(
     client: &mut Client,
+    account: &str,
     startup_config: &StartupConfig,
-    _runtime_config: &SharedRuntimeConfig,
-    channel_registry: &mut ChannelRegistry,
-    send_intermediate: &F,
-) -> CommandResult
-where
-    F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
-{
-    // Authentication and data channel validation
-    if !validate_client_and_data_channel(client) {
+) -> CommandResult {
+    if !client.needs_account() {
         if !client.is_logged_in() {
             return CommandResult {
-                status: CommandStatus::Failure("Not logged in".into()),
-                message: Some("530 Not logged in\r\n".into()),
+                status: CommandStatus::Failure("ACCT before login".into()),
+                message: Some("503 Login with USER and PASS first\r\n".into()),
             };
         }
+        client.set_account(Some(account.to_string()));
         return CommandResult {
-            status: CommandStatus::Failure("Data channel not initialized".into()),
-            message: Some("425 Data channel not initialized\r\n".into()),
+            status: CommandStatus::Success,
+            message: Some("202 Account not required\r\n".into()),
         };
     }
 
-    // 1. Send 150 IMMEDIATELY via callback
-    if send_intermediate("150 Opening ASCII mode data connection for file list\r\n")
-        .await
-        .is_err()
-    {
-        return CommandResult {
-            status: CommandStatus::Failure("Send failed".into()),
-            message: Some("421 Service not available\r\n".into()),
-        };
+    let required = client
+        .username()
+        .and_then(|username| startup_config.required_account(username));
+    if required == Some(account) {
+        if let Some(refusal) = enter_home_directory(client, startup_config) {
+            return refusal;
+        }
+        client.set_needs_account(false);
+        client.set_account(Some(account.to_string()));
+        client.set_logged_in(true);
+        CommandResult {
+            status: CommandStatus::Success,
+            message: Some("230 Login successful\r\n".into()),
+        }
+    } else {
+        CommandResult {
+            status: CommandStatus::Failure("Invalid account".into()),
+            message: Some("530 Invalid account\r\n".into()),
+        }
     }
+}
 
-    // Get client address
-    let client_addr = match client.client_addr() {
-        Some(addr) => *addr,
-        None => {
-            return CommandResult {
-                status: CommandStatus::Failure("Client address unknown".into()),
-                message: Some("530 Client address unknown\r\n".into()),
-            };
-        }
-    };
+/// Handles the LIST command
+///
+/// Leading `-` option flags (as in `LIST -la`) are stripped; of those only
+/// `a` is honoured, to include dot files. What follows is the directory or
+/// file to list.
+async fn handle_cmd_list<F>(
+    client: &mut Client,
+    argument: Option<&str>,
+    data_type: TransferType,
+    startup_config: &StartupConfig,
+    _runtime_config: &SharedRuntimeConfig,
+    channel_registry: &mut ChannelRegistry,
+    send_intermediate: &F,
+) -> CommandResult
+where
+    F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
+{
+    let (options, path) = split_list_options(argument.unwrap_or(""));
+    let show_hidden = options.iter().any(|option| option.contains(['a', 'A']));
+    let (path, pattern) = list_pattern(Some(path), startup_config);
 
-    // Get directory listing
+    // Get the listing first so a bad path is refused before any data connection
     let entries = match storage::list_directory(
-        &startup_config.server_root_path(),
+        &startup_config.storage_root(),
         client.current_virtual_path(),
+        path.filter(|path| !path.is_empty()),
+        pattern,
+        show_hidden,
+        startup_config.max_listing_entries,
+        max_directory_depth(client, startup_config),
     ) {
         Ok(entries) => entries,
+        Err(error) if error.is_backend_unavailable() => {
+            return storage_unavailable(client, error);
+        }
         Err(error) => {
             let (code, message) = match error {
-                crate::error::StorageError::DirectoryNotFound(p) => {
-                    (550, format!("{p}: Directory not found"))
+                crate::error::StorageError::FileNotFound(p) => {
+                    (550, format!("{p}: No such file or directory"))
+                }
+                crate::error::StorageError::NotADirectory(p) => {
+                    (550, format!("{p}: Not a directory"))
                 }
                 crate::error::StorageError::PermissionDenied(p) => {
                     (550, format!("{p}: Permission denied"))
                 }
+                crate::error::StorageError::InvalidPath(e) => (550, format!("Invalid path: {e}")),
+                crate::error::StorageError::PathTooLong => (550, "Path too long".to_string()),
                 crate::error::StorageError::IoError(e) => (550, format!("I/O error: {e}")),
                 _ => (550, "Directory listing failed".to_string()),
             };
@@ -286,108 +658,156 @@ where
         }
     };
 
-    // Send directory listing over data channel
-    match send_directory_listing(channel_registry, &client_addr, entries, startup_config) {
-        Ok(_) => {
-            // Clean up the stream but keep persistent setup
-            transfer::cleanup_data_stream_only(channel_registry, &client_addr);
-
-            CommandResult {
-                status: CommandStatus::Success,
-                message: Some("226 Directory send OK\r\n".into()),
-            }
-        }
-        Err(_) => {
-            transfer::cleanup_data_stream_only(channel_registry, &client_addr);
-            CommandResult {
-                status: CommandStatus::Failure("Transfer failed".into()),
-                message: Some("426 Transfer failed\r\n".into()),
-            }
-        }
-    }
+    let now = SystemTime::now();
+    let lines = entries
+        .iter()
+        .map(|entry| entry.ls_line(now, startup_config.list_time_zone))
+        .collect();
+    send_listing(
+        client,
+        lines,
+        data_type,
+        startup_config,
+        channel_registry,
+        send_intermediate,
+    )
+    .await
 }
 
-/// Handles the PWD command
-fn handle_cmd_pwd(client: &Client) -> CommandResult {
-    // Authentication check
-    if !client.is_logged_in() {
-        return CommandResult {
-            status: CommandStatus::Failure("Not logged in".into()),
-            message: Some("530 Not logged in\r\n".into()),
-        };
-    }
-
-    CommandResult {
-        status: CommandStatus::Success,
-        message: Some(format!("257 \"{}\"\r\n", client.current_virtual_path())),
+/// Splits a wildcard pattern off a LIST or NLST argument when `list_wildcards` is on
+fn list_pattern<'a>(
+    path: Option<&'a str>,
+    startup_config: &StartupConfig,
+) -> (Option<&'a str>, Option<&'a str>) {
+    match path {
+        Some(path) if startup_config.list_wildcards => match storage::wildcard::split_pattern(path)
+        {
+            Some((directory, pattern)) => (Some(directory), Some(pattern)),
+            None => (Some(path), None),
+        },
+        _ => (path, None),
     }
 }
 
-/// Handles the LOGOUT command
-fn handle_cmd_logout(client: &mut Client, channel_registry: &mut ChannelRegistry) -> CommandResult {
-    let client_addr_str = client
-        .client_addr()
-        .map(|addr| addr.to_string())
-        .unwrap_or_else(|| "unknown".to_string());
-
-    info!("Processing LOGOUT command for client {client_addr_str}");
+/// Handles the NLST command, listing names in the current or given directory
+async fn handle_cmd_nlst<F>(
+    client: &mut Client,
+    path: Option<&str>,
+    data_type: TransferType,
+    startup_config: &StartupConfig,
+    channel_registry: &mut ChannelRegistry,
+    send_intermediate: &F,
+) -> CommandResult
+where
+    F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
+{
+    let (path, pattern) = list_pattern(path, startup_config);
 
-    // Check if user is actually logged in
-    if !client.is_logged_in() {
-        info!("LOGOUT attempted by client {client_addr_str} who is not logged in");
-        return CommandResult {
-            status: CommandStatus::Failure("Not logged in".into()),
-            message: Some("530 User not logged in\r\n".into()),
-        };
-    }
+    // Resolve the listing first so a bad path is refused before any data connection
+    let names = match storage::list_names(
+        &startup_config.storage_root(),
+        client.current_virtual_path(),
+        path,
+        pattern,
+        startup_config.max_listing_entries,
+        max_directory_depth(client, startup_config),
+    ) {
+        Ok(names) => names,
+        Err(error) if error.is_backend_unavailable() => {
+            return storage_unavailable(client, error);
+        }
+        Err(error) => return listing_failure(error),
+    };
 
-    // Clean up any persistent data channels for this client
-    if let Some(client_addr) = client.client_addr() {
-        info!("Cleaning up data channels for logging out client {client_addr}");
-        transfer::cleanup_data_channel(channel_registry, client_addr);
-    }
+    send_listing(
+        client,
+        names,
+        data_type,
+        startup_config,
+        channel_registry,
+        send_intermediate,
+    )
+    .await
+}
 
-    // Logout the client directly
-    client.logout();
+/// Handles the MLSD command, listing the facts of each entry in a directory
+async fn handle_cmd_mlsd<F>(
+    client: &mut Client,
+    path: Option<&str>,
+    data_type: TransferType,
+    startup_config: &StartupConfig,
+    channel_registry: &mut ChannelRegistry,
+    send_intermediate: &F,
+) -> CommandResult
+where
+    F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
+{
+    let entries = match storage::list_facts(
+        &startup_config.storage_root(),
+        client.current_virtual_path(),
+        path,
+        client.mlst_facts(),
+        startup_config.max_listing_entries,
+        max_directory_depth(client, startup_config),
+    ) {
+        Ok(entries) => entries,
+        Err(error) if error.is_backend_unavailable() => {
+            return storage_unavailable(client, error);
+        }
+        Err(error) => return listing_failure(error),
+    };
 
-    info!("Client {client_addr_str} has logged out successfully");
+    send_listing(
+        client,
+        entries,
+        data_type,
+        startup_config,
+        channel_registry,
+        send_intermediate,
+    )
+    .await
+}
 
+/// Maps a failed directory lookup to a 550 reply
+fn listing_failure(error: crate::error::StorageError) -> CommandResult {
+    let message = match error {
+        crate::error::StorageError::DirectoryNotFound(p) => format!("{p}: No such directory"),
+        crate::error::StorageError::NotADirectory(p) => format!("{p}: Not a directory"),
+        crate::error::StorageError::PermissionDenied(p) => format!("{p}: Permission denied"),
+        crate::error::StorageError::InvalidPath(e) => format!("Invalid path: {e}"),
+        crate::error::StorageError::PathTooLong => "Path too long".to_string(),
+        crate::error::StorageError::IoError(e) => format!("I/O error: {e}"),
+        _ => "Directory listing failed".to_string(),
+    };
     CommandResult {
-        status: CommandStatus::Success,
-        message: Some("221 Logout successful\r\n".into()),
+        status: CommandStatus::Failure(message.clone()),
+        message: Some(format!("550 {message}\r\n")),
     }
 }
 
-/// Handles the RETR command
-async fn handle_cmd_retr<F>(
+/// Sends an already resolved LIST, NLST or MLSD listing over the data connection
+async fn send_listing<F>(
     client: &mut Client,
-    filename: &str,
-    channel_registry: &mut ChannelRegistry,
+    listing: Vec<String>,
+    data_type: TransferType,
     startup_config: &StartupConfig,
-    _runtime_config: &SharedRuntimeConfig,
+    channel_registry: &mut ChannelRegistry,
     send_intermediate: &F,
 ) -> CommandResult
 where
     F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
 {
-    // Authentication and data channel validation
-    if !validate_client_and_data_channel(client) {
-        if !client.is_logged_in() {
-            return CommandResult {
-                status: CommandStatus::Failure("Not logged in".into()),
-                message: Some("530 Not logged in\r\n".into()),
-            };
-        }
-        return CommandResult {
-            status: CommandStatus::Failure("Data channel not initialized".into()),
-            message: Some("425 Data channel not initialized\r\n".into()),
-        };
-    }
+    let client_addr = match data_client_addr(client) {
+        Ok(addr) => addr,
+        Err(rejection) => return rejection,
+    };
 
-    // 1. Send 150 IMMEDIATELY via callback
-    if send_intermediate("150 Opening BINARY mode data connection for file transfer\r\n")
-        .await
-        .is_err()
+    if send_intermediate(&format!(
+        "150 Opening {} mode data connection for file list\r\n",
+        data_type.label()
+    ))
+    .await
+    .is_err()
     {
         return CommandResult {
             status: CommandStatus::Failure("Send failed".into()),
@@ -395,22 +815,263 @@ where
         };
     }
 
-    // Prepare file retrieval
+    let Some(connection) = DataConnection::take(channel_registry, &client_addr, startup_config)
+    else {
+        return CommandResult {
+            status: CommandStatus::Failure("Data connection failed".into()),
+            message: Some(responses::DATA_CONNECTION_FAILED.into()),
+        };
+    };
+    let mode = client.transfer_mode();
+    let latin1 = client.uses_latin1(startup_config);
+    let cancel = client.transfer_cancel().clone();
+
+    let transfer = PendingTransfer::new(None, move |startup_config| {
+        let result = send_directory_listing(
+            connection,
+            &client_addr,
+            listing,
+            mode,
+            latin1,
+            startup_config,
+            &cancel,
+        );
+        let aborted = cancel.is_cancelled();
+        Box::new(move |_| match result {
+            Ok(false) => CommandResult {
+                status: CommandStatus::Success,
+                message: Some("226 Directory send OK\r\n".into()),
+            },
+            Ok(true) => CommandResult {
+                status: CommandStatus::Success,
+                message: Some("226 Partial listing (truncated)\r\n".into()),
+            },
+            Err(TransferError::DataChannelSetupFailed(_)) => data_connection_failed(aborted),
+            Err(_) => CommandResult {
+                status: CommandStatus::Failure("Transfer failed".into()),
+                message: Some("426 Transfer failed\r\n".into()),
+            },
+        })
+    });
+    CommandResult {
+        status: CommandStatus::Transfer(transfer),
+        message: None,
+    }
+}
+
+/// Replies to a transfer whose data connection could not be opened
+///
+/// One given up on because of ABOR gets 426, as an aborted transfer would.
+fn data_connection_failed(aborted: bool) -> CommandResult {
+    if aborted {
+        CommandResult {
+            status: CommandStatus::Failure("Transfer aborted".into()),
+            message: Some(responses::TRANSFER_ABORTED.into()),
+        }
+    } else {
+        CommandResult {
+            status: CommandStatus::Failure("Data connection failed".into()),
+            message: Some(responses::DATA_CONNECTION_FAILED.into()),
+        }
+    }
+}
+
+/// Handles the MLST command, describing a single file or directory on the control connection
+fn handle_cmd_mlst(
+    client: &mut Client,
+    path: Option<&str>,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    match storage::describe_entry(
+        &startup_config.storage_root(),
+        client.current_virtual_path(),
+        path,
+        client.mlst_facts(),
+        max_directory_depth(client, startup_config),
+    ) {
+        Ok(entry) => CommandResult {
+            status: CommandStatus::Success,
+            message: Some(format!(
+                "250-Listing {}\r\n {entry}\r\n250 End\r\n",
+                path.unwrap_or(client.current_virtual_path())
+            )),
+        },
+        Err(error) if error.is_backend_unavailable() => storage_unavailable(client, error),
+        Err(crate::error::StorageError::FileNotFound(p)) => CommandResult {
+            status: CommandStatus::Failure(format!("{p}: No such file or directory")),
+            message: Some(format!("550 {p}: No such file or directory\r\n")),
+        },
+        Err(error) => listing_failure(error),
+    }
+}
+
+/// Handles the OPTS command, for `OPTS MLST`, `OPTS HASH`, `OPTS MDTM` and `OPTS UTF8`
+fn handle_cmd_opts(client: &mut Client, args: &str) -> CommandResult {
+    let (option, value) = args.split_once(' ').unwrap_or((args, ""));
+    match option.to_ascii_uppercase().as_str() {
+        "MLST" => {
+            let facts = FactSet::parse(value);
+            client.set_mlst_facts(facts);
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!("200 MLST OPTS {}\r\n", facts.selected())),
+            }
+        }
+        "HASH" => {
+            let name = value.trim();
+            if !name.is_empty() {
+                let Some(algorithm) = HashAlgorithm::parse(name) else {
+                    return CommandResult {
+                        status: CommandStatus::Failure(format!("Unknown hash algorithm {name}")),
+                        message: Some("501 Unknown algorithm\r\n".into()),
+                    };
+                };
+                client.set_hash_algorithm(algorithm);
+            }
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!("200 {}\r\n", client.hash_algorithm().name())),
+            }
+        }
+        // Not a standard option: FRACTIONAL adds milliseconds to MDTM replies,
+        // which sync tools use to tell files saved within the same second apart
+        "MDTM" => {
+            match value.trim().to_ascii_uppercase().as_str() {
+                "" => {}
+                "FRACTIONAL" => client.set_mdtm_millis(true),
+                "SECONDS" => client.set_mdtm_millis(false),
+                _ => {
+                    return CommandResult {
+                        status: CommandStatus::Failure(format!("Invalid MDTM option {value}")),
+                        message: Some("501 Option not understood\r\n".into()),
+                    };
+                }
+            }
+            let precision = if client.mdtm_millis() {
+                "FRACTIONAL"
+            } else {
+                "SECONDS"
+            };
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!("200 MDTM {precision}\r\n")),
+            }
+        }
+        // Commands are UTF-8 either way, unless latin1_fallback applies while it is off
+        "UTF8" => match value.trim().to_ascii_uppercase().as_str() {
+            "" | "ON" => {
+                client.set_utf8(true);
+                CommandResult {
+                    status: CommandStatus::Success,
+                    message: Some("200 Always in UTF8 mode\r\n".into()),
+                }
+            }
+            "OFF" => {
+                client.set_utf8(false);
+                CommandResult {
+                    status: CommandStatus::Success,
+                    message: Some("200 UTF8 mode off\r\n".into()),
+                }
+            }
+            _ => CommandResult {
+                status: CommandStatus::Failure(format!("Invalid UTF8 option {value}")),
+                message: Some("501 Option not understood\r\n".into()),
+            },
+        },
+        _ => CommandResult {
+            status: CommandStatus::Failure(format!("Unsupported option {option}")),
+            message: Some("501 Option not understood\r\n".into()),
+        },
+    }
+}
+
+/// Handles the PWD command
+fn handle_cmd_pwd(client: &Client) -> CommandResult {
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some(format!("257 \"{}\"\r\n", client.current_virtual_path())),
+    }
+}
+
+/// Handles the LOGOUT command
+fn handle_cmd_logout(client: &mut Client, channel_registry: &mut ChannelRegistry) -> CommandResult {
+    let client_addr_str = client
+        .client_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    info!("Processing LOGOUT command for client {client_addr_str}");
+
+    // Clean up any persistent data channels for this client
+    if let Some(client_addr) = client.client_addr() {
+        info!("Cleaning up data channels for logging out client {client_addr}");
+        transfer::cleanup_data_channel(channel_registry, client_addr);
+    }
+
+    // Logout the client directly
+    client.logout();
+
+    info!("Client {client_addr_str} has logged out successfully");
+
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some("221 Logout successful\r\n".into()),
+    }
+}
+
+/// Handles the RETR command
+async fn handle_cmd_retr<F>(
+    client: &mut Client,
+    filename: &str,
+    data_type: TransferType,
+    channel_registry: &mut ChannelRegistry,
+    startup_config: &StartupConfig,
+    _runtime_config: &SharedRuntimeConfig,
+    send_intermediate: &F,
+) -> CommandResult
+where
+    F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
+{
+    if let Some(rejection) = check_transfer_limit(client, startup_config) {
+        return rejection;
+    }
+
+    // Wait for a free transfer slot, held until the transfer has run
+    let slot = match claim_transfer_slot(startup_config).await {
+        Ok(slot) => slot,
+        Err(rejection) => return rejection,
+    };
+
+    // Consume any restart offset set by a preceding REST, which TYPE A may
+    // have been switched on after
+    let offset = client.take_restart_offset().unwrap_or(0);
+    if offset > 0 && data_type == TransferType::Ascii {
+        return ascii_restart_rejection();
+    }
+
+    // Prepare file retrieval first so a bad path is refused without a 150
     let file_path = match storage::prepare_file_retrieval(
-        &startup_config.server_root_path(),
+        &startup_config.storage_root(),
         client.current_virtual_path(),
         filename,
-        startup_config,
+        max_directory_depth(client, startup_config),
     ) {
         Ok(path) => path,
+        Err(error) if error.is_backend_unavailable() => {
+            return storage_unavailable(client, error);
+        }
         Err(error) => {
             let (code, message) = match error {
                 crate::error::StorageError::FileNotFound(p) => {
                     (550, format!("{p}: File not found"))
                 }
+                crate::error::StorageError::NotAFile(p) => {
+                    (550, format!("{p}: Not a regular file"))
+                }
                 crate::error::StorageError::PermissionDenied(p) => {
                     (550, format!("{p}: Permission denied"))
                 }
+                crate::error::StorageError::PathTooLong => (550, "Path too long".to_string()),
                 crate::error::StorageError::IoError(e) => (550, format!("I/O error: {e}")),
                 _ => (550, "File retrieval failed".to_string()),
             };
@@ -421,15 +1082,24 @@ where
         }
     };
 
+    // Send 150 once the file is known to be retrievable
+    if send_intermediate(&format!(
+        "150 Opening {} mode data connection for file transfer\r\n",
+        data_type.label()
+    ))
+    .await
+    .is_err()
+    {
+        return CommandResult {
+            status: CommandStatus::Failure("Send failed".into()),
+            message: Some("421 Service not available\r\n".into()),
+        };
+    }
+
     // Get client address
-    let client_addr = match client.client_addr() {
-        Some(addr) => *addr,
-        None => {
-            return CommandResult {
-                status: CommandStatus::Failure("Client address unknown".into()),
-                message: Some("530 Client address unknown\r\n".into()),
-            };
-        }
+    let client_addr = match data_client_addr(client) {
+        Ok(addr) => addr,
+        Err(rejection) => return rejection,
     };
 
     info!(
@@ -439,41 +1109,59 @@ where
         file_path.display()
     );
 
-    // Setup data stream and perform file download
-    let data_stream = match setup_data_stream(channel_registry, &client_addr, startup_config) {
-        Some(stream) => stream,
-        None => {
-            return CommandResult {
-                status: CommandStatus::Failure("Failed to establish data connection".into()),
-                message: Some("425 Failed to establish data connection\r\n".into()),
-            };
-        }
+    // The connection is opened and the file sent once the session is unlocked
+    let Some(connection) = DataConnection::take(channel_registry, &client_addr, startup_config)
+    else {
+        return CommandResult {
+            status: CommandStatus::Failure("Failed to establish data connection".into()),
+            message: Some(responses::DATA_CONNECTION_FAILED.into()),
+        };
+    };
+    let options = DownloadOptions {
+        transfer_type: data_type,
+        mode: client.transfer_mode(),
+        offset,
+        limiter: bandwidth_limiter(client, startup_config, TransferDirection::Download),
+        cancel: client.transfer_cancel().clone(),
     };
 
-    // Delegate file download to transfer module
-    match crate::transfer::handle_file_download(
-        data_stream,
-        &file_path.to_string_lossy(),
-        startup_config,
-    ) {
-        Ok((status, _)) => {
-            // Clean up only the data stream, keep persistent setup
-            transfer::cleanup_data_stream_only(channel_registry, &client_addr);
+    let transfer = PendingTransfer::new(slot, move |startup_config| {
+        let started = Instant::now();
+        let Some(data_stream) = connection.open(startup_config, &options.cancel) else {
+            let aborted = options.cancel.is_cancelled();
+            return Box::new(move |_| data_connection_failed(aborted));
+        };
+        let result = crate::transfer::handle_file_download(
+            data_stream,
+            &file_path.to_string_lossy(),
+            options,
+            startup_config,
+        );
+        Box::new(move |client: &mut Client| match result {
+            Ok(bytes) => {
+                client.record_download(bytes);
+                logging::log_transfer(
+                    client_addr,
+                    client.username().map(String::as_str),
+                    "RETR",
+                    bytes,
+                    started.elapsed(),
+                );
 
-            CommandResult {
-                status,
-                message: Some("226 Transfer complete\r\n".into()),
+                CommandResult {
+                    status: CommandStatus::Success,
+                    message: Some("226 Transfer complete\r\n".into()),
+                }
             }
-        }
-        Err((status, _)) => {
-            // Clean up only the data stream on error
-            transfer::cleanup_data_stream_only(channel_registry, &client_addr);
-
-            CommandResult {
+            Err((status, message)) => CommandResult {
                 status,
-                message: Some("426 Transfer failed\r\n".into()),
-            }
-        }
+                message: Some(message.into()),
+            },
+        })
+    });
+    CommandResult {
+        status: CommandStatus::Transfer(transfer),
+        message: None,
     }
 }
 
@@ -481,6 +1169,7 @@ where
 async fn handle_cmd_stor<F>(
     client: &mut Client,
     filename: &str,
+    data_type: TransferType,
     channel_registry: &mut ChannelRegistry,
     startup_config: &StartupConfig,
     runtime_config: &SharedRuntimeConfig,
@@ -489,24 +1178,34 @@ async fn handle_cmd_stor<F>(
 where
     F: Fn(&str) -> Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>,
 {
-    // Authentication and data channel validation
-    if !validate_client_and_data_channel(client) {
-        if !client.is_logged_in() {
-            return CommandResult {
-                status: CommandStatus::Failure("Not logged in".into()),
-                message: Some("530 Not logged in\r\n".into()),
-            };
-        }
+    // Resuming uploads is not supported, so refuse a pending restart offset
+    if client
+        .take_restart_offset()
+        .is_some_and(|offset| offset > 0)
+    {
         return CommandResult {
-            status: CommandStatus::Failure("Data channel not initialized".into()),
-            message: Some("425 Data channel not initialized\r\n".into()),
+            status: CommandStatus::Failure("REST not supported for STOR".into()),
+            message: Some("554 Restart not supported for uploads\r\n".into()),
         };
     }
 
+    if let Some(rejection) = check_transfer_limit(client, startup_config) {
+        return rejection;
+    }
+
+    // Wait for a free transfer slot, held until the transfer has run
+    let slot = match claim_transfer_slot(startup_config).await {
+        Ok(slot) => slot,
+        Err(rejection) => return rejection,
+    };
+
     // 1. Send 150 IMMEDIATELY via callback
-    if send_intermediate("150 Opening BINARY mode data connection for file transfer\r\n")
-        .await
-        .is_err()
+    if send_intermediate(&format!(
+        "150 Opening {} mode data connection for file transfer\r\n",
+        data_type.label()
+    ))
+    .await
+    .is_err()
     {
         return CommandResult {
             status: CommandStatus::Failure("Send failed".into()),
@@ -516,12 +1215,16 @@ where
 
     // Prepare file storage
     let (file_path, temp_path) = match storage::prepare_file_storage(
-        &startup_config.server_root_path(),
+        &startup_config.storage_root(),
         client.current_virtual_path(),
         filename,
-        startup_config,
+        max_directory_depth(client, startup_config),
+        startup_config.temp_upload_dir_path().as_deref(),
     ) {
         Ok((file_path, temp_path)) => (file_path, temp_path),
+        Err(error) if error.is_backend_unavailable() => {
+            return storage_unavailable(client, error);
+        }
         Err(error) => {
             let (code, message) = match error {
                 crate::error::StorageError::FileAlreadyExists(p) => {
@@ -533,6 +1236,13 @@ where
                 crate::error::StorageError::UploadInProgress(p) => {
                     (550, format!("{p}: Upload already in progress"))
                 }
+                crate::error::StorageError::NotADirectory(p) => {
+                    (550, format!("{p}: Not a directory"))
+                }
+                crate::error::StorageError::DirectoryNotFound(p) => {
+                    (550, format!("{p}: No such directory"))
+                }
+                crate::error::StorageError::PathTooLong => (550, "Path too long".to_string()),
                 crate::error::StorageError::IoError(e) => (550, format!("I/O error: {e}")),
                 _ => (550, "File storage preparation failed".to_string()),
             };
@@ -544,16 +1254,20 @@ where
     };
 
     // Get client address
-    let client_addr = match client.client_addr() {
-        Some(addr) => *addr,
-        None => {
-            return CommandResult {
-                status: CommandStatus::Failure("Client address unknown".into()),
-                message: Some("530 Client address unknown\r\n".into()),
-            };
-        }
+    let client_addr = match data_client_addr(client) {
+        Ok(addr) => addr,
+        Err(rejection) => return rejection,
     };
 
+    // Pick up a modification time declared by SITE UPLOADMTIME before the upload
+    let modified = storage::validation::resolve_file_path(
+        client.current_virtual_path(),
+        filename,
+        max_directory_depth(client, startup_config),
+    )
+    .ok()
+    .and_then(|virtual_path| client.take_pending_mtime(&virtual_path));
+
     info!(
         "Client {} requested to store {} (real: {})",
         client_addr,
@@ -561,56 +1275,83 @@ where
         file_path.display()
     );
 
-    // Receive file upload over data channel
-    match receive_file_upload(
-        channel_registry,
-        &client_addr,
-        &file_path.to_string_lossy(),
-        &temp_path.to_string_lossy(),
-        startup_config,
-        runtime_config,
-    )
-    .await
-    {
-        Ok(_) => {
-            // Clean up the stream but keep persistent setup
-            transfer::cleanup_data_stream_only(channel_registry, &client_addr);
+    let Some(connection) = DataConnection::take(channel_registry, &client_addr, startup_config)
+    else {
+        return CommandResult {
+            status: CommandStatus::Failure("Data connection failed".into()),
+            message: Some(responses::DATA_CONNECTION_FAILED.into()),
+        };
+    };
+    let options = UploadOptions {
+        transfer_type: data_type,
+        mode: client.transfer_mode(),
+        modified,
+        limiter: bandwidth_limiter(client, startup_config, TransferDirection::Upload),
+        umask: startup_config.umask,
+        cancel: client.transfer_cancel().clone(),
+    };
+    // Read now, since the runtime config can be updated while the session runs
+    let max_file_size = runtime_config.read().await.max_file_size_bytes();
 
-            CommandResult {
-                status: CommandStatus::Success,
-                message: Some("226 Transfer complete\r\n".into()),
+    // Receive file upload over data channel once the session is unlocked
+    let transfer = PendingTransfer::new(slot, move |startup_config| {
+        let started = Instant::now();
+        let cancel = options.cancel.clone();
+        let result = receive_file_upload(
+            connection,
+            &client_addr,
+            &file_path.to_string_lossy(),
+            &temp_path.to_string_lossy(),
+            options,
+            startup_config,
+            max_file_size,
+        );
+        let aborted = cancel.is_cancelled();
+        Box::new(move |client: &mut Client| match result {
+            Ok(bytes) => {
+                client.record_upload(bytes);
+                client.hooks().upload_complete(
+                    &file_path,
+                    client.username().map(String::as_str).unwrap_or_default(),
+                );
+                logging::log_transfer(
+                    client_addr,
+                    client.username().map(String::as_str),
+                    "STOR",
+                    bytes,
+                    started.elapsed(),
+                );
+
+                CommandResult {
+                    status: CommandStatus::Success,
+                    message: Some("226 Transfer complete\r\n".into()),
+                }
             }
-        }
-        Err(_) => {
-            transfer::cleanup_data_stream_only(channel_registry, &client_addr);
-            CommandResult {
+            Err(TransferError::DataChannelSetupFailed(_)) => data_connection_failed(aborted),
+            Err(_) => CommandResult {
                 status: CommandStatus::Failure("Transfer failed".into()),
                 message: Some("426 Transfer failed\r\n".into()),
-            }
-        }
+            },
+        })
+    });
+    CommandResult {
+        status: CommandStatus::Transfer(transfer),
+        message: None,
     }
 }
 
 /// Handles the DEL command
 fn handle_cmd_del(
-    client: &Client,
+    client: &mut Client,
     filename: &str,
     startup_config: &StartupConfig,
 ) -> CommandResult {
-    // Authentication check
-    if !client.is_logged_in() {
-        return CommandResult {
-            status: CommandStatus::Failure("Not logged in".into()),
-            message: Some("530 Not logged in\r\n".into()),
-        };
-    }
-
     // Delete file
     match storage::delete_file(
-        &startup_config.server_root_path(),
+        &startup_config.storage_root(),
         client.current_virtual_path(),
         filename,
-        startup_config,
+        max_directory_depth(client, startup_config),
     ) {
         Ok(_) => {
             info!(
@@ -626,14 +1367,19 @@ fn handle_cmd_del(
                 message: Some("250 File deleted successfully\r\n".into()),
             }
         }
+        Err(error) if error.is_backend_unavailable() => storage_unavailable(client, error),
         Err(error) => {
             let (code, message) = match error {
                 crate::error::StorageError::FileNotFound(p) => {
                     (550, format!("{p}: File not found"))
                 }
+                crate::error::StorageError::NotAFile(p) => {
+                    (550, format!("{p}: Not a regular file"))
+                }
                 crate::error::StorageError::PermissionDenied(p) => {
                     (550, format!("{p}: Permission denied"))
                 }
+                crate::error::StorageError::PathTooLong => (550, "Path too long".to_string()),
                 crate::error::StorageError::IoError(e) => (550, format!("I/O error: {e}")),
                 _ => (550, "File deletion failed".to_string()),
             };
@@ -645,60 +1391,60 @@ fn handle_cmd_del(
     }
 }
 
-/// Handles the CWD command
-fn handle_cmd_cwd(
+/// Handles the MKD command
+fn handle_cmd_mkd(
     client: &mut Client,
-    path: &str,
+    dirname: &str,
     startup_config: &StartupConfig,
 ) -> CommandResult {
-    // Authentication check
-    if !client.is_logged_in() {
-        return CommandResult {
-            status: CommandStatus::Failure("Not logged in".into()),
-            message: Some("530 Not logged in\r\n".into()),
-        };
-    }
-
-    // Change directory
-    match navigate::change_directory(
-        &startup_config.server_root_path(),
+    match storage::create_directory(
+        &startup_config.storage_root(),
         client.current_virtual_path(),
-        path,
-        startup_config,
+        dirname,
+        max_directory_depth(client, startup_config),
+        startup_config.umask,
     ) {
-        Ok(new_virtual_path) => {
-            // Update client's virtual path
-            let _ = client.set_current_virtual_path(new_virtual_path.clone());
-
+        Ok(created) => {
             info!(
-                "Client {} changed directory to {}",
+                "Client {} created directory {}",
                 client
                     .client_addr()
                     .map(|a| a.to_string())
                     .unwrap_or_else(|| "unknown".to_string()),
-                new_virtual_path
+                created.virtual_path
             );
-
+            // Extra text after the quoted path is allowed, so clients still parse it
+            let mode = match created.mode {
+                Some(mode) if startup_config.mkd_reports_mode => format!(" (mode {mode:04o})"),
+                _ => String::new(),
+            };
             CommandResult {
                 status: CommandStatus::Success,
-                message: Some("250 Directory changed successfully\r\n".into()),
+                message: Some(format!(
+                    "257 \"{}\" created{mode}\r\n",
+                    created.virtual_path.replace('"', "\"\"")
+                )),
             }
         }
+        Err(error) if error.is_backend_unavailable() => storage_unavailable(client, error),
         Err(error) => {
             let (code, message) = match error {
-                crate::error::NavigateError::DirectoryNotFound(p) => {
-                    (550, format!("{p}: Directory not found"))
+                crate::error::StorageError::FileAlreadyExists(p) => {
+                    (550, format!("{p}: File exists"))
                 }
-                crate::error::NavigateError::NotADirectory(p) => {
+                crate::error::StorageError::DirectoryNotFound(p) => {
+                    (550, format!("{p}: No such directory"))
+                }
+                crate::error::StorageError::NotADirectory(p) => {
                     (550, format!("{p}: Not a directory"))
                 }
-                crate::error::NavigateError::PermissionDenied(p) => {
+                crate::error::StorageError::PermissionDenied(p) => {
                     (550, format!("{p}: Permission denied"))
                 }
-                crate::error::NavigateError::PathTraversal(p) => {
-                    (550, format!("Path traversal attempt: {p}"))
-                }
-                _ => (550, "Directory change failed".to_string()),
+                crate::error::StorageError::InvalidPath(e) => (550, format!("Invalid path: {e}")),
+                crate::error::StorageError::PathTooLong => (550, "Path too long".to_string()),
+                crate::error::StorageError::IoError(e) => (550, format!("I/O error: {e}")),
+                _ => (550, "Directory creation failed".to_string()),
             };
             CommandResult {
                 status: CommandStatus::Failure(message.clone()),
@@ -708,52 +1454,51 @@ fn handle_cmd_cwd(
     }
 }
 
-/// Handles the PASV command
-fn handle_cmd_pasv(
+/// Handles the RMD command
+fn handle_cmd_rmd(
     client: &mut Client,
-    channel_registry: &mut ChannelRegistry,
+    dirname: &str,
     startup_config: &StartupConfig,
 ) -> CommandResult {
-    // Authentication check
-    if !client.is_logged_in() {
-        return CommandResult {
-            status: CommandStatus::Failure("Not logged in".into()),
-            message: Some("530 Not logged in\r\n".into()),
-        };
-    }
-
-    let client_addr = match client.client_addr() {
-        Some(addr) => *addr,
-        None => {
-            return CommandResult {
-                status: CommandStatus::Failure("Client address unknown".into()),
-                message: Some("530 Client address unknown\r\n".into()),
-            };
-        }
-    };
-
-    // Setup passive mode (this will replace any existing setup)
-    match transfer::setup_passive_mode(channel_registry, client_addr, startup_config) {
-        Ok(data_socket) => {
-            client.set_data_channel_init(true);
+    match storage::remove_directory(
+        &startup_config.storage_root(),
+        client.current_virtual_path(),
+        dirname,
+        max_directory_depth(client, startup_config),
+    ) {
+        Ok(()) => {
             info!(
-                "Sending PASV response to client {client_addr}: 227 Entering Passive Mode ({data_socket})"
+                "Client {} removed directory {}",
+                client
+                    .client_addr()
+                    .map(|a| a.to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                dirname
             );
             CommandResult {
                 status: CommandStatus::Success,
-                message: Some(format!("227 Entering Passive Mode ({data_socket})\r\n")),
+                message: Some("250 Directory removed\r\n".into()),
             }
         }
+        Err(error) if error.is_backend_unavailable() => storage_unavailable(client, error),
         Err(error) => {
             let (code, message) = match error {
-                TransferError::NoAvailablePort => (425, "No available port".to_string()),
-                TransferError::PortBindingFailed(addr, e) => {
-                    (425, format!("Can't bind to {addr}: {e}"))
+                crate::error::StorageError::DirectoryNotFound(p) => {
+                    (550, format!("{p}: No such directory"))
                 }
-                TransferError::ListenerConfigurationFailed(e) => {
-                    (425, format!("Listener config failed: {e}"))
+                crate::error::StorageError::NotADirectory(p) => {
+                    (550, format!("{p}: Not a directory"))
                 }
-                _ => (425, "Passive mode setup failed".to_string()),
+                crate::error::StorageError::DirectoryNotEmpty(p) => {
+                    (550, format!("{p}: Directory not empty"))
+                }
+                crate::error::StorageError::PermissionDenied(p) => {
+                    (550, format!("{p}: Permission denied"))
+                }
+                crate::error::StorageError::InvalidPath(e) => (550, format!("Invalid path: {e}")),
+                crate::error::StorageError::PathTooLong => (550, "Path too long".to_string()),
+                crate::error::StorageError::IoError(e) => (550, format!("I/O error: {e}")),
+                _ => (550, "Directory removal failed".to_string()),
             };
             CommandResult {
                 status: CommandStatus::Failure(message.clone()),
@@ -763,29 +1508,185 @@ fn handle_cmd_pasv(
     }
 }
 
-/// Handles the PORT command
-fn handle_cmd_port(
+/// Handles the CWD command
+fn handle_cmd_cwd(
+    client: &mut Client,
+    path: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    // Change directory
+    match navigate::change_directory(
+        &startup_config.storage_root(),
+        client.current_virtual_path(),
+        path,
+        max_directory_depth(client, startup_config),
+    ) {
+        Ok(new_virtual_path) => {
+            // Update client's virtual path
+            let _ = client.set_current_virtual_path(new_virtual_path.clone());
+
+            info!(
+                "Client {} changed directory to {}",
+                client
+                    .client_addr()
+                    .map(|a| a.to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                new_virtual_path
+            );
+
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some("250 Directory changed successfully\r\n".into()),
+            }
+        }
+        Err(error) => {
+            let (code, message) = match error {
+                crate::error::NavigateError::DirectoryNotFound(p) => {
+                    (550, format!("{p}: Directory not found"))
+                }
+                crate::error::NavigateError::NotADirectory(p) => {
+                    (550, format!("{p}: Not a directory"))
+                }
+                crate::error::NavigateError::PermissionDenied(p) => {
+                    (550, format!("{p}: Permission denied"))
+                }
+                crate::error::NavigateError::PathTraversal(p) => {
+                    (550, format!("Path traversal attempt: {p}"))
+                }
+                crate::error::NavigateError::PathTooLong => (550, "Path too long".to_string()),
+                _ => (550, "Directory change failed".to_string()),
+            };
+            CommandResult {
+                status: CommandStatus::Failure(message.clone()),
+                message: Some(format!("{code} {message}\r\n")),
+            }
+        }
+    }
+}
+
+/// Handles the PASV command
+fn handle_cmd_pasv(
     client: &mut Client,
     channel_registry: &mut ChannelRegistry,
-    addr: &str,
     startup_config: &StartupConfig,
 ) -> CommandResult {
-    // Authentication check
-    if !client.is_logged_in() {
-        return CommandResult {
-            status: CommandStatus::Failure("Not logged in".into()),
-            message: Some("530 Not logged in\r\n".into()),
-        };
+    if let Some(rejection) = check_epsv_all(client, "PASV") {
+        return rejection;
     }
 
-    let client_addr = match client.client_addr() {
-        Some(addr) => *addr,
-        None => {
+    let client_addr = match data_client_addr(client) {
+        Ok(addr) => addr,
+        Err(rejection) => return rejection,
+    };
+
+    // Setup passive mode (this will replace any existing setup)
+    match transfer::setup_passive_mode(channel_registry, client_addr, startup_config) {
+        Ok(data_socket) => {
+            client.set_data_channel_init(true);
+            info!(
+                "Sending PASV response to client {client_addr}: 227 Entering Passive Mode ({data_socket})"
+            );
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!("227 Entering Passive Mode ({data_socket})\r\n")),
+            }
+        }
+        Err(error) => passive_mode_error(error),
+    }
+}
+
+/// Handles the EPSV command (RFC 2428)
+///
+/// `EPSV ALL` locks the session into extended passive mode: later PASV and
+/// PORT commands are refused, while EPSV keeps working.
+fn handle_cmd_epsv(
+    client: &mut Client,
+    channel_registry: &mut ChannelRegistry,
+    argument: Option<&str>,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let client_addr = match data_client_addr(client) {
+        Ok(addr) => addr,
+        Err(rejection) => return rejection,
+    };
+
+    // RFC 2428 network protocol numbers: 1 for IPv4, 2 for IPv6
+    let protocol = if client_addr.is_ipv4() { "1" } else { "2" };
+    match argument {
+        Some(arg) if arg.eq_ignore_ascii_case("ALL") => {
+            client.set_epsv_all();
             return CommandResult {
-                status: CommandStatus::Failure("Client address unknown".into()),
-                message: Some("530 Client address unknown\r\n".into()),
+                status: CommandStatus::Success,
+                message: Some("200 EPSV ALL ok\r\n".into()),
+            };
+        }
+        Some(arg) if arg != protocol => {
+            return CommandResult {
+                status: CommandStatus::Failure(format!("Unsupported network protocol: {arg}")),
+                message: Some(format!(
+                    "522 Network protocol not supported, use ({protocol})\r\n"
+                )),
             };
         }
+        _ => {}
+    }
+
+    match transfer::setup_passive_mode(channel_registry, client_addr, startup_config) {
+        Ok(data_socket) => {
+            client.set_data_channel_init(true);
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!(
+                    "229 Entering Extended Passive Mode (|||{}|)\r\n",
+                    data_socket.port()
+                )),
+            }
+        }
+        Err(error) => passive_mode_error(error),
+    }
+}
+
+/// Refuses data channel setup other than EPSV once the client has sent `EPSV ALL`
+fn check_epsv_all(client: &Client, verb: &str) -> Option<CommandResult> {
+    client.is_epsv_all().then(|| CommandResult {
+        status: CommandStatus::Failure(format!("{verb} refused after EPSV ALL")),
+        message: Some(format!("503 {verb} not allowed after EPSV ALL\r\n")),
+    })
+}
+
+/// Translates a failed passive mode setup into an FTP response
+fn passive_mode_error(error: TransferError) -> CommandResult {
+    let (code, message) = match error {
+        TransferError::NoAvailablePort => (425, "No available port".to_string()),
+        TransferError::PassiveListenerLimitReached(_) => {
+            (425, "No available data port, try again later".to_string())
+        }
+        TransferError::PortBindingFailed(addr, e) => (425, format!("Can't bind to {addr}: {e}")),
+        TransferError::ListenerConfigurationFailed(e) => {
+            (425, format!("Listener config failed: {e}"))
+        }
+        _ => (425, "Passive mode setup failed".to_string()),
+    };
+    CommandResult {
+        status: CommandStatus::Failure(message.clone()),
+        message: Some(format!("{code} {message}\r\n")),
+    }
+}
+
+/// Handles the PORT command
+fn handle_cmd_port(
+    client: &mut Client,
+    channel_registry: &mut ChannelRegistry,
+    addr: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    if let Some(rejection) = check_epsv_all(client, "PORT") {
+        return rejection;
+    }
+
+    let client_addr = match data_client_addr(client) {
+        Ok(addr) => addr,
+        Err(rejection) => return rejection,
     };
 
     // Setup active mode (this will replace any existing setup)
@@ -804,13 +1705,13 @@ fn handle_cmd_port(
                     501,
                     format!("IP mismatch: expected {expected}, got {provided}"),
                 ),
-                TransferError::InvalidPortRange(port) => (
+                TransferError::InvalidPortRange(_) => (
                     501,
-                    format!(
-                        "Port {port} out of range (must be >= {})",
-                        startup_config.min_client_port
-                    ),
+                    format!("Port must be >= {}", startup_config.min_client_port),
                 ),
+                TransferError::ForbiddenDataAddress(_) => {
+                    (501, "Forbidden data address".to_string())
+                }
                 _ => (425, "Active mode setup failed".to_string()),
             };
             CommandResult {
@@ -821,6 +1722,646 @@ fn handle_cmd_port(
     }
 }
 
+/// Handles the TYPE command
+fn handle_cmd_type(client: &mut Client, type_arg: &str) -> CommandResult {
+    match TransferType::from_type_arg(type_arg) {
+        Some(transfer_type) => {
+            client.set_transfer_type(transfer_type);
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!("200 Type set to {}\r\n", transfer_type.label())),
+            }
+        }
+        None => CommandResult {
+            status: CommandStatus::Failure(format!("Unsupported type: {type_arg}")),
+            message: Some("504 Command not implemented for that parameter\r\n".into()),
+        },
+    }
+}
+
+/// Handles the MODE command
+///
+/// Stream mode is always available; MODE Z only when `mode_z` is enabled.
+fn handle_cmd_mode(
+    client: &mut Client,
+    mode: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let transfer_mode = match mode.to_ascii_uppercase().as_str() {
+        "S" => TransferMode::Stream,
+        "Z" if startup_config.mode_z => TransferMode::Deflate,
+        _ => {
+            return CommandResult {
+                status: CommandStatus::Failure(format!("Unsupported mode {mode}")),
+                message: Some("504 Command not implemented for that parameter\r\n".into()),
+            };
+        }
+    };
+
+    client.set_transfer_mode(transfer_mode);
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some(format!("200 Mode set to {}\r\n", mode.to_ascii_uppercase())),
+    }
+}
+
+/// Handles the STRU command
+///
+/// Files are always sent as a plain byte sequence, so only STRU F is accepted;
+/// record and page structure are recognized but refused with 504.
+fn handle_cmd_stru(structure: &str) -> CommandResult {
+    if structure.eq_ignore_ascii_case("F") {
+        CommandResult {
+            status: CommandStatus::Success,
+            message: Some("200 Structure set to F\r\n".into()),
+        }
+    } else {
+        CommandResult {
+            status: CommandStatus::Failure(format!("Unsupported structure {structure}")),
+            message: Some("504 Command not implemented for that parameter\r\n".into()),
+        }
+    }
+}
+
+/// Handles the SITE command, dispatching on its subcommand
+async fn handle_cmd_site(
+    client: &mut Client,
+    args: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let subcommand = args.split_whitespace().next().unwrap_or_default();
+    match subcommand.to_ascii_uppercase().as_str() {
+        "DISKUSAGE" => handle_site_diskusage(client, startup_config).await,
+        "HISTORY" => handle_site_history(client, startup_config),
+        "UPLOADMTIME" => handle_site_uploadmtime(client, args, startup_config),
+        _ => CommandResult {
+            status: CommandStatus::Failure(format!("Unknown SITE command {subcommand}")),
+            message: Some("500 Unknown SITE command\r\n".into()),
+        },
+    }
+}
+
+/// Handles SITE DISKUSAGE, reporting the space used by the user's files
+///
+/// A user with a configured home is measured there, anyone else across the
+/// whole tree. The walk runs on a blocking thread, since a large tree can take
+/// a while. There are no per-user quotas, so the quota is always reported as
+/// unlimited.
+async fn handle_site_diskusage(
+    client: &mut Client,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let root = startup_config.storage_root();
+    let username = client.username().map(String::as_str);
+    let home = match username.and_then(|name| startup_config.home_directory(name)) {
+        Some(home) => {
+            let max_depth = startup_config.max_directory_depth_for(username);
+            match navigate::change_directory(&root, "/", home, max_depth) {
+                Ok(home) => home,
+                Err(error) => {
+                    return CommandResult {
+                        status: CommandStatus::Failure(error.to_string()),
+                        message: Some(format!("550 {home}: {error}\r\n")),
+                    };
+                }
+            }
+        }
+        None => "/".to_string(),
+    };
+
+    let usage = tokio::task::spawn_blocking(move || storage::disk_usage(&root, &home)).await;
+    match usage {
+        Err(error) => CommandResult {
+            status: CommandStatus::Failure(error.to_string()),
+            message: Some("451 Requested action aborted: local error in processing\r\n".into()),
+        },
+        Ok(Ok(used)) => CommandResult {
+            status: CommandStatus::Success,
+            message: Some(format!("200 Used: {used} bytes, Quota: unlimited\r\n")),
+        },
+        Ok(Err(error)) if error.is_backend_unavailable() => storage_unavailable(client, error),
+        Ok(Err(error)) => CommandResult {
+            status: CommandStatus::Failure(error.to_string()),
+            message: Some(format!("451 Local error in processing: {error}\r\n")),
+        },
+    }
+}
+
+/// Handles SITE HISTORY, listing the connection's recent commands for admins
+///
+/// Each line is a verb and the code it was answered with; arguments are never
+/// recorded, so passwords can't show up here.
+fn handle_site_history(client: &mut Client, startup_config: &StartupConfig) -> CommandResult {
+    if !client
+        .username()
+        .is_some_and(|name| startup_config.is_admin(name))
+    {
+        return CommandResult {
+            status: CommandStatus::Failure("SITE HISTORY requires an admin".into()),
+            message: Some("550 Permission denied\r\n".into()),
+        };
+    }
+
+    let mut message = String::from("200-Recent commands:\r\n");
+    for entry in client.history().entries() {
+        message.push_str(&format!(" {entry}\r\n"));
+    }
+    message.push_str("200 End\r\n");
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some(message),
+    }
+}
+
+/// Handles SITE UPLOADMTIME, declaring the modification time for an upload
+///
+/// The time is applied when the next STOR of that file commits it. Unlike
+/// MFMT, the file does not have to exist yet.
+fn handle_site_uploadmtime(
+    client: &mut Client,
+    args: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let mut parts = args.trim().splitn(3, char::is_whitespace).skip(1);
+    let timestamp = parts.next().unwrap_or("");
+    let filename = parts.next().unwrap_or("").trim();
+
+    let Some(modified) = storage::parse_ftp_timestamp(timestamp) else {
+        return CommandResult {
+            status: CommandStatus::Failure("Invalid timestamp".into()),
+            message: Some("501 Invalid timestamp, expected YYYYMMDDHHMMSS\r\n".into()),
+        };
+    };
+    if filename.is_empty() {
+        return CommandResult {
+            status: CommandStatus::Failure("Missing filename".into()),
+            message: Some("501 Syntax error: SITE UPLOADMTIME <timestamp> <filename>\r\n".into()),
+        };
+    }
+
+    match storage::validation::resolve_file_path(
+        client.current_virtual_path(),
+        filename,
+        max_directory_depth(client, startup_config),
+    ) {
+        Ok(virtual_path) => {
+            info!("Modification time for {virtual_path} will be applied when it is stored");
+            client.set_pending_mtime(virtual_path, modified);
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!(
+                    "200 Modify={}; {filename} will be applied when the file is stored\r\n",
+                    storage::format_ftp_timestamp(modified)
+                )),
+            }
+        }
+        Err(error) => CommandResult {
+            status: CommandStatus::Failure(error.to_string()),
+            message: Some(format!("553 {error}\r\n")),
+        },
+    }
+}
+
+/// Handles the REST command
+///
+/// Offsets count bytes of the file, which only match what the client has
+/// received in TYPE I, so a non-zero offset is refused in TYPE A.
+fn handle_cmd_rest(client: &mut Client, offset: &str) -> CommandResult {
+    match offset.parse::<u64>() {
+        Ok(offset) if offset > 0 && client.transfer_type() == TransferType::Ascii => {
+            ascii_restart_rejection()
+        }
+        Ok(offset) => {
+            client.set_restart_offset(Some(offset));
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!(
+                    "350 Restarting at {offset}. Send RETR to initiate transfer\r\n"
+                )),
+            }
+        }
+        Err(_) => CommandResult {
+            status: CommandStatus::Failure(format!("Invalid restart offset: {offset}")),
+            message: Some("501 Invalid restart offset\r\n".into()),
+        },
+    }
+}
+
+/// The reply refusing to restart a transfer in TYPE A
+fn ascii_restart_rejection() -> CommandResult {
+    CommandResult {
+        status: CommandStatus::Failure("REST not supported in TYPE A".into()),
+        message: Some("504 Restart not supported in ASCII mode, use TYPE I\r\n".into()),
+    }
+}
+
+/// Handles the ABOR command
+///
+/// A transfer in progress has already been cancelled when ABOR was read, and
+/// answered with 426, so by the time ABOR is processed there is no transfer
+/// left in flight. The cancellation is lifted for later transfers, and the
+/// data stream and any pending restart offset are discarded so the client
+/// can re-issue RETR with a fresh REST.
+fn handle_cmd_abor(client: &mut Client, channel_registry: &mut ChannelRegistry) -> CommandResult {
+    client.transfer_cancel().clear_abort();
+    client.set_restart_offset(None);
+    if let Some(client_addr) = client.client_addr() {
+        transfer::cleanup_data_stream_only(channel_registry, client_addr);
+    }
+
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some("226 ABOR command successful\r\n".into()),
+    }
+}
+
+/// Handles the MDTM command
+fn handle_cmd_mdtm(
+    client: &mut Client,
+    filename: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    match file_metadata(client, filename, startup_config) {
+        Ok(metadata) => {
+            let modified = if client.mdtm_millis() {
+                storage::format_ftp_timestamp_millis(metadata.modified)
+            } else {
+                storage::format_ftp_timestamp(metadata.modified)
+            };
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!("213 {modified}\r\n")),
+            }
+        }
+        Err(error) if error.is_backend_unavailable() => storage_unavailable(client, error),
+        Err(error) => metadata_error(error, "Could not get modification time"),
+    }
+}
+
+/// Handles the SIZE command
+fn handle_cmd_size(
+    client: &mut Client,
+    filename: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    match file_metadata(client, filename, startup_config) {
+        Ok(metadata) => CommandResult {
+            status: CommandStatus::Success,
+            message: Some(format!("213 {}\r\n", metadata.size)),
+        },
+        Err(error) if error.is_backend_unavailable() => storage_unavailable(client, error),
+        Err(error) => metadata_error(error, "Could not get file size"),
+    }
+}
+
+/// Handles the HASH command, replying with the file's digest and the byte range it covers
+fn handle_cmd_hash(
+    client: &mut Client,
+    filename: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let algorithm = client.hash_algorithm();
+    // Reads the whole file, so let the runtime move other tasks off this thread
+    let result = tokio::task::block_in_place(|| {
+        storage::file_hash(
+            &startup_config.storage_root(),
+            client.current_virtual_path(),
+            filename,
+            max_directory_depth(client, startup_config),
+            algorithm,
+        )
+    });
+    match result {
+        Ok(hash) => CommandResult {
+            status: CommandStatus::Success,
+            message: Some(format!(
+                "213 {} 0-{} {} {filename}\r\n",
+                algorithm.name(),
+                hash.size,
+                hash.digest
+            )),
+        },
+        Err(error) if error.is_backend_unavailable() => storage_unavailable(client, error),
+        Err(error) => metadata_error(error, "Could not compute hash"),
+    }
+}
+
+/// Looks up a file's size and modification time, reusing the session's last lookup
+fn file_metadata(
+    client: &mut Client,
+    filename: &str,
+    startup_config: &StartupConfig,
+) -> Result<storage::FileMetadata, crate::error::StorageError> {
+    let max_depth = max_directory_depth(client, startup_config);
+
+    if let Ok(virtual_path) =
+        storage::validation::resolve_file_path(client.current_virtual_path(), filename, max_depth)
+        && let Some(cached) = client.cached_metadata(&virtual_path)
+    {
+        return Ok(cached.clone());
+    }
+
+    let metadata = storage::file_metadata(
+        &startup_config.storage_root(),
+        client.current_virtual_path(),
+        filename,
+        max_depth,
+    )?;
+    client.cache_metadata(metadata.clone());
+    Ok(metadata)
+}
+
+/// Replies to a storage operation that failed because the backend looks unreachable
+///
+/// After `MAX_STORAGE_FAILURES` in a row the session is closed with 421 rather
+/// than letting the client keep retrying against storage that is gone.
+fn storage_unavailable(client: &mut Client, error: crate::error::StorageError) -> CommandResult {
+    let failures = client.record_storage_failure();
+    if failures >= MAX_STORAGE_FAILURES {
+        warn!("Storage backend unreachable after {failures} consecutive failures: {error}");
+        return CommandResult {
+            status: CommandStatus::CloseConnection,
+            message: Some("421 Service not available, storage backend unreachable\r\n".into()),
+        };
+    }
+
+    CommandResult {
+        status: CommandStatus::Failure(error.to_string()),
+        message: Some(format!("451 Local error in processing: {error}\r\n")),
+    }
+}
+
+/// Translates a failed SIZE/MDTM lookup into a 550 reply
+fn metadata_error(error: crate::error::StorageError, fallback: &str) -> CommandResult {
+    let message = match error {
+        crate::error::StorageError::FileNotFound(p) => format!("{p}: File not found"),
+        crate::error::StorageError::NotAFile(p) => format!("{p}: Not a regular file"),
+        crate::error::StorageError::InvalidPath(p) => format!("Invalid path: {p}"),
+        crate::error::StorageError::PathTooLong => "Path too long".to_string(),
+        crate::error::StorageError::IoError(e) => format!("I/O error: {e}"),
+        _ => fallback.to_string(),
+    };
+    CommandResult {
+        status: CommandStatus::Failure(message.clone()),
+        message: Some(format!("550 {message}\r\n")),
+    }
+}
+
+/// Handles the MFMT command
+fn handle_cmd_mfmt(
+    client: &mut Client,
+    args: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let mut parts = args.splitn(2, char::is_whitespace);
+    let timestamp = parts.next().unwrap_or("");
+    let filename = parts.next().unwrap_or("").trim();
+
+    let Some(modified) = storage::parse_ftp_timestamp(timestamp) else {
+        return CommandResult {
+            status: CommandStatus::Failure("Invalid timestamp".into()),
+            message: Some("501 Invalid timestamp, expected YYYYMMDDHHMMSS\r\n".into()),
+        };
+    };
+    if filename.is_empty() {
+        return CommandResult {
+            status: CommandStatus::Failure("Missing filename".into()),
+            message: Some("501 Syntax error: MFMT <timestamp> <filename>\r\n".into()),
+        };
+    }
+
+    match storage::set_modification_time(
+        &startup_config.storage_root(),
+        client.current_virtual_path(),
+        filename,
+        modified,
+        max_directory_depth(client, startup_config),
+    ) {
+        Ok(()) => CommandResult {
+            status: CommandStatus::Success,
+            message: Some(format!(
+                "213 Modify={}; {filename}\r\n",
+                storage::format_ftp_timestamp(modified)
+            )),
+        },
+        Err(error) if error.is_backend_unavailable() => storage_unavailable(client, error),
+        Err(error) => {
+            let message = match error {
+                crate::error::StorageError::FileNotFound(p) => format!("{p}: File not found"),
+                crate::error::StorageError::NotAFile(p) => format!("{p}: Not a regular file"),
+                crate::error::StorageError::PermissionDenied(p) => {
+                    format!("{p}: Permission denied")
+                }
+                crate::error::StorageError::InvalidPath(p) => format!("Invalid path: {p}"),
+                crate::error::StorageError::PathTooLong => "Path too long".to_string(),
+                crate::error::StorageError::IoError(e) => format!("I/O error: {e}"),
+                _ => "Could not set modification time".to_string(),
+            };
+            CommandResult {
+                status: CommandStatus::Failure(message.clone()),
+                message: Some(format!("550 {message}\r\n")),
+            }
+        }
+    }
+}
+
+/// Handles the SYST command
+fn handle_cmd_syst(startup_config: &StartupConfig) -> CommandResult {
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some(responses::system_type(startup_config)),
+    }
+}
+
+/// Handles the STAT command without arguments, reporting the session state
+fn handle_cmd_stat(
+    client: &Client,
+    channel_registry: &ChannelRegistry,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let mut message = responses::titled_reply_header(211, "Status", startup_config);
+    if let Some(addr) = client.client_addr() {
+        message.push_str(&format!(" Connected from {addr}\r\n"));
+    }
+    if let Some(username) = client.username() {
+        message.push_str(&format!(" Logged in as {username}\r\n"));
+    }
+    let channel = client
+        .client_addr()
+        .and_then(|addr| channel_registry.get(addr));
+    match channel.map(|entry| (entry.listener().is_some(), entry.data_socket())) {
+        Some((true, Some(socket))) => {
+            message.push_str(&format!(" Data connection: passive on {socket}\r\n"));
+        }
+        Some((false, Some(socket))) => {
+            message.push_str(&format!(" Data connection: active to {socket}\r\n"));
+        }
+        _ => message.push_str(" Data connection: none\r\n"),
+    }
+    message.push_str(&format!(
+        " TYPE: {}\r\n Current directory: {}\r\n211 End of status\r\n",
+        client.transfer_type().label(),
+        client.current_virtual_path()
+    ));
+
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some(message),
+    }
+}
+
+/// Handles STAT with a path, listing it on the control connection
+///
+/// The control connection isn't meant for bulk data, so the listing stops
+/// after `max_stat_entries` entries with a pointer to LIST and MLSD.
+fn handle_cmd_stat_path(
+    client: &mut Client,
+    path: &str,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    let mut listing = match storage::list_directory(
+        &startup_config.storage_root(),
+        client.current_virtual_path(),
+        Some(path),
+        None,
+        false,
+        Some(startup_config.max_stat_entries),
+        max_directory_depth(client, startup_config),
+    ) {
+        Ok(listing) => listing,
+        Err(error) if error.is_backend_unavailable() => return storage_unavailable(client, error),
+        Err(crate::error::StorageError::FileNotFound(p)) => {
+            return CommandResult {
+                status: CommandStatus::Failure(format!("{p}: No such file or directory")),
+                message: Some(format!("550 {p}: No such file or directory\r\n")),
+            };
+        }
+        Err(error) => return listing_failure(error),
+    };
+
+    let max = startup_config.max_stat_entries;
+    let truncated = listing.len() > max;
+    if truncated {
+        warn!("STAT listing of {path} truncated after {max} entries");
+        listing.truncate(max);
+    }
+
+    let now = SystemTime::now();
+    let mut message = format!("213-Status of {path}:\r\n");
+    for entry in listing {
+        message.push_str(&format!(
+            " {}\r\n",
+            entry.ls_line(now, startup_config.list_time_zone)
+        ));
+    }
+    if truncated {
+        message.push_str(&format!(
+            " Listing truncated after {max} entries; use LIST or MLSD for the full listing\r\n"
+        ));
+    }
+    message.push_str("213 End of status\r\n");
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some(message),
+    }
+}
+
+/// Handles the FEAT command
+fn handle_cmd_feat(client: &Client, startup_config: &StartupConfig) -> CommandResult {
+    let mut message = responses::titled_reply_header(211, "Features", startup_config);
+    let hash = format!(
+        "HASH {}",
+        HashAlgorithm::feature_list(client.hash_algorithm())
+    );
+    let mlst = format!("MLST {}", client.mlst_facts().feature_list());
+    let mut features = vec!["EPSV", &hash, "MDTM", "MDTM FRACTIONAL", "MFMT", &mlst];
+    if startup_config.mode_z {
+        features.push("MODE Z");
+    }
+    features.extend(["REST STREAM", "SIZE", "UTF8"]);
+    for feature in features {
+        // Each feature is named after the command that provides it, except
+        // UTF8, which is negotiated with OPTS
+        let verb = match feature {
+            "UTF8" => "OPTS",
+            feature => feature.split(' ').next().unwrap_or(feature),
+        };
+        if is_command_available(client, verb, startup_config) {
+            message.push_str(&format!(" {feature}\r\n"));
+        }
+    }
+    message.push_str("211 End\r\n");
+
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some(message),
+    }
+}
+
+/// Handles the HELP command, listing the commands this client may use
+///
+/// With an argument, only reports whether that command is available.
+fn handle_cmd_help(
+    client: &Client,
+    verb: Option<&str>,
+    startup_config: &StartupConfig,
+) -> CommandResult {
+    if let Some(verb) = verb {
+        return if command_verbs().any(|known| known == verb)
+            && is_command_available(client, verb, startup_config)
+        {
+            CommandResult {
+                status: CommandStatus::Success,
+                message: Some(format!("214 {verb} is available\r\n")),
+            }
+        } else {
+            CommandResult {
+                status: CommandStatus::Failure(format!("{verb} not available")),
+                message: Some(format!("502 {verb} is not available\r\n")),
+            }
+        };
+    }
+
+    let mut verbs: Vec<&str> = command_verbs()
+        .filter(|verb| is_command_available(client, verb, startup_config))
+        .collect();
+    verbs.sort_unstable();
+
+    let mut message = String::from("214-The following commands are recognized:\r\n");
+    for row in verbs.chunks(8) {
+        message.push_str(&format!(" {}\r\n", row.join(" ")));
+    }
+    message.push_str("214 Help OK\r\n");
+
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some(message),
+    }
+}
+
+/// Handles the NOOP command
+fn handle_cmd_noop() -> CommandResult {
+    CommandResult {
+        status: CommandStatus::Success,
+        message: Some("200 NOOP ok\r\n".into()),
+    }
+}
+
+/// Handles a blank line, which some clients send as a keepalive
+///
+/// It goes unanswered unless `answer_empty_lines` is set, in which case it is
+/// answered like NOOP.
+fn handle_empty_line(startup_config: &StartupConfig) -> CommandResult {
+    if startup_config.answer_empty_lines {
+        handle_cmd_noop()
+    } else {
+        CommandResult {
+            status: CommandStatus::Success,
+            message: None,
+        }
+    }
+}
+
 /// Handles the custom RAX command
 fn handle_cmd_rax() -> CommandResult {
     CommandResult {
@@ -829,6 +2370,14 @@ fn handle_cmd_rax() -> CommandResult {
     }
 }
 
+/// Handles recognized commands that this server does not implement
+fn handle_cmd_unsupported(verb: &str) -> CommandResult {
+    CommandResult {
+        status: CommandStatus::Failure(format!("{verb} not implemented")),
+        message: Some("502 Command not implemented\r\n".into()),
+    }
+}
+
 /// Handles unknown or unsupported commands
 fn handle_cmd_unknown() -> CommandResult {
     CommandResult {
@@ -836,3 +2385,11 @@ fn handle_cmd_unknown() -> CommandResult {
         message: Some("500 Syntax error, command unrecognized\r\n".into()),
     }
 }
+
+/// Handles a known command whose arguments could not be accepted
+fn handle_cmd_invalid(verb: &str) -> CommandResult {
+    CommandResult {
+        status: CommandStatus::Failure(format!("Invalid arguments for {verb}")),
+        message: Some("501 Syntax error in parameters or arguments\r\n".into()),
+    }
+}
@@ -3,11 +3,15 @@
 //! Handles FTP command parsing, validation, and response generation.
 
 pub mod commands;
+pub mod dispatch;
 pub mod handlers;
 pub mod parser;
 pub mod responses;
 pub mod translators;
 
-pub use commands::{Command, CommandResult, CommandStatus};
+pub use commands::{Command, CommandResult, CommandStatus, PendingTransfer, TransferFinish};
+pub use dispatch::{CommandSpec, command_spec, command_verb, command_verbs, write_verbs};
 pub use handlers::{handle_auth_command, handle_command};
-pub use parser::parse_command;
+pub use parser::{
+    decode_command_line, encode_for_client, parse_command, split_list_options, unquote_path,
+};
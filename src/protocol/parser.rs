@@ -5,3 +5,66 @@
 /// Parse a command string into a Command enum
 /// This is the main parsing function exported from commands.rs
 pub use crate::protocol::commands::parse_command;
+
+use std::borrow::Cow;
+
+/// Decodes a raw control-channel line into text.
+///
+/// With `latin1` every byte is taken as the ISO-8859-1 character of the same
+/// value, so any line decodes. Otherwise returns `None` when the bytes are not
+/// valid UTF-8 so the caller can reject the line instead of parsing a
+/// lossily-decoded command.
+pub fn decode_command_line(raw: &[u8], latin1: bool) -> Option<Cow<'_, str>> {
+    if latin1 {
+        Some(Cow::Owned(
+            raw.iter().map(|&byte| char::from(byte)).collect(),
+        ))
+    } else {
+        std::str::from_utf8(raw).ok().map(Cow::Borrowed)
+    }
+}
+
+/// Encodes reply or listing text for the client, the reverse of `decode_command_line`.
+///
+/// With `latin1`, characters outside ISO-8859-1 can't be sent and become `?`.
+pub fn encode_for_client(text: &str, latin1: bool) -> Cow<'_, [u8]> {
+    if latin1 {
+        Cow::Owned(
+            text.chars()
+                .map(|c| u8::try_from(c).unwrap_or(b'?'))
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(text.as_bytes())
+    }
+}
+
+/// Splits a LIST argument into its leading `-` option flags and the path after them.
+///
+/// Many clients send `LIST -la` or `LIST -a /dir` as if talking to `ls`; an
+/// empty path means the current directory.
+pub fn split_list_options(arg: &str) -> (Vec<&str>, &str) {
+    let mut rest = arg.trim();
+    let mut options = Vec::new();
+    while rest.starts_with('-') {
+        let (option, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        options.push(option);
+        rest = tail.trim_start();
+    }
+    (options, rest)
+}
+
+/// Strips the double quotes some clients put around a path argument.
+///
+/// The argument is otherwise taken verbatim, so unquoted names with spaces
+/// work too. Inside quotes a doubled `""` stands for one quote, as in the
+/// 257 reply format.
+pub fn unquote_path(arg: &str) -> String {
+    match arg
+        .strip_prefix('"')
+        .and_then(|inner| inner.strip_suffix('"'))
+    {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => arg.to_string(),
+    }
+}
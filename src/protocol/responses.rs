@@ -1,3 +1,72 @@
 //! FTP Response handling
 //!
 //! Defines FTP response codes and formatting.
+//!
+//! Every reply that names the server software goes through [`server_identity`],
+//! so `hide_server_identity` only needs to be honoured in one place.
+
+use crate::config::StartupConfig;
+
+/// Reply to any command that needs a login, sent before the client has one
+pub const NOT_LOGGED_IN: &str = "530 Please login with USER and PASS\r\n";
+
+/// Reply to a transfer command sent before PASV, EPSV or PORT
+pub const NO_DATA_CHANNEL: &str = "425 Data channel not initialized\r\n";
+
+/// Reply when the data connection can't be set up for the session
+pub const DATA_CONNECTION_FAILED: &str = "425 Failed to establish data connection\r\n";
+
+/// Reply to a transfer cancelled by ABOR or the loss of the control connection
+pub const TRANSFER_ABORTED: &str = "426 Connection closed; transfer aborted\r\n";
+
+/// Sent before disconnecting a client that got too many error replies in a row
+pub const TOO_MANY_ERRORS: &str = "421 Too many errors, closing connection\r\n";
+
+/// Sent before closing a connection that failed to log in `max_login_attempts` times
+pub const TOO_MANY_LOGIN_ATTEMPTS: &str = "421 Too many authentication attempts\r\n";
+
+/// Sent for a data transfer command that arrives while another is still queued or running
+pub const TRANSFER_IN_PROGRESS: &str = "450 Transfer already in progress\r\n";
+
+/// Software name and version advertised to clients
+pub const SERVER_SOFTWARE: &str = concat!("RAX FTP Server ", env!("CARGO_PKG_VERSION"));
+
+/// Returns the software string to advertise, or `None` when identity is hidden
+pub fn server_identity(startup_config: &StartupConfig) -> Option<&'static str> {
+    (!startup_config.hide_server_identity).then_some(SERVER_SOFTWARE)
+}
+
+/// Builds the 220 greeting sent when a client connects
+///
+/// A configured banner is sent first as the continuation lines of a
+/// multi-line 220 reply.
+pub fn greeting(startup_config: &StartupConfig) -> String {
+    let mut reply = String::new();
+    if let Some(banner) = &startup_config.banner {
+        for line in banner.lines() {
+            reply.push_str(&format!("220-{}\r\n", line.trim_end()));
+        }
+    }
+
+    match server_identity(startup_config) {
+        Some(software) => reply.push_str(&format!("220 Welcome to {software}\r\n")),
+        None => reply.push_str("220 Service ready\r\n"),
+    }
+    reply
+}
+
+/// Builds the 215 reply to SYST
+pub fn system_type(startup_config: &StartupConfig) -> String {
+    match server_identity(startup_config) {
+        Some(software) => format!("215 UNIX Type: L8 ({software})\r\n"),
+        None => "215 UNIX Type: L8\r\n".into(),
+    }
+}
+
+/// Builds the first line of a multi-line reply, naming the software if allowed
+pub fn titled_reply_header(code: u16, title: &str, startup_config: &StartupConfig) -> String {
+    match server_identity(startup_config) {
+        Some(software) => format!("{code}-{software} {title}:\r\n"),
+        None => format!("{code}-{title}:\r\n"),
+    }
+}
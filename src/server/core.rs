@@ -1,16 +1,25 @@
 use log::{error, info, warn};
+use socket2::SockRef;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
 use tokio::sync::Mutex;
 
-use crate::client::Client;
 use crate::client::handle_client;
-use crate::config::{ServerConfig, SharedRuntimeConfig, StartupConfig};
-use crate::protocol::handle_auth_command;
-use crate::protocol::parse_command;
+use crate::client::{Client, ErrorCounter};
+use crate::config::{
+    ConfigChanges, ServerConfig, SharedRuntimeConfig, StartupConfig, changed_fields,
+};
+use crate::error::ServerError;
+use crate::logging;
+use crate::protocol::{Command, CommandStatus, handle_auth_command, responses};
+use crate::protocol::{decode_command_line, encode_for_client, parse_command};
+use crate::server::Metrics;
+use crate::server::hooks::{AuthenticateHook, ConnectHook, Hooks, UploadCompleteHook};
 use crate::transfer::ChannelRegistry;
 
 pub struct Server {
@@ -19,50 +28,144 @@ pub struct Server {
     listener: TcpListener,
     startup_config: Arc<StartupConfig>,
     runtime_config: SharedRuntimeConfig,
+    metrics: Arc<Metrics>,
+    hooks: Hooks,
+}
+
+/// Server-wide state handed to each connection
+#[derive(Clone)]
+struct Shared {
+    client_registry: Arc<Mutex<HashMap<SocketAddr, Client>>>,
+    channel_registry: Arc<Mutex<ChannelRegistry>>,
+    startup_config: Arc<StartupConfig>,
+    runtime_config: SharedRuntimeConfig,
+    metrics: Arc<Metrics>,
+    hooks: Hooks,
 }
 
 impl Server {
-    pub async fn new() -> Self {
+    pub async fn new() -> Result<Self, ServerError> {
         // Load configuration from config.toml and environment
-        let config = ServerConfig::load().expect("Failed to load server configuration");
-        let (startup_config, runtime_config) = config.split();
+        let config = ServerConfig::load()?;
+        Self::with_config(config).await
+    }
+
+    /// Creates a server from an already-built configuration.
+    ///
+    /// Lets embedders and tests supply settings without a config.toml on disk.
+    pub async fn with_config(config: ServerConfig) -> Result<Self, ServerError> {
+        config.validate()?;
+        let (mut startup_config, runtime_config) = config.split();
+
+        if let Err(e) = startup_config.load_banner() {
+            warn!("Failed to read banner file, using the default greeting: {e}");
+        }
+
+        if let Err(e) = startup_config.load_authenticator() {
+            error!("Failed to load credentials: {e}");
+            return Err(ServerError::Credentials(e));
+        }
+
+        // Resolved before anything uses it, since sessions never change the
+        // process working directory a relative root would be read against
+        let configured_root = startup_config.server_root_str();
+        if let Err(e) = startup_config.resolve_server_root() {
+            error!("Failed to resolve server root {configured_root}: {e}");
+            return Err(ServerError::Root(configured_root, e));
+        }
+        info!(
+            "Server root directory: {}",
+            startup_config.server_root_str()
+        );
 
         let startup_config = Arc::new(startup_config);
 
-        let listener = match TcpListener::bind(&startup_config.control_socket()).await {
+        let listener = match bind_control_listener(&startup_config).await {
             Ok(listener) => {
                 info!("Server bound to {}", startup_config.control_socket());
                 listener
             }
             Err(e) => {
                 error!("Failed to bind to {}: {e}", startup_config.control_socket());
-                panic!(
-                    "Server startup failed on socket {}: {e}",
-                    startup_config.control_socket()
-                );
+                return Err(ServerError::Bind(startup_config.control_socket(), e));
             }
         };
 
-        // Ensure server root directory exists
-        if let Err(e) = std::fs::create_dir_all(startup_config.server_root_path()) {
-            warn!("Failed to create server root directory: {e}");
-        } else {
-            info!(
-                "Server root directory: {}",
-                startup_config.server_root_str()
-            );
+        for (virtual_path, real_path) in &startup_config.mounts {
+            if std::path::Path::new(real_path).is_dir() {
+                info!("Mounted {real_path} at {virtual_path}");
+            } else {
+                warn!("Mount point {virtual_path} refers to missing directory {real_path}");
+            }
+        }
+
+        if let Some(temp_dir) = startup_config.temp_upload_dir_path() {
+            if let Err(e) = std::fs::create_dir_all(&temp_dir) {
+                warn!("Failed to create temporary upload directory: {e}");
+            } else {
+                info!("Temporary upload directory: {}", temp_dir.display());
+            }
         }
 
-        Self {
+        Ok(Self {
             client_registry: Arc::new(Mutex::new(HashMap::new())),
             channel_registry: Arc::new(Mutex::new(ChannelRegistry::default())),
             listener,
             startup_config,
             runtime_config,
-        }
+            metrics: Arc::new(Metrics::default()),
+            hooks: Hooks::default(),
+        })
+    }
+
+    /// Registers a callback run when a client connects, before the greeting.
+    ///
+    /// Returning `false` refuses the connection with 421.
+    pub fn on_connect(&mut self, hook: impl Fn(SocketAddr) -> bool + Send + Sync + 'static) {
+        let hook: Arc<ConnectHook> = Arc::new(hook);
+        self.hooks.set_on_connect(hook);
+    }
+
+    /// Registers a callback run with the username once a client's login succeeds.
+    ///
+    /// Returning `false` refuses the login with 530; the client may try again.
+    pub fn on_authenticate(
+        &mut self,
+        hook: impl Fn(SocketAddr, &str) -> bool + Send + Sync + 'static,
+    ) {
+        let hook: Arc<AuthenticateHook> = Arc::new(hook);
+        self.hooks.set_on_authenticate(hook);
+    }
+
+    /// Registers a callback run with the stored path and username after each successful STOR.
+    ///
+    /// The callback runs on a blocking thread and the client gets its reply
+    /// without waiting for it, so it may take as long as post-processing needs.
+    pub fn on_upload_complete(&mut self, hook: impl Fn(&Path, &str) + Send + Sync + 'static) {
+        let hook: Arc<UploadCompleteHook> = Arc::new(hook);
+        self.hooks.set_on_upload_complete(hook);
+    }
+
+    /// Returns the metrics collected across all client sessions.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Applies `config` the way SIGHUP applies a reloaded config.toml.
+    ///
+    /// Runtime settings take effect at once; changed startup settings are only
+    /// reported, since they need a restart.
+    pub async fn reload(&self, config: ServerConfig) -> Result<ConfigChanges, ServerError> {
+        config.validate()?;
+        Ok(apply_config(config, &self.startup_config, &self.runtime_config).await)
     }
 
-    pub async fn start(&self) {
+    /// Returns the address the control listener is bound to.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub async fn start(&self) -> Result<(), ServerError> {
         let runtime_config = self.runtime_config.read().await;
         info!(
             "Starting Rax FTP server on {} (max {} clients)",
@@ -71,27 +174,33 @@ impl Server {
         );
         drop(runtime_config);
 
+        #[cfg(unix)]
+        spawn_config_reload(
+            Arc::clone(&self.startup_config),
+            Arc::clone(&self.runtime_config),
+        );
+
         loop {
             match self.listener.accept().await {
                 Ok((stream, addr)) => {
-                    info!("Client {addr} connected to FTP server");
-                    let client_registry = Arc::clone(&self.client_registry);
-                    let channel_registry = Arc::clone(&self.channel_registry);
-                    let startup_config = Arc::clone(&self.startup_config);
-                    let runtime_config = Arc::clone(&self.runtime_config);
+                    info!(event = "connect", client_addr:% = addr; "Client {addr} connected to FTP server");
+                    if let Some(keepalive) = self.startup_config.tcp_keepalive()
+                        && let Err(e) = SockRef::from(&stream).set_tcp_keepalive(&keepalive)
+                    {
+                        warn!("Failed to enable TCP keepalive for client {addr}: {e}");
+                    }
+                    let shared = Shared {
+                        client_registry: Arc::clone(&self.client_registry),
+                        channel_registry: Arc::clone(&self.channel_registry),
+                        startup_config: Arc::clone(&self.startup_config),
+                        runtime_config: Arc::clone(&self.runtime_config),
+                        metrics: Arc::clone(&self.metrics),
+                        hooks: self.hooks.clone(),
+                    };
 
                     // Spawn a task for each client so accept loop doesn't block
                     tokio::spawn(async move {
-                        if let Err(e) = handle_new_client(
-                            stream,
-                            addr,
-                            client_registry,
-                            channel_registry,
-                            startup_config,
-                            runtime_config,
-                        )
-                        .await
-                        {
+                        if let Err(e) = handle_new_client(stream, addr, shared).await {
                             warn!("Failed to handle client {addr}: {e}");
                         }
                     });
@@ -104,32 +213,147 @@ impl Server {
     }
 }
 
+/// Binds the control listener with the socket options from the startup config.
+async fn bind_control_listener(startup_config: &StartupConfig) -> std::io::Result<TcpListener> {
+    let addr = tokio::net::lookup_host(startup_config.control_socket())
+        .await?
+        .next()
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::AddrNotAvailable,
+                "bind address did not resolve",
+            )
+        })?;
+
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(startup_config.reuse_address)?;
+    socket.bind(addr)?;
+    socket.listen(startup_config.listen_backlog)
+}
+
+/// Reloads the runtime configuration from config.toml whenever SIGHUP is received.
+#[cfg(unix)]
+fn spawn_config_reload(startup_config: Arc<StartupConfig>, runtime_config: SharedRuntimeConfig) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                warn!("Failed to install SIGHUP handler, config reload disabled: {e}");
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading configuration");
+            reload_config(&startup_config, &runtime_config).await;
+        }
+    });
+}
+
+/// Re-reads and validates config.toml, applying runtime values in place.
+#[cfg(unix)]
+async fn reload_config(startup_config: &StartupConfig, runtime_config: &SharedRuntimeConfig) {
+    match ServerConfig::load() {
+        Ok(config) => {
+            apply_config(config, startup_config, runtime_config).await;
+        }
+        Err(e) => error!("Config reload failed, keeping current configuration: {e}"),
+    }
+}
+
+/// Applies a reloaded configuration and logs each setting that changed.
+///
+/// Startup values cannot change without a restart, so differences are only
+/// reported. Credentials are re-read either way.
+async fn apply_config(
+    mut config: ServerConfig,
+    startup_config: &StartupConfig,
+    runtime_config: &SharedRuntimeConfig,
+) -> ConfigChanges {
+    // Resolve the root the way startup did, so an unchanged setting compares equal
+    let configured_root = config.startup.server_root_str();
+    if let Err(e) = config.startup.resolve_server_root() {
+        warn!("Reloaded server root {configured_root} can't be resolved: {e}");
+    }
+
+    let restart_required = changed_fields(startup_config, &config.startup);
+    for field in &restart_required {
+        warn!("{field} changed; requires restart, not applied");
+    }
+
+    // Accounts may have been added since startup; the backends stay as they were
+    match startup_config.load_authenticator() {
+        Ok(()) => info!("Credentials reloaded"),
+        Err(e) => error!("Failed to reload credentials, keeping the current accounts: {e}"),
+    }
+
+    let mut runtime = runtime_config.write().await;
+    let applied = changed_fields(&*runtime, &config.runtime);
+    if applied.is_empty() {
+        info!("Runtime configuration unchanged");
+    }
+    for field in &applied {
+        info!("{field} updated");
+    }
+    *runtime = config.runtime;
+
+    ConfigChanges {
+        restart_required,
+        applied,
+    }
+}
+
 /// Handles a new client: greets, authenticates, registers, and spawns session handler.
+///
+/// The embedder's connect hook may refuse the client before the greeting, and
+/// its authenticate hook may refuse a login that otherwise succeeded.
 async fn handle_new_client(
     stream: TcpStream,
     client_addr: SocketAddr,
-    client_registry: Arc<Mutex<HashMap<SocketAddr, Client>>>,
-    channel_registry: Arc<Mutex<ChannelRegistry>>,
-    startup_config: Arc<StartupConfig>,
-    runtime_config: SharedRuntimeConfig,
+    shared: Shared,
 ) -> Result<(), std::io::Error> {
+    let Shared {
+        client_registry,
+        channel_registry,
+        startup_config,
+        runtime_config,
+        metrics,
+        hooks,
+    } = shared;
+
     let mut reader = BufReader::new(stream);
-    let mut line = String::new();
+
+    if !hooks.allow_connect(client_addr) {
+        info!("Connection from {client_addr} refused by the connect hook");
+        reader
+            .get_mut()
+            .write_all(b"421 Connection refused\r\n")
+            .await?;
+        return Ok(());
+    }
+    let mut line = Vec::new();
 
     // Send greeting
     reader
         .get_mut()
-        .write_all(b"220 Welcome to RAX FTP Server\r\n")
+        .write_all(responses::greeting(&startup_config).as_bytes())
         .await?;
 
     // FLUSH THE GREETING MESSAGE IMMEDIATELY
     reader.get_mut().flush().await?;
 
     let mut client = Client::default();
+    let mut errors = ErrorCounter::new(startup_config.max_consecutive_errors);
 
     loop {
         line.clear();
-        let n = reader.read_line(&mut line).await?;
+        let n = reader.read_until(b'\n', &mut line).await?;
         if n == 0 {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::ConnectionAborted,
@@ -137,11 +361,77 @@ async fn handle_new_client(
             ));
         }
 
-        let command = parse_command(&line);
-        let result = handle_auth_command(&mut client, &command, &startup_config);
+        let Some(decoded) = decode_command_line(&line, client.uses_latin1(&startup_config)) else {
+            warn!("Invalid command encoding from client {client_addr} during authentication");
+            reader
+                .get_mut()
+                .write_all(b"500 Invalid command encoding\r\n")
+                .await?;
+            if !errors.record("500") {
+                return refuse_after_errors(reader.get_mut(), client_addr).await;
+            }
+            continue;
+        };
+
+        let command = parse_command(&decoded);
+        let started = Instant::now();
+        let mut result = {
+            let runtime = runtime_config.read().await;
+            handle_auth_command(&mut client, &command, &startup_config, &runtime)
+        };
+        if client.is_logged_in()
+            && let Some(username) = client.username().cloned()
+            && !hooks.allow_login(client_addr, &username)
+        {
+            info!("Login of {username} from {client_addr} refused by the authenticate hook");
+            client.reset_session();
+            result.message = Some("530 Login refused\r\n".into());
+        }
+        metrics.record_command(command.name(), started.elapsed());
+        client.record_command(command.name(), result.message.as_deref());
+        logging::log_command(
+            client_addr,
+            client.username().map(String::as_str),
+            command.name(),
+            result.message.as_deref(),
+            started.elapsed(),
+        );
 
         if let Some(msg) = result.message {
-            reader.get_mut().write_all(msg.as_bytes()).await?;
+            let latin1 = client.uses_latin1(&startup_config);
+            reader
+                .get_mut()
+                .write_all(&encode_for_client(&msg, latin1))
+                .await?;
+            // Failed logins have their own limit, which every refused USER
+            // and PASS counts towards, so probing usernames costs attempts too.
+            // Without one they count as ordinary errors
+            if matches!(command, Command::USER(_) | Command::PASS(_))
+                && startup_config.max_login_attempts > 0
+            {
+                if msg.starts_with(['4', '5']) {
+                    let failed_logins = client.record_failed_login();
+                    if startup_config.max_login_attempts > 0
+                        && failed_logins >= startup_config.max_login_attempts
+                    {
+                        warn!(
+                            "Client {client_addr} failed to log in {failed_logins} times, disconnecting"
+                        );
+                        reader
+                            .get_mut()
+                            .write_all(responses::TOO_MANY_LOGIN_ATTEMPTS.as_bytes())
+                            .await?;
+                        return Ok(());
+                    }
+                }
+            } else if !matches!(command, Command::EMPTY) && !errors.record(&msg) {
+                return refuse_after_errors(reader.get_mut(), client_addr).await;
+            }
+        }
+
+        if matches!(result.status, CommandStatus::CloseConnection) {
+            info!("Closing connection from {client_addr} during authentication");
+            return Ok(());
         }
 
         if client.is_logged_in() {
@@ -157,6 +447,7 @@ async fn handle_new_client(
             }
 
             client.set_client_addr(Some(client_addr));
+            client.set_hooks(hooks.clone());
             clients.insert(client_addr, client);
 
             info!(
@@ -166,7 +457,9 @@ async fn handle_new_client(
                 runtime.max_clients
             );
 
-            let cmd_stream = reader.into_inner();
+            // Anything the client sent after its login is still buffered in
+            // `reader`, so the session handler takes the reader, not the stream
+            let cmd_stream = reader;
 
             drop(clients);
             drop(runtime);
@@ -179,6 +472,7 @@ async fn handle_new_client(
                 channel_registry,
                 startup_config,
                 runtime_config,
+                metrics,
             )
             .await;
 
@@ -186,3 +480,14 @@ async fn handle_new_client(
         }
     }
 }
+
+/// Disconnects a client that got too many error replies in a row before logging in.
+async fn refuse_after_errors(
+    stream: &mut TcpStream,
+    client_addr: SocketAddr,
+) -> Result<(), std::io::Error> {
+    warn!("Client {client_addr} got too many error replies in a row, disconnecting");
+    stream
+        .write_all(responses::TOO_MANY_ERRORS.as_bytes())
+        .await
+}
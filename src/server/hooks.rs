@@ -0,0 +1,75 @@
+//! Callbacks for embedders
+//!
+//! A program embedding the server can register hooks that run when a client
+//! connects and when it logs in, for custom metrics, external allowlists or
+//! per-client decisions. Returning `false` refuses the connection or login.
+//! Hooks run on the client's task, so they should return quickly.
+//!
+//! The upload hook is the exception: it runs on a blocking thread
+//! after each successful STOR, so scanning or indexing the file can take as
+//! long as it needs, and a panic in it is logged rather than ending the session.
+
+use log::warn;
+use std::net::SocketAddr;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Called with the peer address when a client connects, before the greeting
+pub type ConnectHook = dyn Fn(SocketAddr) -> bool + Send + Sync;
+
+/// Called with the peer address and username once a client's login succeeds
+pub type AuthenticateHook = dyn Fn(SocketAddr, &str) -> bool + Send + Sync;
+
+/// Called with the stored file's path and the username after an upload completes
+pub type UploadCompleteHook = dyn Fn(&Path, &str) + Send + Sync;
+
+/// The hooks registered on a server
+#[derive(Clone, Default)]
+pub struct Hooks {
+    on_connect: Option<Arc<ConnectHook>>,
+    on_authenticate: Option<Arc<AuthenticateHook>>,
+    on_upload_complete: Option<Arc<UploadCompleteHook>>,
+}
+
+impl Hooks {
+    pub(crate) fn set_on_connect(&mut self, hook: Arc<ConnectHook>) {
+        self.on_connect = Some(hook);
+    }
+
+    pub(crate) fn set_on_authenticate(&mut self, hook: Arc<AuthenticateHook>) {
+        self.on_authenticate = Some(hook);
+    }
+
+    pub(crate) fn set_on_upload_complete(&mut self, hook: Arc<UploadCompleteHook>) {
+        self.on_upload_complete = Some(hook);
+    }
+
+    /// Whether a new connection from `client_addr` may proceed
+    pub(crate) fn allow_connect(&self, client_addr: SocketAddr) -> bool {
+        self.on_connect
+            .as_ref()
+            .is_none_or(|hook| hook(client_addr))
+    }
+
+    /// Whether `username`, just logged in from `client_addr`, may start a session
+    pub(crate) fn allow_login(&self, client_addr: SocketAddr, username: &str) -> bool {
+        self.on_authenticate
+            .as_ref()
+            .is_none_or(|hook| hook(client_addr, username))
+    }
+
+    /// Runs the upload hook for `path`, stored by `username`, without waiting for it
+    pub(crate) fn upload_complete(&self, path: &Path, username: &str) {
+        let Some(hook) = self.on_upload_complete.clone() else {
+            return;
+        };
+        let path = path.to_path_buf();
+        let username = username.to_string();
+        tokio::task::spawn_blocking(move || {
+            if catch_unwind(AssertUnwindSafe(|| hook(&path, &username))).is_err() {
+                warn!("Upload hook panicked for {}", path.display());
+            }
+        });
+    }
+}
@@ -0,0 +1,124 @@
+//! Server metrics
+//!
+//! Collects per-command response latencies so operators can spot slow
+//! commands, such as LIST on a network filesystem.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the latency buckets, in milliseconds.
+///
+/// Latencies above the last bound are counted in a final overflow bucket.
+pub const LATENCY_BUCKET_BOUNDS_MS: [u64; 10] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 5000];
+
+/// Bucketed latency counts for one command.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKET_BOUNDS_MS.len() + 1],
+    total: Duration,
+}
+
+impl LatencyHistogram {
+    /// Records one observed latency.
+    pub fn record(&mut self, latency: Duration) {
+        let millis = latency.as_millis();
+        let bucket = LATENCY_BUCKET_BOUNDS_MS
+            .iter()
+            .position(|&bound| millis <= u128::from(bound))
+            .unwrap_or(LATENCY_BUCKET_BOUNDS_MS.len());
+        self.counts[bucket] += 1;
+        self.total += latency;
+    }
+
+    /// Returns the number of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the mean latency, or `None` if nothing was recorded.
+    pub fn mean(&self) -> Option<Duration> {
+        let count = self.count();
+        (count > 0).then(|| self.total / count as u32)
+    }
+
+    /// Returns `(upper bound in ms, count)` per bucket.
+    ///
+    /// The overflow bucket has no upper bound.
+    pub fn buckets(&self) -> Vec<(Option<u64>, u64)> {
+        LATENCY_BUCKET_BOUNDS_MS
+            .iter()
+            .map(|&bound| Some(bound))
+            .chain(std::iter::once(None))
+            .zip(self.counts)
+            .collect()
+    }
+
+    /// Estimates a percentile (0.0 to 1.0) as the upper bound of the bucket containing it.
+    ///
+    /// Returns `None` if nothing was recorded; `Some(None)` means the
+    /// percentile falls in the overflow bucket.
+    pub fn percentile(&self, percentile: f64) -> Option<Option<u64>> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((count as f64 * percentile.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.buckets()
+            .into_iter()
+            .find_map(|(bound, bucket_count)| {
+                seen += bucket_count;
+                (seen >= rank).then_some(bound)
+            })
+    }
+}
+
+impl fmt::Display for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_bound = |p| match self.percentile(p) {
+            Some(Some(bound)) => format!("<={bound}ms"),
+            Some(None) => format!(
+                ">{}ms",
+                LATENCY_BUCKET_BOUNDS_MS[LATENCY_BUCKET_BOUNDS_MS.len() - 1]
+            ),
+            None => "-".to_string(),
+        };
+        write!(
+            f,
+            "count {}, mean {:?}, p50 {}, p95 {}, p99 {}",
+            self.count(),
+            self.mean().unwrap_or_default(),
+            format_bound(0.50),
+            format_bound(0.95),
+            format_bound(0.99)
+        )
+    }
+}
+
+/// Metrics shared by every client session of a server.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    command_latencies: Mutex<BTreeMap<&'static str, LatencyHistogram>>,
+}
+
+impl Metrics {
+    /// Records how long a command took to handle.
+    pub fn record_command(&self, command: &'static str, latency: Duration) {
+        let mut latencies = self
+            .command_latencies
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        latencies.entry(command).or_default().record(latency);
+    }
+
+    /// Returns a snapshot of the latency histogram of every command seen so far.
+    pub fn command_latencies(&self) -> BTreeMap<&'static str, LatencyHistogram> {
+        self.command_latencies
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
@@ -4,5 +4,9 @@
 //! and core infrastructure for the FTP server.
 
 pub mod core;
+pub mod hooks;
+pub mod metrics;
 
 pub use core::Server;
+pub use hooks::{AuthenticateHook, ConnectHook, UploadCompleteHook};
+pub use metrics::{LatencyHistogram, Metrics};
@@ -0,0 +1,86 @@
+//! Machine-readable listing facts (RFC 3659)
+//!
+//! MLSD and MLST describe each entry as `fact=value;` pairs followed by its
+//! name. The server supports the `type`, `size` and `modify` facts; clients
+//! pick which of them are sent with `OPTS MLST`.
+
+use std::fs::Metadata;
+
+use crate::storage::timestamps::format_ftp_timestamp;
+
+/// Facts this server can report, in the order they are listed
+const SUPPORTED_FACTS: [&str; 3] = ["type", "size", "modify"];
+
+/// The set of facts a session has selected, all of them by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FactSet {
+    selected: [bool; SUPPORTED_FACTS.len()],
+}
+
+impl Default for FactSet {
+    fn default() -> Self {
+        Self {
+            selected: [true; SUPPORTED_FACTS.len()],
+        }
+    }
+}
+
+impl FactSet {
+    /// Parses an `OPTS MLST` selection such as `type;size;`
+    ///
+    /// Fact names are case-insensitive and unsupported ones are ignored, as
+    /// RFC 3659 requires; an empty selection turns every fact off.
+    pub fn parse(selection: &str) -> Self {
+        let mut selected = [false; SUPPORTED_FACTS.len()];
+        for name in selection.split(';') {
+            if let Some(index) = SUPPORTED_FACTS
+                .iter()
+                .position(|fact| fact.eq_ignore_ascii_case(name.trim()))
+            {
+                selected[index] = true;
+            }
+        }
+        Self { selected }
+    }
+
+    /// The selected facts as `type;size;`, used to acknowledge `OPTS MLST`
+    pub fn selected(&self) -> String {
+        self.facts()
+            .filter(|(_, selected)| *selected)
+            .map(|(fact, _)| format!("{fact};"))
+            .collect()
+    }
+
+    /// Every supported fact with the selected ones starred, as FEAT lists them
+    pub fn feature_list(&self) -> String {
+        self.facts()
+            .map(|(fact, selected)| format!("{fact}{};", if selected { "*" } else { "" }))
+            .collect()
+    }
+
+    /// Formats the selected facts of an entry followed by its name
+    pub fn describe(&self, name: &str, metadata: &Metadata) -> String {
+        let mut line = String::new();
+        for (fact, selected) in self.facts() {
+            if !selected {
+                continue;
+            }
+            match fact {
+                "type" if metadata.is_dir() => line.push_str("type=dir;"),
+                "type" => line.push_str("type=file;"),
+                "size" if metadata.is_file() => line.push_str(&format!("size={};", metadata.len())),
+                "modify" => {
+                    if let Ok(modified) = metadata.modified() {
+                        line.push_str(&format!("modify={};", format_ftp_timestamp(modified)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        format!("{line} {name}")
+    }
+
+    fn facts(&self) -> impl Iterator<Item = (&'static str, bool)> + '_ {
+        SUPPORTED_FACTS.into_iter().zip(self.selected)
+    }
+}
@@ -0,0 +1,97 @@
+//! File hashes for the HASH command (draft-bryan-ftpext-hash)
+//!
+//! Clients verify a file without downloading it by asking for its digest.
+//! The algorithm is chosen per session with `OPTS HASH`.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512};
+
+/// Size of the chunks a file is read in while hashing
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A hash algorithm a client can select with `OPTS HASH`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+    Md5,
+    Crc32,
+}
+
+impl HashAlgorithm {
+    /// Every supported algorithm, in the order FEAT lists them
+    pub const ALL: [HashAlgorithm; 4] = [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Md5,
+        HashAlgorithm::Crc32,
+    ];
+
+    /// The algorithm's name as used on the wire, e.g. `SHA-256`
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "SHA-256",
+            HashAlgorithm::Sha512 => "SHA-512",
+            HashAlgorithm::Md5 => "MD5",
+            HashAlgorithm::Crc32 => "CRC32",
+        }
+    }
+
+    /// Looks up an algorithm by name, ignoring case
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Every supported algorithm with `selected` starred, as FEAT lists them
+    pub fn feature_list(selected: Self) -> String {
+        Self::ALL
+            .into_iter()
+            .map(|algorithm| {
+                let star = if algorithm == selected { "*" } else { "" };
+                format!("{}{star}", algorithm.name())
+            })
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+}
+
+/// Hashes the file at `path`, returning the lowercase hex digest
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let digest = match algorithm {
+        HashAlgorithm::Sha256 => digest::<Sha256>(&mut file)?,
+        HashAlgorithm::Sha512 => digest::<Sha512>(&mut file)?,
+        HashAlgorithm::Md5 => digest::<Md5>(&mut file)?,
+        HashAlgorithm::Crc32 => {
+            let mut hasher = crc32fast::Hasher::new();
+            read_chunks(&mut file, |chunk| hasher.update(chunk))?;
+            hasher.finalize().to_be_bytes().to_vec()
+        }
+    };
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Runs `reader` through the `D` hash function
+fn digest<D: Digest>(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut hasher = D::new();
+    read_chunks(reader, |chunk| hasher.update(chunk))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Feeds `reader` to `consume` a chunk at a time until it is exhausted
+fn read_chunks(reader: &mut impl Read, mut consume: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => return Ok(()),
+            n => consume(&buffer[..n]),
+        }
+    }
+}
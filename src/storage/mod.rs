@@ -2,9 +2,23 @@
 //!
 //! Handles file system operations and storage management.
 
+pub mod facts;
 pub mod filesystem;
+pub mod hash;
 mod operations;
 pub mod permissions;
+pub mod retry;
+pub mod timestamps;
 pub mod validation;
+pub mod wildcard;
 
-pub use operations::{delete_file, list_directory, prepare_file_retrieval, prepare_file_storage};
+pub use operations::{
+    CreatedDirectory, FileHash, FileMetadata, ListEntry, create_directory, delete_file,
+    describe_entry, disk_usage, ensure_home_directory, file_hash, file_metadata, list_directory,
+    list_facts, list_names, prepare_file_retrieval, prepare_file_storage, remove_directory,
+    set_modification_time,
+};
+pub use timestamps::{
+    ListTimeZone, format_ftp_timestamp, format_ftp_timestamp_millis, format_ls_timestamp,
+    format_ls_timestamp_in, parse_ftp_timestamp,
+};
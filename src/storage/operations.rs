@@ -1,132 +1,428 @@
 //! Storage operations
 //!
-//! Handles file system operations for FTP commands including list, retrieve, store, and delete.
+//! Handles file system operations for FTP commands including list, retrieve, store, delete,
+//! and directory creation and removal.
 
-use log::{error, info};
+use log::{error, info, warn};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::thread;
-use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::config::StartupConfig;
 use crate::error::StorageError;
-use crate::storage::validation::{resolve_and_validate_file_path, virtual_to_real_path};
+use crate::storage::facts::FactSet;
+use crate::storage::hash::{self, HashAlgorithm};
+use crate::storage::permissions::{DIRECTORY_MODE, apply_umask, permission_mode};
+use crate::storage::retry::{RETRY_DELAY, TRANSIENT_ERRORS, with_retries};
+use crate::storage::timestamps::{ListTimeZone, format_ls_timestamp_in};
+use crate::storage::validation::{StorageRoot, resolve_cwd_path};
+use crate::storage::wildcard;
+
+/// One entry of a LIST or STAT listing
+#[derive(Debug)]
+pub struct ListEntry {
+    /// The name as listed, `.` and `..` included
+    pub name: String,
+    /// Its metadata, not following symlinks, if it could be read
+    pub metadata: Option<fs::Metadata>,
+}
+
+impl ListEntry {
+    fn new(name: &str, metadata: Option<fs::Metadata>) -> Self {
+        Self {
+            name: name.to_string(),
+            metadata,
+        }
+    }
+
+    /// Formats the entry as an `ls -l` line, such as
+    /// `-rw-r--r--   1 ftp      ftp          1234 Jun  5 08:30 notes.txt`
+    ///
+    /// Files are owned by `ftp` as far as clients are concerned, since the
+    /// server's accounts mean nothing to them. An entry whose metadata couldn't
+    /// be read is listed with no permissions, size 0 and the epoch.
+    pub fn ls_line(&self, now: SystemTime, zone: ListTimeZone) -> String {
+        let (mode, links, size, modified) = match &self.metadata {
+            Some(metadata) => (
+                mode_string(metadata),
+                link_count(metadata),
+                metadata.len(),
+                metadata.modified().unwrap_or(UNIX_EPOCH),
+            ),
+            None => ("----------".to_string(), 1, 0, UNIX_EPOCH),
+        };
+        format!(
+            "{mode} {links:>3} ftp      ftp      {size:>12} {} {}",
+            format_ls_timestamp_in(modified, now, zone),
+            self.name
+        )
+    }
+}
 
-/// Lists the contents of a directory
+/// The type and permission bits of an entry as `ls -l` shows them, e.g. `drwxr-xr-x`
+fn mode_string(metadata: &fs::Metadata) -> String {
+    let kind = if metadata.is_symlink() {
+        'l'
+    } else if metadata.is_dir() {
+        'd'
+    } else {
+        '-'
+    };
+    let mode = permission_bits(metadata);
+    let mut bits = String::with_capacity(10);
+    bits.push(kind);
+    for shift in [6, 3, 0] {
+        let triplet = mode >> shift;
+        bits.push(if triplet & 0o4 != 0 { 'r' } else { '-' });
+        bits.push(if triplet & 0o2 != 0 { 'w' } else { '-' });
+        bits.push(if triplet & 0o1 != 0 { 'x' } else { '-' });
+    }
+    bits
+}
+
+#[cfg(unix)]
+fn permission_bits(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode()
+}
+
+/// Without Unix modes, approximate them from the read-only flag
+#[cfg(not(unix))]
+fn permission_bits(metadata: &fs::Metadata) -> u32 {
+    let mode = if metadata.is_dir() { 0o755 } else { 0o644 };
+    if metadata.permissions().readonly() {
+        mode & !0o222
+    } else {
+        mode
+    }
+}
+
+#[cfg(unix)]
+fn link_count(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink()
+}
+
+#[cfg(not(unix))]
+fn link_count(_metadata: &fs::Metadata) -> u64 {
+    1
+}
+
+/// Lists the contents of a directory, or the entry of a single file
+///
+/// `path` is resolved against the current virtual directory; without one the
+/// current directory is listed, starting with its `.` and `..` entries. Dot files are left out of
+/// directory listings unless `show_hidden` is set. With a `pattern`, `path`
+/// must be a directory and only the entries matching it are listed.
+///
+/// With a `limit`, reading stops once one entry more than it has been listed,
+/// so a huge directory isn't read in full just to be cut off.
 pub fn list_directory(
-    server_root: &Path,
+    root: &StorageRoot,
     current_virtual_path: &str,
-) -> Result<Vec<String>, StorageError> {
-    let real_path = virtual_to_real_path(server_root, current_virtual_path);
-
-    // Read directory contents with retries
-    let retries = 3;
-    let mut result = None;
-
-    for attempt in 1..=retries {
-        match fs::read_dir(&real_path) {
-            Ok(entries) => {
-                let mut file_list = vec![];
-
-                // Add . and .. entries first with metadata format
-                file_list.push(".|0|0".to_string());
-                if current_virtual_path != "/" {
-                    file_list.push("..|0|0".to_string());
-                }
-
-                // Add regular files and directories with metadata
-                for entry in entries.flatten() {
-                    let name = entry.file_name().to_string_lossy().to_string();
-
-                    // Get metadata for size and timestamp
-                    if let Ok(metadata) = entry.metadata() {
-                        let size = if metadata.is_dir() { 0 } else { metadata.len() };
-
-                        let timestamp = metadata
-                            .modified()
-                            .ok()
-                            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
-                            .map(|dur| dur.as_secs())
-                            .unwrap_or(0);
-
-                        let name_with_type = if metadata.is_dir() {
-                            format!("{name}/")
-                        } else {
-                            name
-                        };
-
-                        // Format: "name|size|timestamp"
-                        file_list.push(format!("{name_with_type}|{size}|{timestamp}"));
-                    } else {
-                        // If metadata fails, use fallback format
-                        file_list.push(format!("{name}|0|0"));
-                    }
-                }
-
-                result = Some(file_list);
+    path: Option<&str>,
+    pattern: Option<&str>,
+    show_hidden: bool,
+    limit: Option<usize>,
+    max_depth: usize,
+) -> Result<Vec<ListEntry>, StorageError> {
+    let virtual_path = resolve_cwd_path(current_virtual_path, path.unwrap_or(""), max_depth)
+        .map_err(StorageError::InvalidPath)?;
+    let real_path = root
+        .checked_real_path(&virtual_path)
+        .map_err(StorageError::InvalidPath)?;
+
+    if !real_path.try_exists()? {
+        return Err(StorageError::FileNotFound(virtual_path));
+    }
+
+    if pattern.is_some() && !real_path.is_dir() {
+        return Err(StorageError::NotADirectory(virtual_path));
+    }
+
+    // A file lists as its own entry, like `ls file`
+    if !real_path.is_dir() {
+        let name = virtual_path.rsplit('/').next().unwrap_or_default();
+        return Ok(vec![ListEntry::new(name, fs::metadata(&real_path).ok())]);
+    }
+
+    let entries = match with_retries(
+        || fs::read_dir(&real_path),
+        TRANSIENT_ERRORS,
+        root.max_retries(),
+        RETRY_DELAY,
+    ) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            return Err(StorageError::PermissionDenied(virtual_path));
+        }
+        Err(e) => {
+            error!(
+                "Failed to list directory {} (real: {}): {}",
+                virtual_path,
+                real_path.display(),
+                e
+            );
+            return Err(StorageError::from(e));
+        }
+    };
+
+    let read_limit = read_limit(limit);
+    let mut file_list = vec![];
+
+    // Add . and .. entries first, as ls -a does
+    if pattern.is_none() {
+        file_list.push(ListEntry::new(".", fs::metadata(&real_path).ok()));
+        if virtual_path != "/" {
+            let parent = real_path
+                .parent()
+                .and_then(|parent| fs::metadata(parent).ok());
+            file_list.push(ListEntry::new("..", parent));
+        }
+    }
+
+    // Add regular files and directories with metadata
+    for entry in entries.flatten() {
+        if file_list.len() >= read_limit {
+            break;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !show_hidden && name.starts_with('.') && !is_dot_pattern(pattern) {
+            continue;
+        }
+        if pattern.is_some_and(|pattern| !wildcard::matches(pattern, &name)) {
+            continue;
+        }
+        file_list.push(ListEntry::new(&name, entry.metadata().ok()));
+    }
+
+    // Mount points show up as directories of the root
+    if virtual_path == "/" {
+        for name in root.mount_names() {
+            if file_list.len() >= read_limit {
                 break;
             }
-            Err(e) => {
-                if attempt < retries && e.kind() == std::io::ErrorKind::PermissionDenied {
-                    thread::sleep(Duration::from_millis(100 * attempt as u64));
-                    continue;
-                } else {
-                    // After retries, check if it's still a permission issue
-                    if e.kind() == std::io::ErrorKind::PermissionDenied {
-                        return Err(StorageError::PermissionDenied(
-                            current_virtual_path.to_string(),
-                        ));
-                    }
-                    error!(
-                        "Failed to list directory {} (real: {}): {}",
-                        current_virtual_path,
-                        real_path.display(),
-                        e
-                    );
-                    return Err(StorageError::from(e));
-                }
+            let listed = file_list.iter().any(|entry| entry.name == name);
+            if !listed && pattern.is_none_or(|pattern| wildcard::matches(pattern, name)) {
+                let (mount, _) = root.real_path(&format!("/{name}"));
+                file_list.push(ListEntry::new(name, fs::metadata(mount).ok()));
             }
         }
     }
 
-    let entries = result.ok_or_else(|| {
-        StorageError::IoError(std::io::Error::other(
-            "Failed to read directory after retries",
-        ))
-    })?;
-
     info!(
         "Listed directory {} (real: {}) - {} entries",
-        current_virtual_path,
+        virtual_path,
         real_path.display(),
-        entries.len()
+        file_list.len()
     );
 
-    Ok(entries)
+    Ok(file_list)
+}
+
+/// How many entries to read for a listing capped at `limit`: one more, so the
+/// caller can tell it was cut off
+fn read_limit(limit: Option<usize>) -> usize {
+    limit.map_or(usize::MAX, |max| max.saturating_add(1))
+}
+
+/// A pattern that itself starts with `.` asks for dot files, as in a shell
+fn is_dot_pattern(pattern: Option<&str>) -> bool {
+    pattern.is_some_and(|pattern| pattern.starts_with('.'))
+}
+
+/// Lists the names in a directory, for NLST
+///
+/// `path` is resolved against the current virtual directory; without one the
+/// current directory is listed. Names are sorted and carry no metadata.
+/// With a `pattern` only the matching names are listed, leaving out dot files
+/// unless the pattern starts with `.`. With a `limit`, reading stops once one
+/// name more than it has been found, and only those are sorted.
+pub fn list_names(
+    root: &StorageRoot,
+    current_virtual_path: &str,
+    path: Option<&str>,
+    pattern: Option<&str>,
+    limit: Option<usize>,
+    max_depth: usize,
+) -> Result<Vec<String>, StorageError> {
+    let read_limit = read_limit(limit);
+    let virtual_dir_path = resolve_cwd_path(current_virtual_path, path.unwrap_or(""), max_depth)
+        .map_err(StorageError::InvalidPath)?;
+    let dir_path = root
+        .checked_real_path(&virtual_dir_path)
+        .map_err(StorageError::InvalidPath)?;
+
+    if !dir_path.try_exists()? {
+        return Err(StorageError::DirectoryNotFound(virtual_dir_path));
+    }
+
+    if !dir_path.is_dir() {
+        return Err(StorageError::NotADirectory(virtual_dir_path));
+    }
+
+    let mut names = fs::read_dir(&dir_path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied => {
+                StorageError::PermissionDenied(virtual_dir_path.clone())
+            }
+            _ => StorageError::from(e),
+        })?
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| match pattern {
+            Some(pattern) => {
+                wildcard::matches(pattern, name)
+                    && (!name.starts_with('.') || is_dot_pattern(Some(pattern)))
+            }
+            None => true,
+        })
+        .take(read_limit)
+        .collect::<Vec<_>>();
+    if virtual_dir_path == "/" {
+        for name in root.mount_names() {
+            if names.len() < read_limit
+                && !names.iter().any(|listed| listed == name)
+                && pattern.is_none_or(|pattern| wildcard::matches(pattern, name))
+            {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+
+    info!(
+        "Listed names in {} (real: {}) - {} entries",
+        virtual_dir_path,
+        dir_path.display(),
+        names.len()
+    );
+
+    Ok(names)
+}
+
+/// Lists a directory with the selected facts of each entry, for MLSD
+///
+/// `path` is resolved against the current virtual directory; without one the
+/// current directory is listed. Entries are sorted by name. With a `limit`,
+/// reading stops once one entry more than it has been found, and only those
+/// are sorted.
+pub fn list_facts(
+    root: &StorageRoot,
+    current_virtual_path: &str,
+    path: Option<&str>,
+    facts: FactSet,
+    limit: Option<usize>,
+    max_depth: usize,
+) -> Result<Vec<String>, StorageError> {
+    let read_limit = read_limit(limit);
+    let virtual_dir_path = resolve_cwd_path(current_virtual_path, path.unwrap_or(""), max_depth)
+        .map_err(StorageError::InvalidPath)?;
+    let dir_path = root
+        .checked_real_path(&virtual_dir_path)
+        .map_err(StorageError::InvalidPath)?;
+
+    if !dir_path.try_exists()? {
+        return Err(StorageError::DirectoryNotFound(virtual_dir_path));
+    }
+
+    if !dir_path.is_dir() {
+        return Err(StorageError::NotADirectory(virtual_dir_path));
+    }
+
+    let mut entries = fs::read_dir(&dir_path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied => {
+                StorageError::PermissionDenied(virtual_dir_path.clone())
+            }
+            _ => StorageError::from(e),
+        })?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            entry.metadata().ok().map(|metadata| (name, metadata))
+        })
+        .take(read_limit)
+        .collect::<Vec<_>>();
+    if virtual_dir_path == "/" {
+        for name in root.mount_names() {
+            if entries.len() < read_limit
+                && !entries.iter().any(|(listed, _)| listed == name)
+                && let Ok(metadata) = fs::metadata(root.real_path(&format!("/{name}")).0)
+            {
+                entries.push((name.to_string(), metadata));
+            }
+        }
+    }
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(entries
+        .iter()
+        .map(|(name, metadata)| facts.describe(name, metadata))
+        .collect())
+}
+
+/// Describes a single file or directory with its selected facts, for MLST
+///
+/// The entry is named by its full virtual path.
+pub fn describe_entry(
+    root: &StorageRoot,
+    current_virtual_path: &str,
+    path: Option<&str>,
+    facts: FactSet,
+    max_depth: usize,
+) -> Result<String, StorageError> {
+    let virtual_path = resolve_cwd_path(current_virtual_path, path.unwrap_or(""), max_depth)
+        .map_err(StorageError::InvalidPath)?;
+    let real_path = root
+        .checked_real_path(&virtual_path)
+        .map_err(StorageError::InvalidPath)?;
+
+    let metadata = match fs::metadata(&real_path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(StorageError::FileNotFound(virtual_path));
+        }
+        Err(e) => return Err(StorageError::from(e)),
+    };
+
+    Ok(facts.describe(&virtual_path, &metadata))
 }
 
 /// Prepares for file retrieval
 pub fn prepare_file_retrieval(
-    server_root: &Path,
+    root: &StorageRoot,
     current_virtual_path: &str,
     filename: &str,
-    config: &StartupConfig,
+    max_depth: usize,
 ) -> Result<PathBuf, StorageError> {
     if filename.is_empty() {
         return Err(StorageError::InvalidPath("Empty filename".into()));
     }
 
-    let (file_path, virtual_file_path) =
-        resolve_and_validate_file_path(server_root, current_virtual_path, filename, config)
-            .map_err(StorageError::InvalidPath)?;
-
-    // Check if file exists
-    if !file_path.exists() {
-        return Err(StorageError::FileNotFound(virtual_file_path));
-    }
+    let (file_path, virtual_file_path) = root
+        .resolve_file_path(current_virtual_path, filename, max_depth)
+        .map_err(StorageError::InvalidPath)?;
+
+    let metadata = match with_retries(
+        || fs::metadata(&file_path),
+        TRANSIENT_ERRORS,
+        root.max_retries(),
+        RETRY_DELAY,
+    ) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(StorageError::FileNotFound(virtual_file_path));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            return Err(StorageError::PermissionDenied(virtual_file_path));
+        }
+        Err(e) => return Err(StorageError::from(e)),
+    };
 
-    if !file_path.is_file() {
-        return Err(StorageError::NotADirectory(virtual_file_path));
+    if !metadata.is_file() {
+        return Err(StorageError::NotAFile(virtual_file_path));
     }
 
     info!(
@@ -140,50 +436,53 @@ pub fn prepare_file_retrieval(
 }
 
 /// Prepares for file storage
+///
+/// Returns the destination and the temporary path the upload is written to.
+/// With `temp_dir` set, temporary files are named after the escaped virtual
+/// path so uploads to different directories never collide.
 pub fn prepare_file_storage(
-    server_root: &Path,
+    root: &StorageRoot,
     current_virtual_path: &str,
     filename: &str,
-    config: &StartupConfig,
+    max_depth: usize,
+    temp_dir: Option<&Path>,
 ) -> Result<(PathBuf, PathBuf), StorageError> {
     if filename.is_empty() {
         return Err(StorageError::InvalidPath("Empty filename".into()));
     }
 
-    let (file_path, virtual_file_path) =
-        resolve_and_validate_file_path(server_root, current_virtual_path, filename, config)
-            .map_err(StorageError::InvalidPath)?;
+    let (file_path, virtual_file_path) = root
+        .resolve_file_path(current_virtual_path, filename, max_depth)
+        .map_err(StorageError::InvalidPath)?;
 
-    // Check if parent directory exists
-    if let Some(parent_dir) = file_path.parent() {
-        if !parent_dir.exists() {
-            return Err(StorageError::DirectoryNotFound(
-                parent_dir.to_string_lossy().to_string(),
-            ));
-        }
-        if !parent_dir.is_dir() {
-            return Err(StorageError::NotADirectory(
-                parent_dir.to_string_lossy().to_string(),
-            ));
-        }
-    }
+    // Check if parent directory exists, reporting it by its virtual path
+    check_parent_directory(root, &file_path, &virtual_file_path)?;
 
     // Check if file already exists
-    if file_path.exists() {
+    if file_path.try_exists()? {
         return Err(StorageError::FileAlreadyExists(virtual_file_path));
     }
 
-    // Create temporary file path
-    let temp_file_path = file_path.with_extension(format!(
-        "{}.tmp",
-        file_path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("")
-    ));
+    // Create temporary file path, in the temp directory if one is configured
+    let temp_file_path = match temp_dir {
+        Some(temp_dir) => temp_dir.join(format!(
+            "{}.tmp",
+            virtual_file_path
+                .trim_start_matches('/')
+                .replace('%', "%25")
+                .replace('/', "%2F")
+        )),
+        None => file_path.with_extension(format!(
+            "{}.tmp",
+            file_path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("")
+        )),
+    };
 
     // Check if temporary file exists (upload in progress)
-    if temp_file_path.exists() {
+    if temp_file_path.try_exists()? {
         return Err(StorageError::UploadInProgress(virtual_file_path));
     }
 
@@ -197,66 +496,427 @@ pub fn prepare_file_storage(
     Ok((file_path, temp_file_path))
 }
 
+/// Fails unless the parent of `path` is an existing directory, naming it by its virtual path
+fn check_parent_directory(
+    root: &StorageRoot,
+    path: &Path,
+    virtual_path: &str,
+) -> Result<(), StorageError> {
+    let Some(parent_dir) = path.parent() else {
+        return Ok(());
+    };
+    let virtual_parent = match virtual_path.rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    };
+    let metadata = match with_retries(
+        || fs::metadata(parent_dir),
+        TRANSIENT_ERRORS,
+        root.max_retries(),
+        RETRY_DELAY,
+    ) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(StorageError::DirectoryNotFound(virtual_parent));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            return Err(StorageError::PermissionDenied(virtual_parent));
+        }
+        Err(e) => return Err(StorageError::from(e)),
+    };
+    if !metadata.is_dir() {
+        return Err(StorageError::NotADirectory(virtual_parent));
+    }
+    Ok(())
+}
+
+/// A directory created by MKD
+#[derive(Debug, Clone)]
+pub struct CreatedDirectory {
+    pub virtual_path: String,
+    /// Permission bits it was created with, where the platform has them
+    pub mode: Option<u32>,
+}
+
+/// Creates a directory, returning its virtual path and permission bits
+pub fn create_directory(
+    root: &StorageRoot,
+    current_virtual_path: &str,
+    dirname: &str,
+    max_depth: usize,
+    umask: Option<u32>,
+) -> Result<CreatedDirectory, StorageError> {
+    if dirname.is_empty() {
+        return Err(StorageError::InvalidPath("Empty directory name".into()));
+    }
+
+    let (dir_path, virtual_dir_path) = root
+        .resolve_file_path(current_virtual_path, dirname, max_depth)
+        .map_err(StorageError::InvalidPath)?;
+
+    check_parent_directory(root, &dir_path, &virtual_dir_path)?;
+
+    if dir_path.try_exists()? {
+        return Err(StorageError::FileAlreadyExists(virtual_dir_path));
+    }
+
+    match fs::create_dir(&dir_path) {
+        Ok(()) => {
+            if let Some(umask) = umask
+                && let Err(e) = apply_umask(&dir_path, DIRECTORY_MODE, umask)
+            {
+                warn!("Failed to apply umask to {}: {e}", dir_path.display());
+            }
+            let mode = permission_mode(&dir_path).unwrap_or_else(|e| {
+                warn!("Failed to read the mode of {}: {e}", dir_path.display());
+                None
+            });
+            info!(
+                "Created directory {} (virtual: {}, real: {}, mode: {})",
+                dirname,
+                virtual_dir_path,
+                dir_path.display(),
+                mode.map_or("unknown".to_string(), |mode| format!("{mode:04o}"))
+            );
+            Ok(CreatedDirectory {
+                virtual_path: virtual_dir_path,
+                mode,
+            })
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            Err(StorageError::PermissionDenied(virtual_dir_path))
+        }
+        Err(e) => {
+            error!(
+                "Failed to create directory {} (virtual: {}, real: {}): {}",
+                dirname,
+                virtual_dir_path,
+                dir_path.display(),
+                e
+            );
+            Err(StorageError::from(e))
+        }
+    }
+}
+
+/// Makes sure a user's home directory exists, creating it and any missing
+/// parents with the umask applied. Returns whether anything was created.
+pub fn ensure_home_directory(
+    root: &StorageRoot,
+    virtual_home: &str,
+    umask: Option<u32>,
+) -> Result<bool, StorageError> {
+    let home_path = root
+        .checked_real_path(virtual_home)
+        .map_err(StorageError::InvalidPath)?;
+
+    if home_path.is_dir() {
+        return Ok(false);
+    }
+    if home_path.try_exists()? {
+        return Err(StorageError::NotADirectory(virtual_home.to_string()));
+    }
+
+    let missing: Vec<&Path> = home_path
+        .ancestors()
+        .take_while(|path| !path.exists())
+        .collect();
+    for dir_path in missing.into_iter().rev() {
+        fs::create_dir(dir_path)?;
+        if let Some(umask) = umask
+            && let Err(e) = apply_umask(dir_path, DIRECTORY_MODE, umask)
+        {
+            warn!("Failed to apply umask to {}: {e}", dir_path.display());
+        }
+    }
+
+    info!(
+        "Created home directory {} (real: {})",
+        virtual_home,
+        home_path.display()
+    );
+    Ok(true)
+}
+
+/// Removes an empty directory
+pub fn remove_directory(
+    root: &StorageRoot,
+    current_virtual_path: &str,
+    dirname: &str,
+    max_depth: usize,
+) -> Result<(), StorageError> {
+    if dirname.is_empty() {
+        return Err(StorageError::InvalidPath("Empty directory name".into()));
+    }
+
+    let (dir_path, virtual_dir_path) = root
+        .resolve_file_path(current_virtual_path, dirname, max_depth)
+        .map_err(StorageError::InvalidPath)?;
+
+    // The server root and mount points can never be removed
+    if virtual_dir_path == "/" || root.is_mount_point(&virtual_dir_path) {
+        return Err(StorageError::PermissionDenied(virtual_dir_path));
+    }
+
+    if !dir_path.try_exists()? {
+        return Err(StorageError::DirectoryNotFound(virtual_dir_path));
+    }
+
+    if !dir_path.is_dir() {
+        return Err(StorageError::NotADirectory(virtual_dir_path));
+    }
+
+    match fs::remove_dir(&dir_path) {
+        Ok(()) => {
+            info!(
+                "Removed directory {} (virtual: {}, real: {})",
+                dirname,
+                virtual_dir_path,
+                dir_path.display()
+            );
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::DirectoryNotEmpty => {
+            Err(StorageError::DirectoryNotEmpty(virtual_dir_path))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            Err(StorageError::PermissionDenied(virtual_dir_path))
+        }
+        Err(e) => {
+            error!(
+                "Failed to remove directory {} (virtual: {}, real: {}): {}",
+                dirname,
+                virtual_dir_path,
+                dir_path.display(),
+                e
+            );
+            Err(StorageError::from(e))
+        }
+    }
+}
+
 /// Deletes a file
 pub fn delete_file(
-    server_root: &Path,
+    root: &StorageRoot,
     current_virtual_path: &str,
     filename: &str,
-    config: &StartupConfig,
+    max_depth: usize,
 ) -> Result<(), StorageError> {
     if filename.is_empty() {
         return Err(StorageError::InvalidPath("Empty filename".into()));
     }
 
-    let (file_path, virtual_file_path) =
-        resolve_and_validate_file_path(server_root, current_virtual_path, filename, config)
-            .map_err(StorageError::InvalidPath)?;
+    let (file_path, virtual_file_path) = root
+        .resolve_file_path(current_virtual_path, filename, max_depth)
+        .map_err(StorageError::InvalidPath)?;
 
     // Verify file exists
-    if !file_path.exists() {
+    if !file_path.try_exists()? {
         return Err(StorageError::FileNotFound(virtual_file_path));
     }
 
     if !file_path.is_file() {
-        return Err(StorageError::NotADirectory(virtual_file_path));
-    }
-
-    // Delete with retries for permission issues
-    let retries = 3;
-    for attempt in 1..=retries {
-        match fs::remove_file(&file_path) {
-            Ok(_) => {
-                info!(
-                    "Deleted file {} (virtual: {}, real: {})",
-                    filename,
-                    virtual_file_path,
-                    file_path.display()
-                );
-                return Ok(());
-            }
-            Err(e) => {
-                if attempt < retries && e.kind() == std::io::ErrorKind::PermissionDenied {
-                    thread::sleep(Duration::from_millis(100 * attempt as u64));
-                    continue;
-                } else {
-                    // After retries, check if it's still a permission issue
-                    if e.kind() == std::io::ErrorKind::PermissionDenied {
-                        return Err(StorageError::PermissionDenied(virtual_file_path.clone()));
-                    }
-                    error!(
-                        "Failed to delete file {} (virtual: {}, real: {}): {}",
-                        filename,
-                        virtual_file_path,
-                        file_path.display(),
-                        e
-                    );
-                    return Err(StorageError::from(e));
-                }
-            }
+        return Err(StorageError::NotAFile(virtual_file_path));
+    }
+
+    match with_retries(
+        || fs::remove_file(&file_path),
+        TRANSIENT_ERRORS,
+        root.max_retries(),
+        RETRY_DELAY,
+    ) {
+        Ok(()) => {
+            info!(
+                "Deleted file {} (virtual: {}, real: {})",
+                filename,
+                virtual_file_path,
+                file_path.display()
+            );
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            Err(StorageError::PermissionDenied(virtual_file_path))
+        }
+        Err(e) => {
+            error!(
+                "Failed to delete file {} (virtual: {}, real: {}): {}",
+                filename,
+                virtual_file_path,
+                file_path.display(),
+                e
+            );
+            Err(StorageError::from(e))
+        }
+    }
+}
+
+/// Size and modification time of a regular file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMetadata {
+    pub virtual_path: String,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Gets the size and modification time of a file
+pub fn file_metadata(
+    root: &StorageRoot,
+    current_virtual_path: &str,
+    filename: &str,
+    max_depth: usize,
+) -> Result<FileMetadata, StorageError> {
+    if filename.is_empty() {
+        return Err(StorageError::InvalidPath("Empty filename".into()));
+    }
+
+    let (file_path, virtual_path) = root
+        .resolve_file_path(current_virtual_path, filename, max_depth)
+        .map_err(StorageError::InvalidPath)?;
+
+    let metadata = match fs::metadata(&file_path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(StorageError::FileNotFound(virtual_path));
+        }
+        Err(e) => return Err(StorageError::from(e)),
+    };
+
+    if !metadata.is_file() {
+        return Err(StorageError::NotAFile(virtual_path));
+    }
+
+    Ok(FileMetadata {
+        virtual_path,
+        size: metadata.len(),
+        modified: metadata.modified()?,
+    })
+}
+
+/// A file's digest, as reported by HASH
+#[derive(Debug, Clone)]
+pub struct FileHash {
+    pub size: u64,
+    pub digest: String,
+}
+
+/// Hashes a file with `algorithm`, reading it through in chunks
+pub fn file_hash(
+    root: &StorageRoot,
+    current_virtual_path: &str,
+    filename: &str,
+    max_depth: usize,
+    algorithm: HashAlgorithm,
+) -> Result<FileHash, StorageError> {
+    if filename.is_empty() {
+        return Err(StorageError::InvalidPath("Empty filename".into()));
+    }
+
+    let (file_path, virtual_path) = root
+        .resolve_file_path(current_virtual_path, filename, max_depth)
+        .map_err(StorageError::InvalidPath)?;
+
+    let metadata = match fs::metadata(&file_path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(StorageError::FileNotFound(virtual_path));
+        }
+        Err(e) => return Err(StorageError::from(e)),
+    };
+
+    if !metadata.is_file() {
+        return Err(StorageError::NotAFile(virtual_path));
+    }
+
+    Ok(FileHash {
+        size: metadata.len(),
+        digest: hash::hash_file(&file_path, algorithm)?,
+    })
+}
+
+/// Adds up the size of every file under a normalized virtual directory,
+/// mounts included when it is the root
+///
+/// Symlinks are counted as links rather than followed, so nothing is counted
+/// twice or outside the storage root. A missing directory, such as a mount
+/// whose directory is gone, counts as empty.
+pub fn disk_usage(root: &StorageRoot, virtual_dir: &str) -> Result<u64, StorageError> {
+    if virtual_dir != "/" {
+        let path = root
+            .checked_real_path(virtual_dir)
+            .map_err(StorageError::InvalidPath)?;
+        return match directory_size(&path) {
+            Ok(size) => Ok(size),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(StorageError::from(e)),
+        };
+    }
+
+    let mut total = 0;
+    for physical_root in root.physical_roots() {
+        match directory_size(physical_root) {
+            Ok(size) => total += size,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(StorageError::from(e)),
         }
     }
+    Ok(total)
+}
+
+fn directory_size(path: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.path().symlink_metadata()?;
+        total += if metadata.is_dir() {
+            directory_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(total)
+}
+
+/// Sets the modification time of a file
+pub fn set_modification_time(
+    root: &StorageRoot,
+    current_virtual_path: &str,
+    filename: &str,
+    modified: SystemTime,
+    max_depth: usize,
+) -> Result<(), StorageError> {
+    if filename.is_empty() {
+        return Err(StorageError::InvalidPath("Empty filename".into()));
+    }
+
+    let (file_path, virtual_file_path) = root
+        .resolve_file_path(current_virtual_path, filename, max_depth)
+        .map_err(StorageError::InvalidPath)?;
+
+    if !file_path.try_exists()? {
+        return Err(StorageError::FileNotFound(virtual_file_path));
+    }
+
+    if !file_path.is_file() {
+        return Err(StorageError::NotAFile(virtual_file_path));
+    }
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .open(&file_path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied => {
+                StorageError::PermissionDenied(virtual_file_path.clone())
+            }
+            _ => StorageError::from(e),
+        })?;
+    file.set_modified(modified)?;
+
+    info!(
+        "Set modification time of {} (virtual: {}, real: {})",
+        filename,
+        virtual_file_path,
+        file_path.display()
+    );
 
-    Err(StorageError::IoError(std::io::Error::other(
-        "Failed to delete file after retries",
-    )))
+    Ok(())
 }
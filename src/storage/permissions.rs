@@ -1,3 +1,43 @@
 //! File permissions
 //!
 //! Handles file permission management.
+
+use std::io;
+use std::path::Path;
+
+/// Mode given to new files before the umask is applied
+pub const FILE_MODE: u32 = 0o666;
+
+/// Mode given to new directories before the umask is applied
+pub const DIRECTORY_MODE: u32 = 0o777;
+
+/// Sets `path` to `base_mode` with the bits in `umask` cleared.
+///
+/// Used after creating a file or directory, so the configured umask applies
+/// regardless of the process umask.
+#[cfg(unix)]
+pub fn apply_umask(path: &Path, base_mode: u32, umask: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(base_mode & !umask))
+}
+
+/// Unix permission bits do not exist here, so the umask is ignored.
+#[cfg(not(unix))]
+pub fn apply_umask(_path: &Path, _base_mode: u32, _umask: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Reads the permission bits `path` ended up with, such as `0o755`.
+#[cfg(unix)]
+pub fn permission_mode(path: &Path) -> io::Result<Option<u32>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Ok(Some(std::fs::metadata(path)?.permissions().mode() & 0o7777))
+}
+
+/// Unix permission bits do not exist here, so there is no mode to report.
+#[cfg(not(unix))]
+pub fn permission_mode(_path: &Path) -> io::Result<Option<u32>> {
+    Ok(None)
+}
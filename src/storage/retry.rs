@@ -0,0 +1,46 @@
+//! Retrying transient storage failures
+//!
+//! Some filesystem errors clear up on their own: a file briefly locked by a
+//! virus scanner or backup tool reports `PermissionDenied`, and a signal can
+//! interrupt a call. Storage operations retry those with a growing delay
+//! instead of failing the command straight away.
+
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+
+/// Errors worth retrying when opening, listing or removing files
+pub const TRANSIENT_ERRORS: &[ErrorKind] = &[ErrorKind::PermissionDenied, ErrorKind::Interrupted];
+
+/// Errors worth retrying while reading an open file
+pub const TRANSIENT_READ_ERRORS: &[ErrorKind] = &[ErrorKind::Interrupted];
+
+/// Delay before the first retry; each further retry waits this much longer
+pub const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Runs `op`, retrying it up to `max_retries` times while it fails with one of `kinds`
+///
+/// The `n`th retry waits `n * base_delay` first. Any other error, or the last
+/// one once the retries run out, is returned as is.
+pub fn with_retries<T>(
+    mut op: impl FnMut() -> io::Result<T>,
+    kinds: &[ErrorKind],
+    max_retries: usize,
+    base_delay: Duration,
+) -> io::Result<T> {
+    let mut retries = 0;
+    loop {
+        match op() {
+            Err(e) if kinds.contains(&e.kind()) && retries < max_retries => {
+                retries += 1;
+                warn!(
+                    "Transient storage error (attempt {retries}/{max_retries}): {e}. Retrying..."
+                );
+                thread::sleep(base_delay * retries as u32);
+            }
+            result => return result,
+        }
+    }
+}
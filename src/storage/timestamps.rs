@@ -0,0 +1,202 @@
+//! FTP timestamps
+//!
+//! Converts between `SystemTime` and the `YYYYMMDDHHMMSS` UTC time-val format
+//! used by the RFC 3659 commands, optionally with milliseconds, and formats
+//! times the way `ls -l` shows them.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// How old a time may be and still be listed with its time of day, as `ls` does
+const RECENT_SECONDS: i64 = 15_778_476; // half of an average Gregorian year
+
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Time zone LIST shows modification times in
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ListTimeZone {
+    /// Coordinated Universal Time, like the RFC 3659 commands
+    #[default]
+    Utc,
+    /// The server's local time zone, as `ls -l` on the server would show
+    Local,
+}
+
+impl ListTimeZone {
+    /// Seconds to add to a UTC time to get the time in this zone
+    fn offset_seconds(self, unix_seconds: i64) -> i64 {
+        match self {
+            ListTimeZone::Utc => 0,
+            ListTimeZone::Local => local_offset_seconds(unix_seconds),
+        }
+    }
+}
+
+/// Parses an RFC 3659 time-val (`YYYYMMDDHHMMSS`, UTC).
+///
+/// Returns `None` unless the value is exactly 14 digits describing a valid
+/// date and time at or after the Unix epoch.
+pub fn parse_ftp_timestamp(value: &str) -> Option<SystemTime> {
+    if value.len() != 14 || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| value[range].parse::<i64>().ok();
+    let year = field(0..4)?;
+    let month = field(4..6)?;
+    let day = field(6..8)?;
+    let hour = field(8..10)?;
+    let minute = field(10..12)?;
+    let second = field(12..14)?;
+
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    let seconds =
+        days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second;
+    let seconds = u64::try_from(seconds).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Formats a time as an RFC 3659 time-val (`YYYYMMDDHHMMSS`, UTC).
+///
+/// Times before the Unix epoch are clamped to it.
+pub fn format_ftp_timestamp(time: SystemTime) -> String {
+    let seconds = unix_seconds(time);
+
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let time_of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60
+    )
+}
+
+/// Formats a time as a time-val with milliseconds (`YYYYMMDDHHMMSS.sss`, UTC).
+///
+/// RFC 3659 allows the fractional part; it is `.000` on filesystems that only
+/// keep whole seconds.
+pub fn format_ftp_timestamp_millis(time: SystemTime) -> String {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_millis())
+        .unwrap_or(0);
+    format!("{}.{millis:03}", format_ftp_timestamp(time))
+}
+
+/// Formats a modification time for an `ls -l` style listing, in UTC.
+///
+/// Times from the six months before `now` show the time of day (`Mon DD HH:MM`);
+/// older times, and times in the future, show the year instead (`Mon DD  YYYY`).
+pub fn format_ls_timestamp(time: SystemTime, now: SystemTime) -> String {
+    format_ls_timestamp_in(time, now, ListTimeZone::Utc)
+}
+
+/// Formats a modification time for an `ls -l` style listing, in `zone`.
+///
+/// Whether a time counts as recent depends only on its age, not the zone.
+pub fn format_ls_timestamp_in(time: SystemTime, now: SystemTime, zone: ListTimeZone) -> String {
+    let utc_seconds = unix_seconds(time);
+    let age = unix_seconds(now) - utc_seconds;
+    let seconds = utc_seconds + zone.offset_seconds(utc_seconds);
+
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let month = MONTH_NAMES[(month - 1) as usize];
+    if (0..RECENT_SECONDS).contains(&age) {
+        let time_of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+        format!(
+            "{month} {day:>2} {:02}:{:02}",
+            time_of_day / 3600,
+            time_of_day % 3600 / 60
+        )
+    } else {
+        format!("{month} {day:>2}  {year}")
+    }
+}
+
+/// The local time zone's offset from UTC at a time, from the C library.
+#[cfg(unix)]
+fn local_offset_seconds(unix_seconds: i64) -> i64 {
+    let time = unix_seconds as libc::time_t;
+    // SAFETY: `tm` is plain data that localtime_r fills in, and both pointers
+    // are valid for the duration of the call
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff
+}
+
+/// Without a way to look up the local zone, local times are shown as UTC.
+#[cfg(not(unix))]
+fn local_offset_seconds(_unix_seconds: i64) -> i64 {
+    0
+}
+
+/// Seconds since the Unix epoch, clamping earlier times to it.
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Proleptic Gregorian date for a count of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
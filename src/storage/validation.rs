@@ -1,12 +1,27 @@
 //! Path validation and sanitization
 //!
 //! Handles comprehensive path validation, security checks, and path resolution for FTP operations.
+//!
+//! `..` is resolved lexically, in one place: requested paths are first joined
+//! onto the client's virtual directory and `normalize_path` then folds each `..`
+//! into its parent, stopping at the virtual root, and drops each `.`. A path
+//! that is still relative when validated cannot be resolved, so any `..` or
+//! `.` left in it is rejected by `validate_path_component`.
+//!
+//! Virtual paths map onto `server_root`, except under mount points: a
+//! [`StorageRoot`] grafts other physical directories onto top-level virtual
+//! directories, and paths beneath one are bounds-checked against it instead.
 
-use crate::config::StartupConfig;
 use log::warn;
-use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
 /// Normalize path separators to Unix style and validate path structure
+///
+/// For absolute paths, `..` components are resolved lexically and clamped at
+/// the root, so `/a/../b` becomes `/b` and `/..` becomes `/`, and `.`
+/// components are dropped. Relative paths keep their `..` and `.` components.
 pub fn normalize_path(path: &str) -> Result<String, String> {
     if path.is_empty() {
         return Ok("/".to_string());
@@ -14,24 +29,44 @@ pub fn normalize_path(path: &str) -> Result<String, String> {
 
     // Convert Windows-style backslashes to forward slashes
     let normalized = path.replace('\\', "/");
-
-    // Remove consecutive slashes
-    let normalized = normalized
-        .split('/')
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<_>>()
-        .join("/");
+    let is_absolute = normalized.starts_with('/');
+
+    // Remove consecutive slashes and resolve ".." against the root
+    let mut components: Vec<&str> = Vec::new();
+    for component in normalized.split('/').filter(|s| !s.is_empty()) {
+        if component == ".." && is_absolute {
+            components.pop();
+        } else if component == "." && is_absolute {
+            continue;
+        } else {
+            components.push(component);
+        }
+    }
+    let normalized = components.join("/");
 
     // Ensure leading slash for absolute paths
-    if path.starts_with('/') || path.starts_with('\\') {
+    if is_absolute {
         Ok(format!("/{normalized}"))
     } else {
         Ok(normalized)
     }
 }
 
+/// Join a requested path onto the current virtual directory
+///
+/// Absolute requests replace the current directory; relative ones are appended.
+fn join_virtual_path(current_virtual_path: &str, requested: &str) -> String {
+    if requested.starts_with('/') || requested.starts_with('\\') {
+        requested.to_string()
+    } else if current_virtual_path.ends_with('/') {
+        format!("{current_virtual_path}{requested}")
+    } else {
+        format!("{current_virtual_path}/{requested}")
+    }
+}
+
 /// Validate directory depth doesn't exceed maximum allowed
-pub fn validate_directory_depth(path: &str, config: &StartupConfig) -> Result<(), String> {
+pub fn validate_directory_depth(path: &str, max_depth: usize) -> Result<(), String> {
     let depth = if path == "/" {
         0
     } else {
@@ -41,10 +76,9 @@ pub fn validate_directory_depth(path: &str, config: &StartupConfig) -> Result<()
             .count()
     };
 
-    if depth > config.max_directory_depth {
+    if depth > max_depth {
         return Err(format!(
-            "Directory depth {depth} exceeds maximum allowed depth of {}",
-            config.max_directory_depth
+            "Directory depth {depth} exceeds maximum allowed depth of {max_depth}"
         ));
     }
 
@@ -70,26 +104,36 @@ pub fn validate_path_component(component: &str) -> Result<(), String> {
         }
     }
 
-    // Check for reserved names on Windows
+    // Device names are only reserved on Windows; they are ordinary filenames elsewhere
+    #[cfg(windows)]
+    if is_reserved_device_name(component) {
+        return Err(format!("Reserved filename: {component}"));
+    }
+
+    Ok(())
+}
+
+/// Check whether a component names a Windows device (`CON`, `aux.txt`, `LPT1.log`, ...)
+///
+/// Windows ignores any extension and trailing spaces when matching device names.
+#[cfg(windows)]
+fn is_reserved_device_name(component: &str) -> bool {
     let reserved_names = [
         "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
         "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
     ];
 
-    if reserved_names.contains(&component.to_uppercase().as_str()) {
-        return Err(format!("Reserved filename: {component}"));
-    }
-
-    Ok(())
+    let stem = component.split('.').next().unwrap_or(component).trim_end();
+    reserved_names.contains(&stem.to_uppercase().as_str())
 }
 
 /// Comprehensive path validation
-pub fn validate_path(path: &str, config: &StartupConfig) -> Result<String, String> {
+pub fn validate_path(path: &str, max_depth: usize) -> Result<String, String> {
     // Step 1: Normalize path separators
     let normalized = normalize_path(path)?;
 
     // Step 2: Validate directory depth
-    validate_directory_depth(&normalized, config)?;
+    validate_directory_depth(&normalized, max_depth)?;
 
     // Step 3: Validate each path component
     if normalized != "/" {
@@ -111,7 +155,7 @@ pub fn validate_path(path: &str, config: &StartupConfig) -> Result<String, Strin
 pub fn resolve_file_path(
     current_virtual_path: &str,
     file_path: &str,
-    config: &StartupConfig,
+    max_depth: usize,
 ) -> Result<String, String> {
     let file_path = file_path.trim();
 
@@ -119,28 +163,22 @@ pub fn resolve_file_path(
         return Err("Empty file path".to_string());
     }
 
-    // Determine the virtual file path
-    let virtual_file_path = if file_path.starts_with('/') || file_path.starts_with('\\') {
-        // Absolute path
-        validate_path(file_path, config)?
-    } else {
-        // Relative path - resolve relative to current virtual directory
-        let combined = if current_virtual_path.ends_with('/') {
-            format!("{current_virtual_path}{file_path}")
-        } else {
-            format!("{current_virtual_path}/{file_path}")
-        };
-        validate_path(&combined, config)?
-    };
+    let virtual_file_path = validate_path(
+        &join_virtual_path(current_virtual_path, file_path),
+        max_depth,
+    )?;
 
     Ok(virtual_file_path)
 }
 
 /// Resolve a directory path for CWD command
+///
+/// Trailing slashes are ignored, so `dir/` and `dir` resolve alike, and `.`
+/// stays in the current directory.
 pub fn resolve_cwd_path(
     current_virtual_path: &str,
     requested_path: &str,
-    config: &StartupConfig,
+    max_depth: usize,
 ) -> Result<String, String> {
     let requested = requested_path.trim();
 
@@ -148,24 +186,10 @@ pub fn resolve_cwd_path(
         return Ok(current_virtual_path.to_string());
     }
 
-    // Handle special case of ".." when already at root
-    if requested == ".." && current_virtual_path == "/" {
-        return Ok("/".to_string());
-    }
-
-    // Handle absolute paths
-    if requested.starts_with('/') || requested.starts_with('\\') {
-        return validate_path(requested, config);
-    }
-
-    // Handle relative paths
-    let combined = if current_virtual_path.ends_with('/') {
-        format!("{current_virtual_path}{requested}")
-    } else {
-        format!("{current_virtual_path}/{requested}")
-    };
-
-    validate_path(&combined, config)
+    validate_path(
+        &join_virtual_path(current_virtual_path, requested),
+        max_depth,
+    )
 }
 
 /// Convert virtual path to real filesystem path within server_root
@@ -182,7 +206,13 @@ pub fn virtual_to_real_path(server_root: &Path, virtual_path: &str) -> PathBuf {
 }
 
 /// Verify real path is within server_root bounds (security check)
+///
+/// Besides checking where the whole path resolves to, every existing
+/// component is resolved in turn, so a symlink anywhere along the way is
+/// judged by its own target (see `verify_components_within_bounds`).
 pub fn verify_path_within_bounds(server_root: &Path, real_path: &Path) -> Result<(), String> {
+    verify_components_within_bounds(server_root, real_path)?;
+
     match real_path.canonicalize() {
         Ok(canonical_real) => {
             match server_root.canonicalize() {
@@ -199,15 +229,55 @@ pub fn verify_path_within_bounds(server_root: &Path, real_path: &Path) -> Result
         }
         Err(_) => {
             // Path doesn't exist yet, check parent directory
-            if let Some(parent) = real_path.parent() {
-                if let Ok(canonical_parent) = parent.canonicalize() {
-                    if let Ok(canonical_root) = server_root.canonicalize() {
-                        if !canonical_parent.starts_with(canonical_root) {
-                            return Err("Path outside server root".to_string());
-                        }
-                    }
+            if let Some(parent) = real_path.parent()
+                && let Ok(canonical_parent) = parent.canonicalize()
+                && let Ok(canonical_root) = server_root.canonicalize()
+                && !canonical_parent.starts_with(canonical_root)
+            {
+                return Err("Path outside server root".to_string());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve a path one component at a time, failing on the first one that leads
+/// outside server_root
+///
+/// Canonicalizing only the final path checks wherever the symlinks pointed at
+/// that moment; walking the components progressively means each symlink on the
+/// way is checked against the root itself, and resolution stops at the first
+/// escape instead of following it further.
+fn verify_components_within_bounds(server_root: &Path, real_path: &Path) -> Result<(), String> {
+    let Ok(canonical_root) = server_root.canonicalize() else {
+        // Reported by the caller's own canonicalization check
+        return Ok(());
+    };
+    let relative = real_path
+        .strip_prefix(server_root)
+        .map_err(|_| "Path outside server root".to_string())?;
+
+    let mut resolved = canonical_root.clone();
+    for component in relative.components() {
+        let Component::Normal(name) = component else {
+            return Err("Path outside server root".to_string());
+        };
+
+        let candidate = resolved.join(name);
+        match fs::symlink_metadata(&candidate) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = candidate
+                    .canonicalize()
+                    .map_err(|_| "Path contains an unresolvable symlink".to_string())?;
+                if !target.starts_with(&canonical_root) {
+                    return Err("Path outside server root".to_string());
                 }
+                resolved = target;
             }
+            Ok(_) => resolved = candidate,
+            // Nothing exists from here on, so nothing further can lead out
+            Err(_) => break,
         }
     }
 
@@ -219,16 +289,108 @@ pub fn resolve_and_validate_file_path(
     server_root: &Path,
     current_virtual_path: &str,
     file_path: &str,
-    config: &StartupConfig,
+    max_depth: usize,
 ) -> Result<(PathBuf, String), String> {
-    // Resolve virtual file path
-    let virtual_file_path = resolve_file_path(current_virtual_path, file_path, config)?;
+    StorageRoot::new(server_root).resolve_file_path(current_virtual_path, file_path, max_depth)
+}
+
+/// Retries of a transient failure for a root built without a config
+const DEFAULT_MAX_RETRIES: usize = 2;
+
+/// The server root, plus any physical directories mounted onto it
+///
+/// A mount maps a top-level virtual directory such as `/public` to its own
+/// physical directory. Paths beneath it resolve there and must stay within it.
+/// Operations on the root retry transient failures up to `max_retries` times.
+#[derive(Debug, Clone)]
+pub struct StorageRoot {
+    server_root: PathBuf,
+    mounts: HashMap<String, PathBuf>,
+    max_retries: usize,
+}
+
+impl StorageRoot {
+    /// A storage root without mount points
+    pub fn new(server_root: &Path) -> Self {
+        Self {
+            server_root: server_root.to_path_buf(),
+            mounts: HashMap::new(),
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+
+    /// Adds mount points, keyed by their virtual path (`/public`)
+    pub fn with_mounts<'a>(
+        mut self,
+        mounts: impl IntoIterator<Item = (&'a str, &'a Path)>,
+    ) -> Self {
+        for (virtual_path, real_path) in mounts {
+            let name = virtual_path.trim_matches('/').to_string();
+            self.mounts.insert(name, real_path.to_path_buf());
+        }
+        self
+    }
+
+    /// Sets how often operations on this root retry a transient failure
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// How often operations on this root retry a transient failure
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
+    }
+
+    /// Names of the mount points, sorted, as they appear in the root directory
+    pub fn mount_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.mounts.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
 
-    // Convert to real path
-    let real_path = virtual_to_real_path(server_root, &virtual_file_path);
+    /// The server root followed by every mounted directory
+    pub fn physical_roots(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.server_root.as_path())
+            .chain(self.mounts.values().map(PathBuf::as_path))
+    }
 
-    // Verify security bounds
-    verify_path_within_bounds(server_root, &real_path)?;
+    /// Whether a normalized virtual path is a mount point itself
+    pub fn is_mount_point(&self, virtual_path: &str) -> bool {
+        self.mounts
+            .contains_key(virtual_path.trim_start_matches('/'))
+    }
 
-    Ok((real_path, virtual_file_path))
+    /// Converts a normalized virtual path to a real path, along with the
+    /// physical root it has to stay within
+    pub fn real_path(&self, virtual_path: &str) -> (PathBuf, &Path) {
+        let relative = virtual_path.trim_start_matches('/');
+        let (first, rest) = relative.split_once('/').unwrap_or((relative, ""));
+        match self.mounts.get(first) {
+            Some(mount) => (virtual_to_real_path(mount, rest), mount),
+            None => (
+                virtual_to_real_path(&self.server_root, virtual_path),
+                &self.server_root,
+            ),
+        }
+    }
+
+    /// Converts a normalized virtual path to a real path and verifies its bounds
+    pub fn checked_real_path(&self, virtual_path: &str) -> Result<PathBuf, String> {
+        let (real_path, bounds) = self.real_path(virtual_path);
+        verify_path_within_bounds(bounds, &real_path)?;
+        Ok(real_path)
+    }
+
+    /// Complete file path resolution with all security checks
+    pub fn resolve_file_path(
+        &self,
+        current_virtual_path: &str,
+        file_path: &str,
+        max_depth: usize,
+    ) -> Result<(PathBuf, String), String> {
+        let virtual_file_path = resolve_file_path(current_virtual_path, file_path, max_depth)?;
+        let real_path = self.checked_real_path(&virtual_file_path)?;
+        Ok((real_path, virtual_file_path))
+    }
 }
@@ -0,0 +1,49 @@
+//! Wildcard matching for LIST and NLST
+//!
+//! Only the last component of a listing argument may be a pattern, using `*`
+//! for any run of characters and `?` for a single one. There are no character
+//! classes or escapes, since glob dialects differ between clients.
+
+/// Splits a listing argument into its directory and a trailing wildcard pattern
+///
+/// Returns `None` when the last component has no `*` or `?`, in which case the
+/// argument is an ordinary path.
+pub fn split_pattern(path: &str) -> Option<(&str, &str)> {
+    let (directory, last) = match path.rfind('/') {
+        Some(0) => ("/", &path[1..]),
+        Some(index) => (&path[..index], &path[index + 1..]),
+        None => ("", path),
+    };
+    last.contains(['*', '?']).then_some((directory, last))
+}
+
+/// Reports whether `name` matches `pattern`
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Greedy match that backtracks to the most recent `*`
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
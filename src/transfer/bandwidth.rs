@@ -0,0 +1,108 @@
+//! Per-user bandwidth limiting
+//!
+//! Each capped user gets one token bucket per direction, shared by all of
+//! their transfers, so the configured rate applies to the user as a whole
+//! rather than to each data connection.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::StartupConfig;
+
+/// Direction of a transfer, as seen from the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+/// Token bucket pacing transfers to a fixed number of bytes per second.
+///
+/// The bucket holds at most one second's worth of bytes. Transfers may
+/// overdraw it; the debt is paid off by sleeping before the next chunk.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    bytes_per_sec: u64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl BandwidthLimiter {
+    /// Creates a limiter that starts with a full bucket.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            state: Mutex::new(BucketState {
+                bytes_per_sec,
+                tokens: bytes_per_sec as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Charges `bytes` to the bucket and returns how long to wait before continuing.
+    pub fn reserve(&self, bytes: usize) -> Duration {
+        let mut state = self.lock();
+        let rate = state.bytes_per_sec as f64;
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * rate).min(rate);
+        state.refilled_at = now;
+
+        state.tokens -= bytes as f64;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / rate)
+        }
+    }
+
+    /// Charges `bytes` to the bucket, sleeping if the rate has been exceeded.
+    pub fn throttle(&self, bytes: usize) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    fn set_rate(&self, bytes_per_sec: u64) {
+        let mut state = self.lock();
+        if state.bytes_per_sec != bytes_per_sec {
+            state.bytes_per_sec = bytes_per_sec;
+            state.tokens = state.tokens.min(bytes_per_sec as f64);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BucketState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Limiters keyed by username and direction
+type LimiterMap = HashMap<(String, TransferDirection), Arc<BandwidthLimiter>>;
+
+/// Limiters shared by every session of the same user
+static USER_LIMITERS: LazyLock<Mutex<LimiterMap>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Returns the shared limiter for a user's transfers in `direction`, or `None` if uncapped
+pub fn user_limiter(
+    startup_config: &StartupConfig,
+    username: &str,
+    direction: TransferDirection,
+) -> Option<Arc<BandwidthLimiter>> {
+    let bytes_per_sec = startup_config.bandwidth_limit(username, direction)?;
+
+    let mut limiters = USER_LIMITERS.lock().unwrap_or_else(|e| e.into_inner());
+    let limiter = limiters
+        .entry((username.to_string(), direction))
+        .or_insert_with(|| Arc::new(BandwidthLimiter::new(bytes_per_sec)));
+    limiter.set_rate(bytes_per_sec);
+    Some(Arc::clone(limiter))
+}
@@ -0,0 +1,48 @@
+//! Cancellation of in-progress transfers
+//!
+//! Transfers run on blocking sockets, so they can't notice on their own that
+//! the client has gone or asked them to stop. The task reading the control
+//! connection cancels the session's token when it sees EOF or an error, or
+//! aborts it when ABOR arrives during a transfer, and the transfer loops
+//! check it between chunks.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How often a transfer blocked on an idle data connection checks for cancellation
+pub const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shared flags set when a session's control connection is lost or ABOR is sent
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<CancelFlags>);
+
+#[derive(Debug, Default)]
+struct CancelFlags {
+    /// The control connection is gone; never cleared
+    lost: AtomicBool,
+    /// ABOR was sent; cleared once the ABOR has been answered
+    aborted: AtomicBool,
+}
+
+impl CancelToken {
+    /// Marks every transfer holding this token as cancelled, for good
+    pub fn cancel(&self) {
+        self.0.lost.store(true, Ordering::Relaxed);
+    }
+
+    /// Cancels the transfer in progress until `clear_abort` is called
+    pub fn abort(&self) {
+        self.0.aborted.store(true, Ordering::Relaxed);
+    }
+
+    /// Lets later transfers run again after an ABOR
+    pub fn clear_abort(&self) {
+        self.0.aborted.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the token has been cancelled or aborted
+    pub fn is_cancelled(&self) -> bool {
+        self.0.lost.load(Ordering::Relaxed) || self.0.aborted.load(Ordering::Relaxed)
+    }
+}
@@ -9,6 +9,7 @@
 use log::warn;
 use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
 
 /// Represents the state of a single FTP data channel associated with a client.
 /// Contains optional references to the client's data socket address,
@@ -19,6 +20,7 @@ pub struct ChannelEntry {
     data_stream: Option<TcpStream>,  // Established TCP stream for the data transfer
     listener: Option<TcpListener>,   // Listener socket for passive mode connections
     owner_ip: Option<IpAddr>,        // IP address of the client that owns this channel
+    listener_used: Option<Instant>,  // When the listener was created or last handed to a transfer
 }
 
 impl ChannelEntry {
@@ -53,9 +55,17 @@ impl ChannelEntry {
 
     /// Sets the passive mode TCP listener, replacing any existing value.
     pub fn set_listener(&mut self, listener: Option<TcpListener>) {
+        self.listener_used = listener.as_ref().map(|_| Instant::now());
         self.listener = listener;
     }
 
+    /// Records that the passive mode listener was just handed to a transfer.
+    pub fn touch_listener(&mut self) {
+        if self.listener.is_some() {
+            self.listener_used = Some(Instant::now());
+        }
+    }
+
     /// Sets the owner IP address for this channel.
     pub fn set_owner_ip(&mut self, ip: Option<IpAddr>) {
         self.owner_ip = ip;
@@ -72,6 +82,7 @@ impl ChannelEntry {
     pub fn cleanup_all(&mut self) {
         self.cleanup_stream_only();
         self.listener = None;
+        self.listener_used = None;
         self.data_socket = None;
         self.owner_ip = None;
     }
@@ -89,11 +100,11 @@ impl ChannelRegistry {
     ///
     /// If the provided data socket is already in use by another client, it logs a warning and skips insertion.
     pub fn insert(&mut self, addr: SocketAddr, entry: ChannelEntry) {
-        if let Some(socket) = entry.data_socket {
-            if self.is_socket_taken(&socket) {
-                warn!("Attempted to insert a data socket already in use: {socket}");
-                return;
-            }
+        if let Some(socket) = entry.data_socket
+            && self.is_socket_taken(&socket)
+        {
+            warn!("Attempted to insert a data socket already in use: {socket}");
+            return;
         }
         self.registry.insert(addr, entry);
     }
@@ -103,6 +114,11 @@ impl ChannelRegistry {
         self.registry.remove(addr)
     }
 
+    /// Returns the data channel entry for a client address, if present.
+    pub fn get(&self, addr: &SocketAddr) -> Option<&ChannelEntry> {
+        self.registry.get(addr)
+    }
+
     /// Returns a mutable reference to the data channel entry for a client address, if present.
     pub fn get_mut(&mut self, addr: &SocketAddr) -> Option<&mut ChannelEntry> {
         self.registry.get_mut(addr)
@@ -113,20 +129,16 @@ impl ChannelRegistry {
         self.registry.contains_key(addr)
     }
 
-    /// Attempts to find the next available socket address in the configured PASV port range
-    /// that is not currently assigned to any client's data socket.
-    pub fn next_available_socket(
-        &self,
-        bind_address: &str,
+    /// Returns the socket addresses in the configured PASV port range that are
+    /// not currently assigned to any client's data socket, in port order.
+    pub fn available_sockets<'a>(
+        &'a self,
+        bind_address: &'a str,
         port_range: std::ops::Range<u16>,
-    ) -> Option<SocketAddr> {
-        for port in port_range {
-            let data_socket: SocketAddr = format!("{bind_address}:{port}").parse().unwrap();
-            if !self.is_socket_taken(&data_socket) {
-                return Some(data_socket);
-            }
-        }
-        None
+    ) -> impl Iterator<Item = SocketAddr> + 'a {
+        port_range
+            .map(move |port| format!("{bind_address}:{port}").parse().unwrap())
+            .filter(|data_socket| !self.is_socket_taken(data_socket))
     }
 
     /// Checks if the given socket address is already assigned as a data socket for any client.
@@ -136,6 +148,34 @@ impl ChannelRegistry {
             .any(|entry| entry.data_socket.as_ref() == Some(addr))
     }
 
+    /// Returns the number of passive mode listeners currently held by the registry.
+    pub fn passive_listener_count(&self) -> usize {
+        self.registry
+            .values()
+            .filter(|entry| entry.listener.is_some())
+            .count()
+    }
+
+    /// Drops every passive mode listener that has gone unused for at least
+    /// `idle`, such as one from a client that sent PASV and never connected.
+    /// Returns the clients whose data channel was reaped.
+    pub fn reap_idle_listeners(&mut self, idle: Duration) -> Vec<SocketAddr> {
+        let reaped: Vec<SocketAddr> = self
+            .registry
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .listener_used
+                    .is_some_and(|used| used.elapsed() >= idle)
+            })
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in &reaped {
+            self.cleanup_all(addr);
+        }
+        reaped
+    }
+
     /// Completely cleans up all data channel resources for a client.
     pub fn cleanup_all(&mut self, client_addr: &SocketAddr) {
         if let Some(mut entry) = self.remove(client_addr) {
@@ -0,0 +1,127 @@
+//! MODE Z compression
+//!
+//! In MODE Z the data connection carries a zlib (deflate) stream instead of
+//! the raw bytes. Data is compressed and decompressed chunk by chunk, after
+//! the TYPE translation on the way out and before it on the way in.
+//!
+//! Without the `mode-z` feature MODE Z is refused, so these codecs are never
+//! constructed; their stand-ins pass data through unchanged so the transfer
+//! code needs no feature gates of its own.
+
+use std::io;
+
+/// Transfer mode negotiated with the MODE command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransferMode {
+    /// MODE S: bytes are sent as they are
+    #[default]
+    Stream,
+    /// MODE Z: bytes are sent as one zlib stream
+    Deflate,
+}
+
+#[cfg(feature = "mode-z")]
+mod codec {
+    use std::io::{self, Write};
+
+    use flate2::Compression;
+    use flate2::write::{ZlibDecoder, ZlibEncoder};
+
+    /// Compresses a MODE Z download.
+    pub struct Deflater(ZlibEncoder<Vec<u8>>);
+
+    impl Default for Deflater {
+        fn default() -> Self {
+            Self(ZlibEncoder::new(Vec::new(), Compression::default()))
+        }
+    }
+
+    impl Deflater {
+        /// Appends whatever compressed output `input` produced to `output`.
+        pub fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+            self.0.write_all(input)?;
+            output.append(self.0.get_mut());
+            Ok(())
+        }
+
+        /// Appends the end of the compressed stream to `output`.
+        pub fn finish(self, output: &mut Vec<u8>) -> io::Result<()> {
+            output.append(&mut self.0.finish()?);
+            Ok(())
+        }
+    }
+
+    /// Decompresses a MODE Z upload.
+    pub struct Inflater(ZlibDecoder<Vec<u8>>);
+
+    impl Default for Inflater {
+        fn default() -> Self {
+            Self(ZlibDecoder::new(Vec::new()))
+        }
+    }
+
+    impl Inflater {
+        /// Appends whatever `input` decompressed to to `output`.
+        pub fn decompress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+            self.0.write_all(input)?;
+            output.append(self.0.get_mut());
+            Ok(())
+        }
+
+        /// Appends the rest of the decompressed data to `output`.
+        pub fn finish(self, output: &mut Vec<u8>) -> io::Result<()> {
+            output.append(&mut self.0.finish()?);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "mode-z"))]
+mod codec {
+    use std::io;
+
+    /// Stand-in for the MODE Z compressor, which needs the `mode-z` feature.
+    #[derive(Default)]
+    pub struct Deflater {
+        _private: (),
+    }
+
+    impl Deflater {
+        pub fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+            output.extend_from_slice(input);
+            Ok(())
+        }
+
+        pub fn finish(self, _output: &mut Vec<u8>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Stand-in for the MODE Z decompressor, which needs the `mode-z` feature.
+    #[derive(Default)]
+    pub struct Inflater {
+        _private: (),
+    }
+
+    impl Inflater {
+        pub fn decompress(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+            output.extend_from_slice(input);
+            Ok(())
+        }
+
+        pub fn finish(self, _output: &mut Vec<u8>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+pub use codec::{Deflater, Inflater};
+
+/// Compresses a complete buffer, such as a directory listing, in one go.
+pub fn deflate(input: &[u8]) -> io::Result<Vec<u8>> {
+    let mut deflater = Deflater::default();
+    let mut output = Vec::new();
+    deflater.compress(input, &mut output)?;
+    deflater.finish(&mut output)?;
+    Ok(output)
+}
@@ -2,63 +2,139 @@
 //!
 //! Manages data connections for file transfers in FTP server.
 
-use log::{error, info};
+use log::{error, info, warn};
+use socket2::{Domain, Protocol, Socket, Type};
 use std::io::Write;
-use std::net::{SocketAddr, TcpStream};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::client::Client;
-use crate::config::{SharedRuntimeConfig, StartupConfig};
+use crate::config::StartupConfig;
 use crate::error::TransferError;
-use crate::transfer::ChannelRegistry;
+use crate::protocol::encode_for_client;
+use crate::transfer::compression::{self, TransferMode};
+use crate::transfer::{CancelToken, ChannelRegistry, UploadOptions};
 
-/// Validates client authentication and data channel initialization
-pub fn validate_client_and_data_channel(client: &Client) -> bool {
-    client.is_logged_in() && client.is_data_channel_init()
+/// How often a pending passive accept checks for a connection
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A data connection the client has asked for but that is not open yet.
+///
+/// Taken from the registry while the session holds it, then opened by the
+/// transfer without it, since accepting or connecting can take a while.
+pub enum DataConnection {
+    /// Active mode: the server connects to the client
+    Active(SocketAddr),
+    /// Passive mode: the client connects to the server's listener
+    Passive {
+        listener: TcpListener,
+        /// The only peer accepted, unless FXP is allowed
+        expected_peer: Option<IpAddr>,
+    },
 }
 
-/// Sets up a data connection for the given client
-pub fn setup_data_stream(
-    channel_registry: &mut ChannelRegistry,
-    client_addr: &SocketAddr,
-    config: &StartupConfig,
-) -> Option<TcpStream> {
-    let entry = channel_registry.get_mut(client_addr)?;
-
-    // Check if this is active mode (has data_socket but no listener)
-    if let Some(data_socket) = entry.data_socket() {
-        if entry.listener().is_none() {
-            // Active mode: Server connects to client
-            info!("Active mode: Server connecting to client at {data_socket}");
-            return connect_to_client(*data_socket, config);
+impl DataConnection {
+    /// Takes the data connection set up by the client's last PASV or PORT.
+    ///
+    /// The registry keeps its entry, so the next transfer can use it too.
+    /// A passive listener counts as used, which keeps it from being reaped.
+    pub fn take(
+        channel_registry: &mut ChannelRegistry,
+        client_addr: &SocketAddr,
+        config: &StartupConfig,
+    ) -> Option<Self> {
+        let Some(entry) = channel_registry.get_mut(client_addr) else {
+            error!("No data channel setup found for client {client_addr}");
+            return None;
+        };
+        entry.touch_listener();
+
+        // Passive mode has a listener; active mode only the client's address
+        match (entry.listener(), entry.data_socket()) {
+            (Some(listener), _) => match listener.try_clone() {
+                Ok(listener) => Some(DataConnection::Passive {
+                    listener,
+                    expected_peer: (!config.allow_fxp).then(|| client_addr.ip()),
+                }),
+                Err(e) => {
+                    error!("Failed to clone passive listener for client {client_addr}: {e}");
+                    None
+                }
+            },
+            (None, Some(data_socket)) => Some(DataConnection::Active(*data_socket)),
+            (None, None) => {
+                error!("No data channel setup found for client {client_addr}");
+                None
+            }
         }
     }
 
-    // Passive mode: Accept connection from client
-    if let Some(listener) = entry.listener_mut() {
-        info!("Passive mode: Accepting connection from client");
-        return accept_from_client(listener);
+    /// Opens the connection, blocking until it is established, it times out or
+    /// `cancel` is cancelled.
+    pub fn open(self, config: &StartupConfig, cancel: &CancelToken) -> Option<TcpStream> {
+        match self {
+            DataConnection::Active(data_socket) => {
+                info!("Active mode: Server connecting to client at {data_socket}");
+                connect_to_client(data_socket, config)
+            }
+            DataConnection::Passive {
+                listener,
+                expected_peer,
+            } => {
+                info!("Passive mode: Accepting connection from client");
+                accept_from_client(
+                    &listener,
+                    expected_peer,
+                    config.connection_timeout(),
+                    cancel,
+                )
+            }
+        }
     }
-
-    error!("No data channel setup found for client {client_addr}");
-    None
 }
 
 /// Sends directory listing over data connection
+///
+/// Listings are always sent as ASCII with CRLF line endings, independent of the
+/// session TYPE, which only applies to file transfers. MODE Z does apply to them.
+/// With `latin1` names are sent as ISO-8859-1, matching how commands are decoded.
+///
+/// A listing longer than `max_listing_entries`, which the storage layer stops
+/// reading one entry past, is cut off after that many entries and ends with a
+/// notice line instead. Returns whether it was.
 pub fn send_directory_listing(
-    channel_registry: &mut ChannelRegistry,
+    connection: DataConnection,
     client_addr: &SocketAddr,
-    listing: Vec<String>,
+    mut listing: Vec<String>,
+    mode: TransferMode,
+    latin1: bool,
     config: &StartupConfig,
-) -> Result<(), TransferError> {
-    let mut data_stream =
-        setup_data_stream(channel_registry, client_addr, config).ok_or_else(|| {
-            TransferError::DataChannelSetupFailed("Failed to establish data connection".into())
-        })?;
+    cancel: &CancelToken,
+) -> Result<bool, TransferError> {
+    let mut data_stream = connection.open(config, cancel).ok_or_else(|| {
+        TransferError::DataChannelSetupFailed("Failed to establish data connection".into())
+    })?;
+    let mut truncated = false;
+    if let Some(max) = config.max_listing_entries
+        && listing.len() > max
+    {
+        warn!("Listing for client {client_addr} truncated after {max} entries");
+        listing.truncate(max);
+        listing.push(format!("Listing truncated after {max} entries"));
+        truncated = true;
+    }
 
-    let listing_data = listing.join("\r\n") + "\r\n";
+    let listing_data: String = listing.iter().map(|line| format!("{line}\r\n")).collect();
+    let listing_data = encode_for_client(&listing_data, latin1);
+    let listing_data = match mode {
+        TransferMode::Stream => listing_data.into_owned(),
+        TransferMode::Deflate => {
+            compression::deflate(&listing_data).map_err(TransferError::TransferFailed)?
+        }
+    };
 
     data_stream
-        .write_all(listing_data.as_bytes())
+        .write_all(&listing_data)
         .map_err(TransferError::TransferFailed)?;
 
     data_stream.flush().map_err(TransferError::TransferFailed)?;
@@ -66,20 +142,22 @@ pub fn send_directory_listing(
     let _ = data_stream.shutdown(std::net::Shutdown::Both);
 
     info!("Directory listing sent successfully to client {client_addr}");
-    Ok(())
+    Ok(truncated)
 }
 
 /// Receives file upload over data connection
-pub async fn receive_file_upload(
-    channel_registry: &mut ChannelRegistry,
+pub fn receive_file_upload(
+    connection: DataConnection,
     client_addr: &SocketAddr,
     final_filename: &str,
     temp_filename: &str,
+    options: UploadOptions,
     startup_config: &StartupConfig,
-    runtime_config: &SharedRuntimeConfig,
-) -> Result<(), TransferError> {
-    let data_stream =
-        setup_data_stream(channel_registry, client_addr, startup_config).ok_or_else(|| {
+    max_file_size: u64,
+) -> Result<u64, TransferError> {
+    let data_stream = connection
+        .open(startup_config, &options.cancel)
+        .ok_or_else(|| {
             TransferError::DataChannelSetupFailed("Failed to establish data connection".into())
         })?;
 
@@ -87,14 +165,13 @@ pub async fn receive_file_upload(
         data_stream,
         final_filename,
         temp_filename,
+        options,
         startup_config,
-        runtime_config,
-    )
-    .await
-    {
-        Ok(_) => {
+        max_file_size,
+    ) {
+        Ok(bytes) => {
             info!("File upload completed successfully to {client_addr}");
-            Ok(())
+            Ok(bytes)
         }
         Err((_, msg)) => {
             error!("File upload failed for {client_addr}: {msg}");
@@ -105,7 +182,12 @@ pub async fn receive_file_upload(
 
 /// Active mode: Server connects to client
 fn connect_to_client(data_socket: SocketAddr, config: &StartupConfig) -> Option<TcpStream> {
-    match TcpStream::connect_timeout(&data_socket, config.connection_timeout()) {
+    let result = match config.active_source_port {
+        Some(port) => connect_from_port(data_socket, port, config),
+        None => TcpStream::connect_timeout(&data_socket, config.connection_timeout()),
+    };
+
+    match result {
         Ok(stream) => {
             info!("Connected to client at {data_socket}");
             Some(stream)
@@ -117,25 +199,81 @@ fn connect_to_client(data_socket: SocketAddr, config: &StartupConfig) -> Option<
     }
 }
 
+/// Opens an active-mode connection from a fixed local port.
+///
+/// SO_REUSEADDR lets several clients' data connections share the port, since
+/// each still has a distinct remote address.
+fn connect_from_port(
+    data_socket: SocketAddr,
+    local_port: u16,
+    config: &StartupConfig,
+) -> std::io::Result<TcpStream> {
+    let local_ip: IpAddr = config.bind_address.parse().map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("bind_address {} is not an IP address", config.bind_address),
+        )
+    })?;
+
+    let socket = Socket::new(
+        Domain::for_address(data_socket),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::new(local_ip, local_port).into())?;
+    socket.connect_timeout(&data_socket.into(), config.connection_timeout())?;
+    Ok(socket.into())
+}
+
 /// Passive mode: Accept connection from client
-fn accept_from_client(listener: &mut std::net::TcpListener) -> Option<TcpStream> {
-    // Set to blocking mode for accept
-    if let Err(e) = listener.set_nonblocking(false) {
-        error!("Failed to set listener to blocking mode: {e}");
-        return None;
-    }
+///
+/// Polls the non-blocking listener until a client connects, `timeout`
+/// elapses or `cancel` is cancelled, so a client that sends PASV and RETR but
+/// never opens the data connection can ABOR the transfer or is given up on.
+/// When `expected_peer` is set, a connection from any other address is
+/// refused to prevent site-to-site (FXP) transfers.
+fn accept_from_client(
+    listener: &TcpListener,
+    expected_peer: Option<IpAddr>,
+    timeout: Duration,
+    cancel: &CancelToken,
+) -> Option<TcpStream> {
+    let deadline = Instant::now() + timeout;
 
-    match listener.accept() {
-        Ok((stream, peer_addr)) => {
-            info!("Accepted connection from {peer_addr}");
-            // Reset to non-blocking for next time
-            let _ = listener.set_nonblocking(true);
-            Some(stream)
+    loop {
+        if cancel.is_cancelled() {
+            warn!("Transfer cancelled while waiting for passive data connection");
+            return None;
         }
-        Err(e) => {
-            error!("Failed to accept connection: {e}");
-            let _ = listener.set_nonblocking(true);
-            None
+        match listener.accept() {
+            Ok((stream, peer_addr)) => {
+                if let Some(expected) = expected_peer
+                    && peer_addr.ip() != expected
+                {
+                    warn!("Rejected data connection from {peer_addr}: expected peer {expected}");
+                    let _ = stream.shutdown(std::net::Shutdown::Both);
+                    return None;
+                }
+                info!("Accepted connection from {peer_addr}");
+                // Accepted sockets may inherit non-blocking mode from the listener
+                if let Err(e) = stream.set_nonblocking(false) {
+                    error!("Failed to set data connection to blocking mode: {e}");
+                    return None;
+                }
+                return Some(stream);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    warn!("Timed out after {timeout:?} waiting for passive data connection");
+                    return None;
+                }
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+            Err(e) => {
+                error!("Failed to accept connection: {e}");
+                return None;
+            }
         }
     }
 }
@@ -5,26 +5,74 @@
 //! TCP data streams, managing errors and reporting FTP-compliant
 //! status codes and messages.
 
-use crate::config::{SharedRuntimeConfig, StartupConfig};
-use crate::protocol::CommandStatus;
+use crate::config::StartupConfig;
+use crate::protocol::{CommandStatus, responses};
+use crate::storage::permissions::{FILE_MODE, apply_umask};
+use crate::storage::retry::{RETRY_DELAY, TRANSIENT_ERRORS, TRANSIENT_READ_ERRORS, with_retries};
+use crate::transfer::BandwidthLimiter;
+use crate::transfer::cancel::{CANCEL_POLL_INTERVAL, CancelToken};
+use crate::transfer::compression::{Deflater, Inflater, TransferMode};
+use crate::transfer::modes::{AsciiDecoder, AsciiEncoder, TransferType};
 use log::{error, info, warn};
-use std::fs::{File, remove_file, rename};
-use std::io::{Read, Write};
+use std::fs::{File, copy, remove_file, rename};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+
+/// Per-upload settings chosen by the session before STOR.
+#[derive(Debug, Clone, Default)]
+pub struct UploadOptions {
+    /// Representation of the incoming data
+    pub transfer_type: TransferType,
+    /// Whether the incoming data is MODE Z compressed
+    pub mode: TransferMode,
+    /// Modification time to give the committed file, declared by MFMT
+    pub modified: Option<SystemTime>,
+    /// Bandwidth cap shared by the user's uploads
+    pub limiter: Option<Arc<BandwidthLimiter>>,
+    /// Permission bits to clear on the committed file
+    pub umask: Option<u32>,
+    /// Aborts the upload when the control connection is lost or ABOR is sent
+    pub cancel: CancelToken,
+}
+
+/// Per-download settings chosen by the session before RETR.
+#[derive(Debug, Clone, Default)]
+pub struct DownloadOptions {
+    /// Representation of the outgoing data
+    pub transfer_type: TransferType,
+    /// Whether to MODE Z compress the outgoing data
+    pub mode: TransferMode,
+    /// Byte of the file to start from, set by REST
+    pub offset: u64,
+    /// Bandwidth cap shared by the user's downloads
+    pub limiter: Option<Arc<BandwidthLimiter>>,
+    /// Aborts the download when the control connection is lost or ABOR is sent
+    pub cancel: CancelToken,
+}
 
 /// Handles uploading a file from the client to the server using temporary files.
 ///
 /// This function implements atomic file uploads by writing to a temporary file first,
 /// then renaming it to the final destination on successful completion.
-pub async fn handle_file_upload(
+/// Returns the number of bytes received over the data connection.
+/// ASCII uploads have their CRLF line endings converted to LF before being written,
+/// and MODE Z uploads are decompressed before that.
+/// A modification time in `options` is applied to the final file once it is in place.
+/// Uploads larger than `max_file_size` are refused with 552.
+/// The upload is aborted and its temporary file removed if `options.cancel` is
+/// cancelled, even while the data connection sits idle.
+pub fn handle_file_upload(
     mut data_stream: TcpStream,
     final_filename: &str,
     temp_filename: &str,
+    options: UploadOptions,
     config: &StartupConfig,
-    runtime_config: &SharedRuntimeConfig,
-) -> Result<(CommandStatus, &'static str), (CommandStatus, &'static str)> {
+    max_file_size: u64,
+) -> Result<u64, (CommandStatus, &'static str)> {
     info!("Starting file upload: {temp_filename} -> {final_filename}");
 
     // Create temporary file for atomic upload
@@ -41,12 +89,16 @@ pub async fn handle_file_upload(
 
     let mut buffer = vec![0; config.buffer_size];
     let mut total_bytes_received = 0u64;
+    let mut total_bytes_stored = 0u64;
+    let mut decoder = AsciiDecoder::default();
+    let mut decoded = Vec::new();
+    let mut inflater = (options.mode == TransferMode::Deflate).then(Inflater::default);
+    let mut inflated = Vec::new();
 
-    // Get max file size from runtime config (since it can be updated at runtime)
-    let max_file_size = {
-        let runtime = runtime_config.read().await;
-        runtime.max_file_size_bytes()
-    };
+    // Wake up periodically so a lost control connection is noticed
+    if let Err(e) = data_stream.set_read_timeout(Some(CANCEL_POLL_INTERVAL)) {
+        warn!("Failed to set read timeout on data connection: {e}");
+    }
 
     // Send initial response indicating data transfer is starting
     info!("Ready to receive data for {final_filename}");
@@ -54,10 +106,17 @@ pub async fn handle_file_upload(
     loop {
         let mut retries = 0;
         let n = loop {
+            if options.cancel.is_cancelled() {
+                warn!("Transfer cancelled, aborting upload of {final_filename}");
+                let _ = remove_file(temp_filename);
+                return Err(cancelled());
+            }
             match data_stream.read(&mut buffer) {
                 Ok(0) => break 0, // EOF - upload complete
                 Ok(n) => break n,
-                Err(e) if retries < config.max_retries => {
+                // The poll interval elapsed without data; not a failed attempt
+                Err(e) if is_poll_timeout(&e) => continue,
+                Err(e) if is_transient(&e) && retries < config.max_retries => {
                     warn!(
                         "Transient read error (attempt {}/{}): {}. Retrying...",
                         retries + 1,
@@ -68,7 +127,7 @@ pub async fn handle_file_upload(
                     thread::sleep(Duration::from_millis(100 * retries as u64));
                 }
                 Err(e) => {
-                    error!("Read failure after {} retries: {e}", config.max_retries);
+                    error!("Read failure after {retries} retries: {e}");
                     // Clean up temporary file
                     let _ = remove_file(temp_filename);
                     return Err((
@@ -83,6 +142,10 @@ pub async fn handle_file_upload(
             break; // End of file reached
         }
 
+        if let Some(limiter) = &options.limiter {
+            limiter.throttle(n);
+        }
+
         // Check file size limit BEFORE writing (fail fast)
         total_bytes_received += n as u64;
         if total_bytes_received > max_file_size {
@@ -97,17 +160,60 @@ pub async fn handle_file_upload(
             ));
         }
 
+        // Undo MODE Z compression first, then the TYPE translation
+        let received = match inflater.as_mut() {
+            Some(inflater) => {
+                inflated.clear();
+                if let Err(e) = inflater.decompress(&buffer[..n], &mut inflated) {
+                    error!("Invalid compressed data in upload of {final_filename}: {e}");
+                    let _ = remove_file(temp_filename);
+                    return Err(invalid_compressed_data());
+                }
+                &inflated[..]
+            }
+            None => &buffer[..n],
+        };
+
         // Write chunk to temporary file
-        if let Err(e) = temp_file.write_all(&buffer[..n]) {
-            error!("Failed to write to temporary file {temp_filename}: {e}");
-            // Clean up temporary file
+        let chunk = match options.transfer_type {
+            TransferType::Binary => received,
+            TransferType::Ascii => {
+                decoded.clear();
+                decoder.decode(received, &mut decoded);
+                &decoded[..]
+            }
+        };
+        write_chunk(
+            &mut temp_file,
+            chunk,
+            &mut total_bytes_stored,
+            max_file_size,
+            temp_filename,
+        )?;
+    }
+
+    // Write out what the decompressor and ASCII decoder still hold back
+    decoded.clear();
+    if let Some(inflater) = inflater.take() {
+        inflated.clear();
+        if let Err(e) = inflater.finish(&mut inflated) {
+            error!("Invalid compressed data in upload of {final_filename}: {e}");
             let _ = remove_file(temp_filename);
-            return Err((
-                CommandStatus::Failure("552 Insufficient storage space".into()),
-                "552 Insufficient storage space\r\n",
-            ));
+            return Err(invalid_compressed_data());
+        }
+        match options.transfer_type {
+            TransferType::Binary => decoded.append(&mut inflated),
+            TransferType::Ascii => decoder.decode(&inflated, &mut decoded),
         }
     }
+    decoder.finish(&mut decoded);
+    write_chunk(
+        &mut temp_file,
+        &decoded,
+        &mut total_bytes_stored,
+        max_file_size,
+        temp_filename,
+    )?;
 
     // Ensure all data is written to disk
     if let Err(e) = temp_file.flush() {
@@ -123,12 +229,26 @@ pub async fn handle_file_upload(
     drop(temp_file);
 
     // Atomically move temporary file to final location
-    match rename(temp_filename, final_filename) {
+    match commit_upload(temp_filename, final_filename) {
         Ok(_) => {
             info!(
                 "File upload completed successfully: {final_filename} ({total_bytes_received} bytes)"
             );
-            Ok((CommandStatus::Success, "226 Transfer complete\r\n"))
+            if let Some(umask) = options.umask
+                && let Err(e) = apply_umask(Path::new(final_filename), FILE_MODE, umask)
+            {
+                warn!("Failed to apply umask to {final_filename}: {e}");
+            }
+            // Applied after the rename so the committed file carries the time
+            if let Some(modified) = options.modified
+                && let Err(e) = File::options()
+                    .write(true)
+                    .open(final_filename)
+                    .and_then(|file| file.set_modified(modified))
+            {
+                warn!("Failed to set modification time of {final_filename}: {e}");
+            }
+            Ok(total_bytes_received)
         }
         Err(e) => {
             error!("Failed to rename {temp_filename} to {final_filename}: {e}");
@@ -142,15 +262,116 @@ pub async fn handle_file_upload(
     }
 }
 
+/// Appends a chunk of an upload to its temporary file.
+///
+/// The size limit is checked again here, against what is stored, so a small
+/// MODE Z upload can't decompress into a file larger than `max_file_size`.
+fn write_chunk(
+    temp_file: &mut File,
+    chunk: &[u8],
+    total_bytes_stored: &mut u64,
+    max_file_size: u64,
+    temp_filename: &str,
+) -> Result<(), (CommandStatus, &'static str)> {
+    *total_bytes_stored += chunk.len() as u64;
+    if *total_bytes_stored > max_file_size {
+        error!("File size limit exceeded: {total_bytes_stored} bytes > {max_file_size} bytes");
+        let _ = remove_file(temp_filename);
+        return Err((
+            CommandStatus::Failure("552 Insufficient storage space".into()),
+            "552 Insufficient storage space (file too large)\r\n",
+        ));
+    }
+
+    if let Err(e) = temp_file.write_all(chunk) {
+        error!("Failed to write to temporary file {temp_filename}: {e}");
+        // Clean up temporary file
+        let _ = remove_file(temp_filename);
+        return Err((
+            CommandStatus::Failure("552 Insufficient storage space".into()),
+            "552 Insufficient storage space\r\n",
+        ));
+    }
+    Ok(())
+}
+
+/// The error returned when a MODE Z upload is not a valid zlib stream.
+fn invalid_compressed_data() -> (CommandStatus, &'static str) {
+    (
+        CommandStatus::Failure("451 Invalid compressed data".into()),
+        "451 Requested action aborted: invalid compressed data\r\n",
+    )
+}
+
+/// Whether a read or write timed out after `CANCEL_POLL_INTERVAL` without progress.
+fn is_poll_timeout(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+/// The error returned when a transfer is cancelled.
+fn cancelled() -> (CommandStatus, &'static str) {
+    (
+        CommandStatus::Failure("426 Connection closed; transfer aborted".into()),
+        responses::TRANSFER_ABORTED,
+    )
+}
+
+/// Whether a data connection read error may succeed if retried.
+///
+/// A reset or aborted connection is final: retrying would read EOF and
+/// commit a truncated upload as if it had completed.
+/// Timeouts are handled separately, as poll ticks (see `is_poll_timeout`).
+fn is_transient(error: &std::io::Error) -> bool {
+    error.kind() == std::io::ErrorKind::Interrupted
+}
+
+/// Moves a finished upload from its temporary path to its destination.
+///
+/// A temporary directory on another filesystem cannot be renamed across, so
+/// the file is copied next to the destination first and renamed from there,
+/// keeping the final step atomic.
+fn commit_upload(temp_filename: &str, final_filename: &str) -> std::io::Result<()> {
+    match rename(temp_filename, final_filename) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            info!("{temp_filename} is on another filesystem, copying to {final_filename}");
+            let staged = format!("{final_filename}.tmp");
+            let result = copy(temp_filename, &staged)
+                .and_then(|_| rename(&staged, final_filename))
+                .inspect_err(|_| {
+                    let _ = remove_file(&staged);
+                });
+            if result.is_ok() {
+                let _ = remove_file(temp_filename);
+            }
+            result
+        }
+        result => result,
+    }
+}
+
 /// Handles downloading a file from the server to the client.
+///
+/// ASCII downloads have bare LF line endings expanded to CRLF on the wire, and
+/// MODE Z downloads are compressed after that.
+/// A non-zero `options.offset` (set by REST) resumes the download from that byte of the file.
+/// With a limiter, reads from the file are paced to the user's download cap.
+/// The download is aborted once `options.cancel` is cancelled, even while the
+/// client has stopped reading.
+/// Returns the number of bytes sent over the data connection.
 pub fn handle_file_download(
     mut data_stream: TcpStream,
     filename: &str,
+    options: DownloadOptions,
     config: &StartupConfig,
-) -> Result<(CommandStatus, &'static str), (CommandStatus, &'static str)> {
-    info!("Starting file download: {filename}");
+) -> Result<u64, (CommandStatus, &'static str)> {
+    let offset = options.offset;
+    info!("Starting file download: {filename} (offset {offset})");
 
-    let mut file = match File::open(filename) {
+    let open = || File::open(filename);
+    let mut file = match with_retries(open, TRANSIENT_ERRORS, config.max_retries, RETRY_DELAY) {
         Ok(file) => file,
         Err(e) => {
             error!("Failed to open file {filename}: {e}");
@@ -161,11 +382,56 @@ pub fn handle_file_download(
         }
     };
 
+    // Send up to the end of the file as it is now, even if it is still being
+    // appended to, so a client tailing a log with REST gets a consistent cut
+    let file_len = match file.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            error!("Failed to read the size of {filename}: {e}");
+            return Err((
+                CommandStatus::Failure("451 Requested action aborted".into()),
+                "451 Requested action aborted\r\n",
+            ));
+        }
+    };
+    if offset > 0 {
+        if offset > file_len {
+            error!("Restart offset {offset} beyond end of {filename} ({file_len} bytes)");
+            return Err((
+                CommandStatus::Failure("554 Invalid REST parameter".into()),
+                "554 Requested action not taken: invalid REST parameter\r\n",
+            ));
+        }
+        if let Err(e) = file.seek(SeekFrom::Start(offset)) {
+            error!("Failed to seek to offset {offset} in {filename}: {e}");
+            return Err((
+                CommandStatus::Failure("451 Requested action aborted".into()),
+                "451 Requested action aborted\r\n",
+            ));
+        }
+    }
+    let mut file = file.take(file_len - offset);
+
+    // Wake up periodically so a lost control connection is noticed
+    if let Err(e) = data_stream.set_write_timeout(Some(CANCEL_POLL_INTERVAL)) {
+        warn!("Failed to set write timeout on data connection: {e}");
+    }
+
     let mut buffer = vec![0; config.buffer_size];
     let mut total_bytes_sent = 0u64;
+    let mut encoder = AsciiEncoder::default();
+    let mut encoded = Vec::new();
+    let mut deflater = (options.mode == TransferMode::Deflate).then(Deflater::default);
+    let mut compressed = Vec::new();
 
     loop {
-        let n = match file.read(&mut buffer) {
+        if options.cancel.is_cancelled() {
+            warn!("Transfer cancelled, aborting download of {filename}");
+            return Err(cancelled());
+        }
+
+        let read = || file.read(&mut buffer);
+        let n = match with_retries(read, TRANSIENT_READ_ERRORS, config.max_retries, RETRY_DELAY) {
             Ok(0) => break, // EOF
             Ok(n) => n,
             Err(e) => {
@@ -177,34 +443,49 @@ pub fn handle_file_download(
             }
         };
 
-        let mut retries = 0;
-        loop {
-            match data_stream.write_all(&buffer[..n]) {
-                Ok(_) => break,
-                Err(e) if retries < config.max_retries => {
-                    warn!(
-                        "Transient write error (attempt {}/{}): {}. Retrying...",
-                        retries + 1,
-                        config.max_retries,
-                        e
-                    );
-                    retries += 1;
-                    thread::sleep(Duration::from_millis(100 * retries as u64));
-                }
-                Err(e) => {
-                    error!(
-                        "Write failure to data stream after {} retries: {e}",
-                        config.max_retries
-                    );
-                    return Err((
-                        CommandStatus::Failure("426 Connection closed; transfer aborted".into()),
-                        "426 Connection closed; transfer aborted\r\n",
-                    ));
+        if let Some(limiter) = &options.limiter {
+            limiter.throttle(n);
+        }
+
+        let chunk = match options.transfer_type {
+            TransferType::Binary => &buffer[..n],
+            TransferType::Ascii => {
+                encoded.clear();
+                encoder.encode(&buffer[..n], &mut encoded);
+                &encoded[..]
+            }
+        };
+        let chunk = match deflater.as_mut() {
+            Some(deflater) => {
+                compressed.clear();
+                if let Err(e) = deflater.compress(chunk, &mut compressed) {
+                    error!("Failed to compress {filename}: {e}");
+                    return Err(compression_failed());
                 }
+                &compressed[..]
             }
-        }
+            None => chunk,
+        };
 
-        total_bytes_sent += n as u64;
+        send_chunk(&mut data_stream, chunk, &options.cancel, filename, config)?;
+        total_bytes_sent += chunk.len() as u64;
+    }
+
+    // End the MODE Z stream
+    if let Some(deflater) = deflater.take() {
+        compressed.clear();
+        if let Err(e) = deflater.finish(&mut compressed) {
+            error!("Failed to compress {filename}: {e}");
+            return Err(compression_failed());
+        }
+        send_chunk(
+            &mut data_stream,
+            &compressed,
+            &options.cancel,
+            filename,
+            config,
+        )?;
+        total_bytes_sent += compressed.len() as u64;
     }
 
     if let Err(e) = data_stream.flush() {
@@ -217,5 +498,63 @@ pub fn handle_file_download(
 
     info!("File download completed successfully: {filename} ({total_bytes_sent} bytes)");
 
-    Ok((CommandStatus::Success, "226 Transfer complete\r\n"))
+    Ok(total_bytes_sent)
+}
+
+/// Writes one chunk of a download to the data connection.
+///
+/// Written piecewise so a client that stopped reading can't block cancellation.
+fn send_chunk(
+    data_stream: &mut TcpStream,
+    chunk: &[u8],
+    cancel: &CancelToken,
+    filename: &str,
+    config: &StartupConfig,
+) -> Result<(), (CommandStatus, &'static str)> {
+    let mut written = 0;
+    let mut retries = 0;
+    while written < chunk.len() {
+        if cancel.is_cancelled() {
+            warn!("Transfer cancelled, aborting download of {filename}");
+            return Err(cancelled());
+        }
+        match data_stream.write(&chunk[written..]) {
+            Ok(0) => {
+                error!("Data connection closed while sending {filename}");
+                return Err(cancelled());
+            }
+            Ok(n) => written += n,
+            // The poll interval elapsed with the client not reading; not a failed attempt
+            Err(e) if is_poll_timeout(&e) => continue,
+            Err(e) if retries < config.max_retries => {
+                warn!(
+                    "Transient write error (attempt {}/{}): {}. Retrying...",
+                    retries + 1,
+                    config.max_retries,
+                    e
+                );
+                retries += 1;
+                thread::sleep(Duration::from_millis(100 * retries as u64));
+            }
+            Err(e) => {
+                error!(
+                    "Write failure to data stream after {} retries: {e}",
+                    config.max_retries
+                );
+                return Err((
+                    CommandStatus::Failure("426 Connection closed; transfer aborted".into()),
+                    "426 Connection closed; transfer aborted\r\n",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The error returned when a MODE Z download can't be compressed.
+fn compression_failed() -> (CommandStatus, &'static str) {
+    (
+        CommandStatus::Failure("451 Requested action aborted".into()),
+        "451 Requested action aborted\r\n",
+    )
 }
@@ -3,18 +3,25 @@
 //! Handles data channel management, file transfers, and connection operations
 //! with support for persistent data connections.
 
+pub mod bandwidth;
+pub mod cancel;
 pub mod channel_registry;
+pub mod compression;
 pub mod data_channel;
 pub mod file_ops;
+pub mod modes;
 pub mod operations;
+pub mod slots;
 
 // Re-export key types and functions
+pub use bandwidth::{BandwidthLimiter, TransferDirection, user_limiter};
+pub use cancel::CancelToken;
 pub use channel_registry::{ChannelEntry, ChannelRegistry};
-pub use data_channel::{
-    receive_file_upload, send_directory_listing, setup_data_stream,
-    validate_client_and_data_channel,
-};
-pub use file_ops::{handle_file_download, handle_file_upload};
+pub use compression::TransferMode;
+pub use data_channel::{DataConnection, receive_file_upload, send_directory_listing};
+pub use file_ops::{DownloadOptions, UploadOptions, handle_file_download, handle_file_upload};
+pub use modes::TransferType;
 pub use operations::{
     cleanup_data_channel, cleanup_data_stream_only, setup_active_mode, setup_passive_mode,
 };
+pub use slots::acquire_transfer_slot;
@@ -1,5 +1,96 @@
 //! FTP Transfer modes
 //!
-//! Handles active and passive mode implementations.
+//! Defines the data representation (TYPE) used on the data channel and the
+//! line-ending translation applied to ASCII transfers.
 
-// Transfer mode types removed - not currently used in the implementation
+/// Data representation negotiated with the TYPE command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransferType {
+    /// TYPE A: text with CRLF line endings on the wire
+    Ascii,
+    /// TYPE I: bytes are transferred unchanged
+    #[default]
+    Binary,
+}
+
+impl TransferType {
+    /// Parses the argument of a TYPE command (`A`, `A N`, `I`, `L 8` or `L8`).
+    pub fn from_type_arg(arg: &str) -> Option<Self> {
+        let mut parts = arg.split_whitespace();
+        let code = parts.next()?.to_ascii_uppercase();
+        let param = parts.next().map(|p| p.to_ascii_uppercase());
+        if parts.next().is_some() {
+            return None;
+        }
+
+        match (code.as_str(), param.as_deref()) {
+            ("A", None | Some("N")) => Some(TransferType::Ascii),
+            ("I", None) | ("L", Some("8")) | ("L8", None) => Some(TransferType::Binary),
+            _ => None,
+        }
+    }
+
+    /// Returns the mode name used in `150` replies.
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransferType::Ascii => "ASCII",
+            TransferType::Binary => "BINARY",
+        }
+    }
+}
+
+/// Converts local LF line endings to CRLF for ASCII downloads.
+///
+/// Tracks the previous byte across chunks so existing CRLF pairs are not doubled.
+#[derive(Default)]
+pub struct AsciiEncoder {
+    last_was_cr: bool,
+}
+
+impl AsciiEncoder {
+    /// Appends the wire form of `input` to `output`.
+    pub fn encode(&mut self, input: &[u8], output: &mut Vec<u8>) {
+        for &byte in input {
+            if byte == b'\n' && !self.last_was_cr {
+                output.push(b'\r');
+            }
+            output.push(byte);
+            self.last_was_cr = byte == b'\r';
+        }
+    }
+}
+
+/// Converts CRLF line endings received in ASCII uploads to local LF.
+///
+/// A trailing CR at a chunk boundary is held back until the next byte is seen.
+#[derive(Default)]
+pub struct AsciiDecoder {
+    pending_cr: bool,
+}
+
+impl AsciiDecoder {
+    /// Appends the local form of `input` to `output`.
+    pub fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) {
+        for &byte in input {
+            if self.pending_cr {
+                self.pending_cr = false;
+                if byte != b'\n' {
+                    output.push(b'\r');
+                }
+            }
+            if byte == b'\r' {
+                self.pending_cr = true;
+            } else {
+                output.push(byte);
+            }
+        }
+    }
+
+    /// Flushes a CR that was held back at the end of the stream.
+    pub fn finish(&mut self, output: &mut Vec<u8>) {
+        if self.pending_cr {
+            self.pending_cr = false;
+            output.push(b'\r');
+        }
+    }
+}
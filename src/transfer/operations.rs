@@ -3,7 +3,7 @@
 //! Handles data channel setup and management for FTP passive and active modes.
 //! Updated to support persistent data connections.
 
-use log::{error, info};
+use log::{error, info, warn};
 use std::net::{SocketAddr, TcpListener};
 use std::str::FromStr;
 
@@ -23,14 +23,41 @@ pub fn setup_passive_mode(
         channel_registry.cleanup_all(&client_addr);
     }
 
-    // Find next available socket for data connection
-    let data_socket = channel_registry
-        .next_available_socket(&config.bind_address, config.data_port_range())
-        .ok_or(TransferError::NoAvailablePort)?;
+    // At the cap, first reap listeners nobody has used for a while, so
+    // clients that sent PASV and never connected don't hold slots forever
+    let max_listeners = config.max_passive_listeners();
+    if channel_registry.passive_listener_count() >= max_listeners {
+        for reaped in channel_registry.reap_idle_listeners(config.passive_listener_idle()) {
+            info!("Reaped idle passive listener of client {reaped}");
+        }
+    }
 
-    // Bind the listener
-    let listener = TcpListener::bind(data_socket)
-        .map_err(|e| TransferError::PortBindingFailed(data_socket, e))?;
+    // Refuse to bind another listener once the configured cap is reached
+    if channel_registry.passive_listener_count() >= max_listeners {
+        warn!(
+            "Passive listener limit ({max_listeners}) reached, rejecting PASV from client {client_addr}"
+        );
+        return Err(TransferError::PassiveListenerLimitReached(max_listeners));
+    }
+
+    // Bind the first free port in the range. Ports held by other processes
+    // are skipped rather than failing PASV outright.
+    let mut bound = None;
+    for data_socket in
+        channel_registry.available_sockets(&config.bind_address, config.data_port_range())
+    {
+        match TcpListener::bind(data_socket) {
+            Ok(listener) => {
+                bound = Some((data_socket, listener));
+                break;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                warn!("PASV port {data_socket} is in use elsewhere, trying the next one");
+            }
+            Err(e) => return Err(TransferError::PortBindingFailed(data_socket, e)),
+        }
+    }
+    let (data_socket, listener) = bound.ok_or(TransferError::NoAvailablePort)?;
 
     // Set listener to non-blocking to "stop listening" until needed
     listener
@@ -84,20 +111,33 @@ pub fn setup_active_mode(
     let parsed_addr = SocketAddr::from_str(port_command_addr)
         .map_err(|_| TransferError::InvalidPortCommand("Invalid address format".into()))?;
 
-    // Validate IP matches client (for security)
-    if parsed_addr.ip() != client_addr.ip() {
+    // Validate IP matches client (for security), unless FXP is allowed
+    if !config.allow_fxp && parsed_addr.ip() != client_addr.ip() {
         return Err(TransferError::IpMismatch {
             expected: client_addr.ip().to_string(),
             provided: parsed_addr.ip().to_string(),
         });
     }
 
-    // Validate port range
+    // Validate port range; privileged ports are refused to prevent bounce attacks
     let port = parsed_addr.port();
+    if port == 0 {
+        return Err(TransferError::InvalidPortCommand("Invalid port".into()));
+    }
     if port < config.min_client_port {
         return Err(TransferError::InvalidPortRange(port));
     }
 
+    // Refuse the server's own ports and any internal services, so PORT can't
+    // be used to make the server probe them (FTP bounce)
+    if port == config.control_port
+        || config.data_port_range().contains(&port)
+        || config.active_source_port == Some(port)
+        || config.denied_data_ports.contains(&port)
+    {
+        return Err(TransferError::ForbiddenDataAddress(parsed_addr));
+    }
+
     // ✅ CORRECT: In active mode, server stores client's address and connects to it later
     // The client is the one with the TcpListener, not the server!
 
@@ -0,0 +1,44 @@
+//! Server-wide cap on concurrent file transfers
+//!
+//! RETR and STOR each hold a slot for the duration of the transfer. When all
+//! slots are taken a new transfer may wait up to the configured queue timeout
+//! for one to free up before it is refused.
+
+use std::sync::{Arc, OnceLock};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+use crate::config::StartupConfig;
+use crate::error::TransferError;
+
+/// Slots shared by every session, sized from the startup configuration on first use
+static TRANSFER_SLOTS: OnceLock<Arc<Semaphore>> = OnceLock::new();
+
+/// Claims a transfer slot, queueing for up to `transfer_queue_timeout_secs`.
+///
+/// Returns `Ok(None)` when transfers are uncapped. The slot is released when
+/// the returned permit is dropped.
+pub async fn acquire_transfer_slot(
+    startup_config: &StartupConfig,
+) -> Result<Option<OwnedSemaphorePermit>, TransferError> {
+    let Some(max) = startup_config.max_concurrent_transfers else {
+        return Ok(None);
+    };
+    let slots = Arc::clone(TRANSFER_SLOTS.get_or_init(|| Arc::new(Semaphore::new(max))));
+
+    // Take a free slot straight away if there is one
+    if let Ok(permit) = Arc::clone(&slots).try_acquire_owned() {
+        return Ok(Some(permit));
+    }
+
+    let wait = startup_config.transfer_queue_timeout();
+    if wait.is_zero() {
+        return Err(TransferError::TooManyTransfers(max));
+    }
+
+    match timeout(wait, slots.acquire_owned()).await {
+        Ok(Ok(permit)) => Ok(Some(permit)),
+        _ => Err(TransferError::TooManyTransfers(max)),
+    }
+}
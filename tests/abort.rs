@@ -0,0 +1,47 @@
+//! End-to-end tests for aborting transfers with ABOR.
+
+mod common;
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use common::TestServer;
+
+#[test]
+fn abor_cancels_a_retr_waiting_for_its_data_connection() {
+    let server = TestServer::start("abor-accept", 42420);
+    fs::write(server.root.join("file.txt"), "data").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    // The data connection is never opened, so RETR waits in accept
+    client.pasv();
+    assert!(client.cmd("RETR file.txt").starts_with("150"));
+    let started = Instant::now();
+    client.send("ABOR");
+    assert_eq!(client.reply(), "426 Connection closed; transfer aborted");
+    assert_eq!(client.reply(), "226 ABOR command successful");
+    // Well before the five second connection timeout would have given up
+    assert!(started.elapsed() < Duration::from_secs(3));
+
+    // The abort doesn't carry over to the next transfer
+    let (data, reply) = client.retr("file.txt");
+    assert_eq!(data, b"data");
+    assert_eq!(reply, "226 Transfer complete");
+}
+
+#[test]
+fn abor_cancels_a_stor_waiting_for_its_data_connection() {
+    let server = TestServer::start("abor-accept-stor", 42440);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    client.pasv();
+    assert!(client.cmd("STOR upload.txt").starts_with("150"));
+    client.send("ABOR");
+    assert!(client.reply().starts_with("426"));
+    assert_eq!(client.reply(), "226 ABOR command successful");
+    assert!(!server.root.join("upload.txt").exists());
+
+    assert_eq!(client.stor("upload.txt", b"data"), "226 Transfer complete");
+}
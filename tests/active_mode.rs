@@ -0,0 +1,76 @@
+//! End-to-end tests for active mode (PORT) data connections.
+
+mod common;
+
+use std::fs;
+use std::io::Read;
+use std::net::TcpListener;
+
+use common::TestServer;
+
+#[test]
+fn port_rejects_privileged_and_zero_ports() {
+    let server = TestServer::start("port-validation", 40460);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("PORT 127.0.0.1:22"), "501 Port must be >= 1024");
+    assert_eq!(client.cmd("PORT 127.0.0.1:0"), "501 Invalid port");
+    assert_eq!(
+        client.cmd("PORT 127.0.0.1:50000"),
+        "200 PORT command successful"
+    );
+}
+
+#[test]
+fn port_refuses_the_servers_own_and_denied_ports() {
+    let server = TestServer::start_with("port-denied", 41580, "denied_data_ports = [50001]");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let forbidden = [server.addr.port(), 41580, 41589, 50001];
+    for port in forbidden {
+        assert_eq!(
+            client.cmd(&format!("PORT 127.0.0.1:{port}")),
+            "501 Forbidden data address"
+        );
+    }
+    assert_eq!(
+        client.cmd("PORT 127.0.0.1:50002"),
+        "200 PORT command successful"
+    );
+}
+
+#[test]
+fn port_download_connects_back_to_the_client() {
+    let server = TestServer::start("port-retr", 41560);
+    let contents: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+    fs::write(server.root.join("active.bin"), &contents).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    assert_eq!(
+        client.cmd(&format!("PORT 127.0.0.1:{port}")),
+        "200 PORT command successful"
+    );
+
+    client.send("RETR active.bin");
+    let (mut data_stream, peer) = listener.accept().unwrap();
+    assert!(peer.ip().is_loopback());
+    assert!(client.reply().starts_with("150"));
+    let mut received = Vec::new();
+    data_stream.read_to_end(&mut received).unwrap();
+    assert_eq!(client.reply(), "226 Transfer complete");
+    assert_eq!(received, contents);
+
+    // The PORT setting carries over to the next transfer
+    client.send("RETR active.bin");
+    let (mut data_stream, _) = listener.accept().unwrap();
+    assert!(client.reply().starts_with("150"));
+    received.clear();
+    data_stream.read_to_end(&mut received).unwrap();
+    assert_eq!(client.reply(), "226 Transfer complete");
+    assert_eq!(received, contents);
+}
@@ -0,0 +1,46 @@
+//! End-to-end tests for anonymous logins.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn anonymous_login_is_refused_unless_enabled() {
+    let server = TestServer::start("anonymous-disabled", 41900);
+    let mut client = server.connect();
+
+    assert_eq!(
+        client.cmd("USER anonymous"),
+        "530 Anonymous access not allowed"
+    );
+    assert_eq!(
+        client.cmd("PASS guest@example.com"),
+        "530 Username not provided"
+    );
+    assert_eq!(client.cmd("USER nobody"), "530 Unknown user 'nobody'");
+}
+
+#[test]
+fn anonymous_login_accepts_any_password_when_enabled() {
+    let server = TestServer::start_with(
+        "anonymous-enabled",
+        41920,
+        "anonymous_login = true\nlog_anonymous_passwords = true\n\
+         [users.anonymous]\nread_only = true",
+    );
+    let mut client = server.connect();
+
+    client.login("anonymous", "guest@example.com");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+    assert_eq!(client.cmd("MKD uploads"), "550 Permission denied");
+
+    client.login("ANONYMOUS", "x");
+    assert_eq!(client.cmd("MKD uploads"), "550 Permission denied");
+
+    // Named accounts still need their own password
+    client.cmd("USER alice");
+    assert_eq!(
+        client.cmd("PASS guest@example.com"),
+        "530 Invalid password for user: alice"
+    );
+}
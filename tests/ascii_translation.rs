@@ -0,0 +1,82 @@
+//! Tests for the TYPE A line-ending translation applied to file transfers.
+
+use rax_ftp_server::transfer::modes::{AsciiDecoder, AsciiEncoder};
+
+/// Encodes `chunks` as one download would, one buffer at a time.
+fn encode(chunks: &[&[u8]]) -> Vec<u8> {
+    let mut encoder = AsciiEncoder::default();
+    let mut output = Vec::new();
+    for chunk in chunks {
+        encoder.encode(chunk, &mut output);
+    }
+    output
+}
+
+/// Decodes `chunks` as one upload would, one buffer at a time.
+fn decode(chunks: &[&[u8]]) -> Vec<u8> {
+    let mut decoder = AsciiDecoder::default();
+    let mut output = Vec::new();
+    for chunk in chunks {
+        decoder.decode(chunk, &mut output);
+    }
+    decoder.finish(&mut output);
+    output
+}
+
+#[test]
+fn encoder_turns_lf_into_crlf() {
+    assert_eq!(encode(&[b"one\ntwo\n"]), b"one\r\ntwo\r\n");
+    assert_eq!(encode(&[b"\n\n"]), b"\r\n\r\n");
+    assert_eq!(encode(&[b"no newline"]), b"no newline");
+}
+
+#[test]
+fn encoder_keeps_existing_crlf_pairs() {
+    assert_eq!(encode(&[b"one\r\ntwo\n"]), b"one\r\ntwo\r\n");
+    // The pair split across two buffers is still not doubled
+    assert_eq!(encode(&[b"one\r", b"\ntwo"]), b"one\r\ntwo");
+}
+
+#[test]
+fn encoder_passes_a_lone_cr_through() {
+    assert_eq!(encode(&[b"a\rb\n"]), b"a\rb\r\n");
+    assert_eq!(encode(&[b"a\r", b"b"]), b"a\rb");
+    assert_eq!(encode(&[b"a\r"]), b"a\r");
+}
+
+#[test]
+fn decoder_turns_crlf_into_lf() {
+    assert_eq!(decode(&[b"one\r\ntwo\r\n"]), b"one\ntwo\n");
+    assert_eq!(decode(&[b"\r\n\r\n"]), b"\n\n");
+    // A bare LF is already local
+    assert_eq!(decode(&[b"one\ntwo"]), b"one\ntwo");
+}
+
+#[test]
+fn decoder_joins_crlf_pairs_split_across_buffers() {
+    assert_eq!(decode(&[b"one\r", b"\ntwo\r", b"\n"]), b"one\ntwo\n");
+    assert_eq!(decode(&[b"\r", b"\n", b"\r", b"\n"]), b"\n\n");
+}
+
+#[test]
+fn decoder_keeps_a_lone_cr() {
+    assert_eq!(decode(&[b"a\rb"]), b"a\rb");
+    assert_eq!(decode(&[b"a\r", b"b"]), b"a\rb");
+    assert_eq!(decode(&[b"a\r\r\nb"]), b"a\r\nb");
+    assert_eq!(decode(&[b"a\r", b"\r", b"\nb"]), b"a\r\nb");
+}
+
+#[test]
+fn decoder_flushes_a_trailing_cr_at_eof() {
+    assert_eq!(decode(&[b"last line\r"]), b"last line\r");
+    assert_eq!(decode(&[b"last line", b"\r"]), b"last line\r");
+    assert_eq!(decode(&[b"\r"]), b"\r");
+
+    // Without finish the held-back CR is not written yet
+    let mut decoder = AsciiDecoder::default();
+    let mut output = Vec::new();
+    decoder.decode(b"end\r", &mut output);
+    assert_eq!(output, b"end");
+    decoder.finish(&mut output);
+    assert_eq!(output, b"end\r");
+}
@@ -0,0 +1,69 @@
+//! Tests for chaining credential backends in `auth::authenticator`.
+
+use std::fs;
+
+use rax_ftp_server::auth::{
+    Authenticator, BuiltinAuthenticator, ChainAuthenticator, FileAuthenticator,
+};
+use rax_ftp_server::error::AuthError;
+
+fn file_backend(name: &str, contents: &str) -> FileAuthenticator {
+    let path =
+        std::env::temp_dir().join(format!("rax-ftp-credentials-{}-{name}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    let backend = FileAuthenticator::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    backend
+}
+
+#[test]
+fn file_backend_parses_username_password_lines() {
+    let backend = file_backend(
+        "parse",
+        "# admins\ncarol:s3cret:with:colons\n\n dave : pw\n",
+    );
+    assert!(backend.authenticate("carol", "s3cret:with:colons").is_ok());
+    assert!(backend.authenticate("dave", " pw").is_ok());
+    assert!(!backend.has_user("# admins"));
+}
+
+#[test]
+fn chain_accepts_credentials_from_any_backend() {
+    let chain = ChainAuthenticator::new(vec![
+        Box::new(file_backend("any", "carol:carol-pw\n")),
+        Box::new(BuiltinAuthenticator),
+    ]);
+
+    assert!(chain.authenticate("carol", "carol-pw").is_ok());
+    assert!(chain.authenticate("alice", "alice123").is_ok());
+}
+
+#[test]
+fn chain_falls_through_when_an_earlier_backend_rejects() {
+    let chain = ChainAuthenticator::new(vec![
+        Box::new(file_backend("fallthrough", "alice:override\n")),
+        Box::new(BuiltinAuthenticator),
+    ]);
+
+    assert!(chain.authenticate("alice", "override").is_ok());
+    assert!(chain.authenticate("alice", "alice123").is_ok());
+}
+
+#[test]
+fn chain_failures_do_not_reveal_the_backend() {
+    let chain = ChainAuthenticator::new(vec![
+        Box::new(file_backend("failures", "carol:carol-pw\n")),
+        Box::new(BuiltinAuthenticator),
+    ]);
+
+    for user in ["carol", "alice"] {
+        assert!(matches!(
+            chain.authenticate(user, "wrong"),
+            Err(AuthError::InvalidPassword(u)) if u == user
+        ));
+    }
+    assert!(matches!(
+        chain.authenticate("mallory", "wrong"),
+        Err(AuthError::UserNotFound(_))
+    ));
+}
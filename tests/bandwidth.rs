@@ -0,0 +1,86 @@
+//! Tests for per-user bandwidth caps.
+
+mod common;
+
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::TestServer;
+use rax_ftp_server::transfer::BandwidthLimiter;
+
+#[test]
+fn limiter_allows_a_burst_then_charges_the_overdraft() {
+    let limiter = BandwidthLimiter::new(1000);
+    assert_eq!(limiter.reserve(1000), Duration::ZERO);
+
+    let wait = limiter.reserve(500);
+    assert!(
+        wait > Duration::from_millis(400) && wait <= Duration::from_millis(500),
+        "{wait:?}"
+    );
+}
+
+#[test]
+fn downloads_are_paced_to_the_user_cap() {
+    let server = TestServer::start_with(
+        "bandwidth-download",
+        40560,
+        "[users.bob]\nmax_download_kib_per_sec = 64",
+    );
+    let data = vec![7u8; 192 * 1024];
+    fs::write(server.root.join("big.bin"), &data).unwrap();
+    let mut client = server.connect();
+    client.login("bob", "bob123");
+
+    // One second of burst, then 128 KiB of debt at 64 KiB/s
+    let started = Instant::now();
+    let (received, reply) = client.retr("big.bin");
+    let elapsed = started.elapsed();
+
+    assert!(reply.starts_with("226"), "{reply}");
+    assert_eq!(received, data);
+    assert!(elapsed >= Duration::from_millis(1500), "{elapsed:?}");
+}
+
+#[test]
+fn concurrent_downloads_share_the_cap_without_blocking_other_sessions() {
+    let server = TestServer::start_with(
+        "bandwidth-concurrent",
+        42460,
+        "[users.bob]\nmax_download_kib_per_sec = 64",
+    );
+    let data = vec![7u8; 128 * 1024];
+    fs::write(server.root.join("big.bin"), &data).unwrap();
+
+    // One second of burst, then 192 KiB of debt at 64 KiB/s between the two
+    let started = Instant::now();
+    let downloads: Vec<_> = (0..2)
+        .map(|_| {
+            let mut client = server.connect();
+            client.login("bob", "bob123");
+            thread::spawn(move || client.retr("big.bin"))
+        })
+        .collect();
+
+    // While both sessions are being paced, others are still served promptly
+    thread::sleep(Duration::from_millis(500));
+    let asked = Instant::now();
+    let mut other = server.connect();
+    other.login("alice", "alice123");
+    assert!(other.cmd("PWD").starts_with("257"));
+    assert!(other.cmd("NOOP").starts_with("200"));
+    assert!(
+        asked.elapsed() < Duration::from_millis(500),
+        "{:?}",
+        asked.elapsed()
+    );
+
+    for download in downloads {
+        let (received, reply) = download.join().unwrap();
+        assert!(reply.starts_with("226"), "{reply}");
+        assert_eq!(received, data);
+    }
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(2500), "{elapsed:?}");
+}
@@ -0,0 +1,66 @@
+//! End-to-end tests for the greeting and the banner file sent with it.
+
+mod common;
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use common::TestServer;
+
+#[test]
+fn banner_file_lines_precede_the_greeting() {
+    let banner = std::env::temp_dir().join(format!("rax-ftp-banner-{}.txt", std::process::id()));
+    fs::write(&banner, "Authorized use only.\r\n220 is not the end\n").unwrap();
+    let server = TestServer::start_with(
+        "banner",
+        40940,
+        &format!("banner_file = \"{}\"", banner.display()),
+    );
+
+    let mut client = server.connect();
+    let lines: Vec<&str> = client.greeting.lines().collect();
+    assert_eq!(lines[0], "220-Authorized use only.");
+    assert_eq!(lines[1], "220-220 is not the end");
+    assert!(
+        lines[2].starts_with("220 Welcome to"),
+        "{}",
+        client.greeting
+    );
+    assert!(client.cmd("NOOP").starts_with("200"));
+
+    // The banner was read at startup, so later edits don't show up
+    fs::write(&banner, "Changed\n").unwrap();
+    assert!(server.connect().greeting.starts_with("220-Authorized"));
+    fs::remove_file(&banner).unwrap();
+}
+
+#[test]
+fn missing_banner_file_falls_back_to_the_default_greeting() {
+    let server = TestServer::start_with(
+        "banner-missing",
+        40960,
+        "banner_file = \"/nonexistent/rax-ftp-banner.txt\"",
+    );
+    let client = server.connect();
+    assert!(
+        client.greeting.starts_with("220 Welcome to"),
+        "{}",
+        client.greeting
+    );
+}
+
+#[test]
+fn greeting_arrives_before_the_client_sends_anything() {
+    let server = TestServer::start("greeting-flush", 41720);
+    let stream = TcpStream::connect(server.addr).unwrap();
+    // A greeting left unflushed would make this read time out
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+
+    let mut greeting = String::new();
+    BufReader::new(stream).read_line(&mut greeting).unwrap();
+    assert!(greeting.starts_with("220 "), "{greeting}");
+}
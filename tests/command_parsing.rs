@@ -0,0 +1,177 @@
+//! Tests for command argument validation in `protocol::parse_command`.
+
+mod common;
+
+use common::TestServer;
+use rax_ftp_server::protocol::{Command, parse_command};
+
+#[test]
+fn single_token_commands_reject_extra_arguments() {
+    for line in [
+        "USER alice bob",
+        "PASS secret extra",
+        "PORT 127.0.0.1:2000 extra",
+        "REST 100 200",
+    ] {
+        let verb = line.split(' ').next().unwrap().to_string();
+        assert_eq!(parse_command(line), Command::INVALID(verb), "{line:?}");
+    }
+}
+
+#[test]
+fn single_token_commands_accept_one_argument() {
+    assert_eq!(
+        parse_command("USER alice"),
+        Command::USER("alice".to_string())
+    );
+    assert_eq!(
+        parse_command("PORT 127.0.0.1:2000"),
+        Command::PORT("127.0.0.1:2000".to_string())
+    );
+    // Malformed but single-token arguments are left for the handler to judge
+    assert_eq!(
+        parse_command("PORT 1,2,3"),
+        Command::PORT("1,2,3".to_string())
+    );
+    assert_eq!(
+        parse_command("USER   alice  "),
+        Command::USER("alice".to_string())
+    );
+}
+
+#[test]
+fn type_accepts_a_format_parameter() {
+    assert_eq!(parse_command("TYPE A N"), Command::TYPE("A N".to_string()));
+    assert_eq!(parse_command("TYPE L 8"), Command::TYPE("L 8".to_string()));
+    assert_eq!(
+        parse_command("TYPE A N X"),
+        Command::INVALID("TYPE".to_string())
+    );
+}
+
+#[test]
+fn filename_arguments_may_contain_spaces() {
+    assert_eq!(
+        parse_command("RETR my report.txt"),
+        Command::RETR("my report.txt".to_string())
+    );
+    assert_eq!(
+        parse_command("STOR a b c.txt"),
+        Command::STOR("a b c.txt".to_string())
+    );
+}
+
+#[test]
+fn dele_and_del_both_delete_files() {
+    assert_eq!(
+        parse_command("DELE file.txt"),
+        Command::DEL("file.txt".to_string())
+    );
+    assert_eq!(
+        parse_command("DEL file.txt"),
+        Command::DEL("file.txt".to_string())
+    );
+
+    let server = TestServer::start("dele", 40480);
+    std::fs::write(server.root.join("file.txt"), b"data").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("DELE file.txt"), "250 File deleted successfully");
+    assert!(!server.root.join("file.txt").exists());
+}
+
+#[test]
+fn legacy_passive_variants_are_not_implemented() {
+    assert_eq!(
+        parse_command("lpsv"),
+        Command::UNSUPPORTED("LPSV".to_string())
+    );
+
+    let server = TestServer::start("legacy-passive", 40500);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("LPSV"), "502 Command not implemented");
+    assert_eq!(
+        client.cmd("LPRT 4,4,127,0,0,1,2,8,79"),
+        "502 Command not implemented"
+    );
+    assert!(client.cmd("PASV").starts_with("227"));
+}
+
+#[test]
+fn extra_arguments_are_answered_with_501() {
+    let server = TestServer::start("extra-arguments", 40160);
+    let mut client = server.connect();
+
+    assert!(client.cmd("USER alice bob").starts_with("501"));
+    client.login("alice", "alice123");
+
+    assert!(
+        client
+            .cmd("PORT 127.0.0.1:2000 extra")
+            .starts_with("501 Syntax error")
+    );
+    assert!(client.cmd("PORT 1,2,3").starts_with("501"));
+    assert!(client.cmd("TYPE I").starts_with("200"));
+}
+
+#[test]
+fn file_arguments_keep_spaces_and_drop_surrounding_quotes() {
+    assert_eq!(
+        parse_command("RETR my report.pdf"),
+        Command::RETR("my report.pdf".to_string())
+    );
+    assert_eq!(
+        parse_command("STOR \"weekly notes.txt\""),
+        Command::STOR("weekly notes.txt".to_string())
+    );
+    assert_eq!(
+        parse_command("DELE \"say \"\"hi\"\".txt\""),
+        Command::DEL("say \"hi\".txt".to_string())
+    );
+    assert_eq!(parse_command("SIZE \"\""), Command::UNKNOWN);
+}
+
+#[test]
+fn quoted_names_with_spaces_round_trip() {
+    let server = TestServer::start("quoted-names", 40860);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(
+        client
+            .stor("\"weekly notes.txt\"", b"notes")
+            .starts_with("226")
+    );
+    assert!(server.root.join("weekly notes.txt").exists());
+    assert_eq!(client.cmd("SIZE weekly notes.txt"), "213 5");
+    let (data, reply) = client.retr("weekly notes.txt");
+    assert!(reply.starts_with("226"), "{reply}");
+    assert_eq!(data, b"notes");
+}
+
+#[test]
+fn spsv_is_an_alias_for_pasv() {
+    assert_eq!(parse_command("spsv"), Command::PASV);
+
+    let server = TestServer::start("spsv", 41700);
+    std::fs::write(server.root.join("file.txt"), b"contents").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let reply = client.cmd("SPSV");
+    assert!(reply.starts_with("227"), "SPSV failed: {reply}");
+    let start = reply.find('(').unwrap() + 1;
+    let end = reply.find(')').unwrap();
+    let data_addr: std::net::SocketAddr = reply[start..end].parse().unwrap();
+
+    client.send("RETR file.txt");
+    let mut data_stream = std::net::TcpStream::connect(data_addr).unwrap();
+    assert!(client.reply().starts_with("150"));
+    let mut data = Vec::new();
+    std::io::Read::read_to_end(&mut data_stream, &mut data).unwrap();
+    assert_eq!(data, b"contents");
+    assert_eq!(client.reply(), "226 Transfer complete");
+}
@@ -0,0 +1,252 @@
+//! Shared harness for end-to-end tests.
+//!
+//! Starts a server on an ephemeral control port with a throwaway root
+//! directory and provides a small blocking FTP client to drive it.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use config::{Config, File, FileFormat};
+use rax_ftp_server::Server;
+use rax_ftp_server::config::ServerConfig;
+
+/// A server running on a background thread for the duration of a test.
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub root: PathBuf,
+}
+
+impl TestServer {
+    /// Starts a server whose PASV ports begin at `data_port_min`.
+    ///
+    /// Each test needs its own data port range so servers running in parallel
+    /// never compete for the same PASV listener.
+    pub fn start(name: &str, data_port_min: u16) -> Self {
+        Self::start_with(name, data_port_min, "")
+    }
+
+    /// Starts a server with `extra` TOML appended to the base test config.
+    pub fn start_with(name: &str, data_port_min: u16, extra: &str) -> Self {
+        Self::start_setup(name, data_port_min, extra, |_| {})
+    }
+
+    /// Starts a server after letting `setup` adjust it, e.g. to register hooks.
+    pub fn start_setup(
+        name: &str,
+        data_port_min: u16,
+        extra: &str,
+        setup: impl FnOnce(&mut Server) + Send + 'static,
+    ) -> Self {
+        let root = std::env::temp_dir().join(format!("rax-ftp-e2e-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).expect("create server root");
+
+        let config = test_config(&root, free_port(), data_port_min, extra);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().expect("build runtime");
+            runtime.block_on(async move {
+                let mut server = Server::with_config(config).await.expect("start server");
+                setup(&mut server);
+                tx.send(server.local_addr().expect("local addr")).unwrap();
+                let _ = server.start().await;
+            });
+        });
+        let addr = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("server did not start");
+
+        Self { addr, root }
+    }
+
+    /// Connects a new control connection and consumes the greeting.
+    pub fn connect(&self) -> FtpClient {
+        FtpClient::connect(self.addr)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Reserves a free local port for a control listener.
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("reserve control port")
+}
+
+/// Builds the base test config, with `extra` TOML appended.
+pub fn test_config(
+    root: &std::path::Path,
+    control_port: u16,
+    data_port_min: u16,
+    extra: &str,
+) -> ServerConfig {
+    let toml = format!(
+        r#"
+        bind_address = "127.0.0.1"
+        control_port = {control_port}
+        data_port_min = {data_port_min}
+        data_port_max = {data_port_max}
+        server_root = "{root}"
+        max_clients = 10
+        max_file_size_mb = 10
+        buffer_size = 8192
+        connection_timeout_secs = 5
+        max_retries = 3
+        max_command_length = 512
+        max_directory_depth = 3
+        max_username_length = 64
+        min_client_port = 1024
+        {extra}
+        "#,
+        data_port_max = data_port_min + 10,
+        root = root.display(),
+    );
+    Config::builder()
+        .add_source(File::from_str(&toml, FileFormat::Toml))
+        .build()
+        .and_then(|c| c.try_deserialize())
+        .expect("test config should deserialize")
+}
+
+/// Minimal blocking FTP client for driving the server in tests.
+pub struct FtpClient {
+    pub greeting: String,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl FtpClient {
+    pub fn connect(addr: SocketAddr) -> Self {
+        let stream = TcpStream::connect(addr).expect("connect control");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut client = Self {
+            greeting: String::new(),
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        };
+        let greeting = client.reply();
+        assert!(
+            greeting.starts_with("220"),
+            "unexpected greeting {greeting:?}"
+        );
+        client.greeting = greeting;
+        client
+    }
+
+    /// The client's end of the control connection.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.writer.local_addr().unwrap()
+    }
+
+    /// Reads one complete reply, joining the lines of a multi-line reply.
+    pub fn reply(&mut self) -> String {
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).expect("read reply");
+            assert!(!line.is_empty(), "connection closed");
+            reply.push_str(&line);
+            // A multi-line reply ends with its code followed by a space
+            let bytes = reply.as_bytes();
+            if bytes.len() < 4 || bytes[3] != b'-' || line.starts_with(&format!("{} ", &reply[..3]))
+            {
+                return reply.trim_end().to_string();
+            }
+        }
+    }
+
+    /// Returns true once the server has closed the control connection.
+    ///
+    /// A reset counts as closed: the server resets the connection when it
+    /// closes with commands still unread.
+    pub fn is_closed(&mut self) -> bool {
+        let mut rest = Vec::new();
+        match self.reader.read_to_end(&mut rest) {
+            Ok(n) => n == 0,
+            Err(e) => e.kind() == std::io::ErrorKind::ConnectionReset,
+        }
+    }
+
+    /// Sends a command and returns its reply.
+    pub fn cmd(&mut self, command: &str) -> String {
+        self.send(command);
+        self.reply()
+    }
+
+    /// Writes raw bytes to the control connection, e.g. several commands at once.
+    pub fn send_raw(&mut self, bytes: &[u8]) {
+        self.writer.write_all(bytes).expect("send raw bytes");
+    }
+
+    pub fn send(&mut self, command: &str) {
+        self.writer
+            .write_all(format!("{command}\r\n").as_bytes())
+            .expect("send command");
+    }
+
+    pub fn login(&mut self, user: &str, pass: &str) {
+        assert!(self.cmd(&format!("USER {user}")).starts_with("331"));
+        let reply = self.cmd(&format!("PASS {pass}"));
+        assert!(reply.starts_with("230"), "login failed: {reply}");
+    }
+
+    /// Enters passive mode and returns the announced data address.
+    pub fn pasv(&mut self) -> SocketAddr {
+        let reply = self.cmd("PASV");
+        assert!(reply.starts_with("227"), "PASV failed: {reply}");
+        let start = reply.find('(').expect("PASV address") + 1;
+        let end = reply.find(')').expect("PASV address");
+        reply[start..end].parse().expect("PASV socket address")
+    }
+
+    /// Uploads `data` with STOR and returns the final reply.
+    pub fn stor(&mut self, name: &str, data: &[u8]) -> String {
+        let data_addr = self.pasv();
+        self.send(&format!("STOR {name}"));
+        let mut data_stream = TcpStream::connect(data_addr).expect("connect data");
+        let preliminary = self.reply();
+        assert!(
+            preliminary.starts_with("150"),
+            "STOR refused: {preliminary}"
+        );
+        data_stream.write_all(data).unwrap();
+        drop(data_stream);
+        self.reply()
+    }
+
+    /// Downloads `name` with RETR, returning the data and the final reply.
+    pub fn retr(&mut self, name: &str) -> (Vec<u8>, String) {
+        self.download(&format!("RETR {name}"))
+    }
+
+    /// Runs a command that sends data to the client over PASV (RETR, LIST,
+    /// NLST, ...), returning the data and the final reply.
+    pub fn download(&mut self, command: &str) -> (Vec<u8>, String) {
+        let data_addr = self.pasv();
+        self.send(command);
+        let mut data_stream = TcpStream::connect(data_addr).expect("connect data");
+        let preliminary = self.reply();
+        assert!(
+            preliminary.starts_with("150"),
+            "{command} refused: {preliminary}"
+        );
+        let mut data = Vec::new();
+        data_stream.read_to_end(&mut data).unwrap();
+        (data, self.reply())
+    }
+}
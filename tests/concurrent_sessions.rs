@@ -0,0 +1,30 @@
+//! End-to-end tests for state kept separately per client session.
+
+mod common;
+
+use std::fs;
+
+use common::TestServer;
+
+#[test]
+fn clients_keep_independent_working_directories() {
+    let server = TestServer::start("independent-cwd", 40400);
+    fs::create_dir_all(server.root.join("dir1")).unwrap();
+    fs::create_dir_all(server.root.join("dir2")).unwrap();
+
+    let mut a = server.connect();
+    let mut b = server.connect();
+    a.login("alice", "alice123");
+    b.login("bob", "bob123");
+
+    assert!(a.cmd("CWD /dir1").starts_with("250"));
+    assert!(b.cmd("CWD /dir2").starts_with("250"));
+
+    assert_eq!(a.cmd("PWD"), "257 \"/dir1\"");
+    assert_eq!(b.cmd("PWD"), "257 \"/dir2\"");
+
+    // Relative paths resolve against each client's own directory
+    assert!(a.stor("a.txt", b"from a").starts_with("226"));
+    assert!(server.root.join("dir1/a.txt").is_file());
+    assert!(!server.root.join("dir2/a.txt").exists());
+}
@@ -0,0 +1,61 @@
+//! Tests for applying a reloaded configuration, as SIGHUP does.
+
+mod common;
+
+use std::fs;
+use std::sync::Arc;
+
+use common::{FtpClient, free_port, test_config};
+use rax_ftp_server::Server;
+use rax_ftp_server::config::ConfigChanges;
+
+#[test]
+fn reload_applies_runtime_settings_and_names_startup_ones() {
+    let root = std::env::temp_dir().join(format!("rax-ftp-reload-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let port = free_port();
+    let config = |extra: &str| {
+        let extra = format!("{extra}\n[users.admin]\nadmin = true");
+        test_config(&root, port, 42640, &extra)
+    };
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = Arc::new(runtime.block_on(Server::with_config(config(""))).unwrap());
+
+    // Reading back the same settings changes nothing
+    let changes = runtime.block_on(server.reload(config(""))).unwrap();
+    assert_eq!(changes, ConfigChanges::default());
+
+    // An invalid config is refused as a whole
+    assert!(
+        runtime
+            .block_on(server.reload(config("max_passive_listeners = 0")))
+            .is_err()
+    );
+
+    let changes = runtime
+        .block_on(server.reload(config(
+            "maintenance_mode = true\nmaintenance_message = \"Back soon\"\nread_only = true\nhide_server_identity = true",
+        )))
+        .unwrap();
+    assert_eq!(changes.applied, ["maintenance_message", "maintenance_mode"]);
+    assert_eq!(
+        changes.restart_required,
+        ["hide_server_identity", "read_only"]
+    );
+
+    let addr = server.local_addr().unwrap();
+    let running = Arc::clone(&server);
+    runtime.spawn(async move { running.start().await });
+
+    // Maintenance mode took effect; read-only mode waits for a restart
+    let mut client = FtpClient::connect(addr);
+    assert_eq!(client.cmd("USER alice"), "421 Back soon");
+    let mut admin = FtpClient::connect(addr);
+    admin.login("admin", "admin123");
+    assert!(admin.cmd("MKD uploads").starts_with("257"));
+
+    runtime.shutdown_background();
+    let _ = fs::remove_dir_all(&root);
+}
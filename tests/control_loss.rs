@@ -0,0 +1,77 @@
+//! End-to-end tests for transfers whose control connection is lost.
+
+mod common;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::TestServer;
+
+/// Waits up to five seconds for `condition` to hold.
+fn eventually(condition: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+#[test]
+fn upload_is_aborted_when_the_control_connection_drops() {
+    let server = TestServer::start("control-loss-stor", 41040);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let data_addr = client.pasv();
+    client.send("STOR orphan.bin");
+    let mut data_stream = TcpStream::connect(data_addr).expect("connect data");
+    assert!(client.reply().starts_with("150"));
+    data_stream.write_all(&[1u8; 4096]).unwrap();
+    assert!(eventually(|| fs::read_dir(&server.root)
+        .unwrap()
+        .next()
+        .is_some()));
+
+    // The data connection stays open but idle once the client is gone
+    drop(client);
+    assert!(
+        eventually(|| fs::read_dir(&server.root).unwrap().next().is_none()),
+        "upload was not cleaned up"
+    );
+
+    // The session is gone, so other clients get through again
+    let mut other = server.connect();
+    other.login("bob", "bob123");
+    drop(data_stream);
+}
+
+#[test]
+fn download_is_aborted_when_the_control_connection_drops() {
+    const SIZE: usize = 32 * 1024 * 1024;
+    let server = TestServer::start("control-loss-retr", 41060);
+    fs::write(server.root.join("big.bin"), vec![7u8; SIZE]).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let data_addr = client.pasv();
+    client.send("RETR big.bin");
+    let mut data_stream = TcpStream::connect(data_addr).expect("connect data");
+    assert!(client.reply().starts_with("150"));
+
+    // Stop reading so the server blocks on a full socket, then go away
+    drop(client);
+    thread::sleep(Duration::from_secs(2));
+
+    let mut received = Vec::new();
+    data_stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let _ = data_stream.read_to_end(&mut received);
+    assert!(received.len() < SIZE, "download ran to completion");
+}
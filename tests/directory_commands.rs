@@ -0,0 +1,108 @@
+//! End-to-end tests for MKD/RMD and the legacy X-prefixed aliases.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn mkd_and_rmd_manage_directories() {
+    let server = TestServer::start("mkd-rmd", 40320);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("MKD reports"), "257 \"/reports\" created");
+    assert!(server.root.join("reports").is_dir());
+    assert_eq!(client.cmd("MKD reports"), "550 /reports: File exists");
+
+    std::fs::write(server.root.join("reports/q1.txt"), b"data").unwrap();
+    assert_eq!(
+        client.cmd("RMD reports"),
+        "550 /reports: Directory not empty"
+    );
+
+    std::fs::remove_file(server.root.join("reports/q1.txt")).unwrap();
+    assert_eq!(client.cmd("RMD reports"), "250 Directory removed");
+    assert!(!server.root.join("reports").exists());
+    assert_eq!(client.cmd("RMD reports"), "550 /reports: No such directory");
+}
+
+#[test]
+fn x_prefixed_aliases_match_their_standard_commands() {
+    let server = TestServer::start("x-aliases", 40340);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let mkd = client.cmd("MKD first");
+    assert_eq!(client.cmd("XMKD second"), mkd.replace("first", "second"));
+
+    let cwd = client.cmd("CWD first");
+    assert_eq!(client.cmd("XCWD /second"), cwd);
+
+    assert_eq!(client.cmd("XPWD"), client.cmd("PWD"));
+
+    client.cmd("CWD /");
+    let rmd = client.cmd("RMD first");
+    assert_eq!(client.cmd("XRMD second"), rmd);
+}
+
+#[test]
+fn cwd_handles_trailing_slashes_root_and_dot() {
+    let server = TestServer::start("cwd-forms", 41120);
+    std::fs::create_dir(server.root.join("dir")).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.cmd("CWD dir/").starts_with("250"));
+    assert_eq!(client.cmd("PWD"), "257 \"/dir\"");
+    assert!(client.cmd("CWD .").starts_with("250"));
+    assert_eq!(client.cmd("PWD"), "257 \"/dir\"");
+    assert!(client.cmd("CWD /").starts_with("250"));
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+    assert!(client.cmd("CWD /dir/").starts_with("250"));
+    assert_eq!(client.cmd("PWD"), "257 \"/dir\"");
+}
+
+#[test]
+fn dot_refers_to_the_current_directory() {
+    let server = TestServer::start("dot-paths", 41140);
+    std::fs::create_dir(server.root.join("docs")).unwrap();
+    std::fs::write(server.root.join("docs/file.txt"), b"data").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.cmd("CWD .").starts_with("250"));
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+    assert!(client.cmd("CWD ./docs").starts_with("250"));
+    assert_eq!(client.cmd("PWD"), "257 \"/docs\"");
+
+    let (data, reply) = client.retr("./file.txt");
+    assert!(reply.starts_with("226"), "{reply}");
+    assert_eq!(data, b"data");
+}
+
+#[test]
+fn file_commands_on_a_directory_say_it_is_not_a_regular_file() {
+    let server = TestServer::start("file-commands-on-dir", 41420);
+    std::fs::create_dir_all(server.root.join("docs")).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    client.pasv();
+
+    assert_eq!(client.cmd("RETR docs"), "550 /docs: Not a regular file");
+    assert_eq!(client.cmd("DELE docs"), "550 /docs: Not a regular file");
+    assert_eq!(client.cmd("SIZE docs"), "550 /docs: Not a regular file");
+    assert!(server.root.join("docs").is_dir());
+}
+
+#[test]
+fn names_longer_than_the_os_allows_are_reported_as_too_long() {
+    let server = TestServer::start("path-too-long", 41740);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    let name = "n".repeat(300);
+
+    assert_eq!(client.cmd(&format!("MKD {name}")), "550 Path too long");
+    assert_eq!(client.cmd(&format!("DELE {name}")), "550 Path too long");
+    assert_eq!(client.cmd(&format!("CWD {name}")), "550 Path too long");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+}
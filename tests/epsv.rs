@@ -0,0 +1,58 @@
+//! End-to-end tests for extended passive mode and `EPSV ALL`.
+
+mod common;
+
+use std::fs;
+use std::io::Read;
+use std::net::{SocketAddr, TcpStream};
+
+use common::{FtpClient, TestServer};
+
+/// Enters extended passive mode and returns the data address to connect to.
+fn epsv(client: &mut FtpClient, server: &TestServer) -> SocketAddr {
+    let reply = client.cmd("EPSV");
+    assert!(reply.starts_with("229"), "EPSV failed: {reply}");
+    let port = reply
+        .split('|')
+        .nth(3)
+        .and_then(|port| port.parse().ok())
+        .expect("EPSV port");
+    SocketAddr::new(server.addr.ip(), port)
+}
+
+#[test]
+fn epsv_all_refuses_other_data_setup_but_keeps_epsv_working() {
+    let server = TestServer::start("epsv-all", 40760);
+    fs::write(server.root.join("listed.txt"), b"x").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("EPSV ALL"), "200 EPSV ALL ok");
+    assert_eq!(
+        client.cmd("PORT 127,0,0,1,39,16"),
+        "503 PORT not allowed after EPSV ALL"
+    );
+    assert_eq!(client.cmd("PASV"), "503 PASV not allowed after EPSV ALL");
+
+    let data_addr = epsv(&mut client, &server);
+    client.send("LIST");
+    let mut data_stream = TcpStream::connect(data_addr).expect("connect data");
+    assert!(client.reply().starts_with("150"));
+    let mut listing = String::new();
+    data_stream.read_to_string(&mut listing).unwrap();
+    assert!(client.reply().starts_with("226"));
+    assert!(listing.contains("listed.txt"), "{listing}");
+}
+
+#[test]
+fn epsv_rejects_unsupported_network_protocols() {
+    let server = TestServer::start("epsv-protocol", 40780);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        client.cmd("EPSV 2"),
+        "522 Network protocol not supported, use (1)"
+    );
+    assert!(client.cmd("EPSV 1").starts_with("229"));
+}
@@ -0,0 +1,175 @@
+//! End-to-end tests for disconnecting clients after repeated error replies.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn consecutive_errors_close_the_session() {
+    let server = TestServer::start_with("error-limit", 41520, "max_consecutive_errors = 3");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    // A successful command resets the count
+    assert!(client.cmd("BOGUS").starts_with("5"));
+    assert!(client.cmd("DELE missing.txt").starts_with("550"));
+    assert_eq!(client.cmd("NOOP"), "200 NOOP ok");
+
+    assert!(client.cmd("BOGUS").starts_with("5"));
+    assert!(client.cmd("CWD missing").starts_with("550"));
+    client.send("SIZE missing.txt");
+    assert!(client.reply().starts_with("550"));
+    assert_eq!(client.reply(), "421 Too many errors, closing connection");
+    assert!(client.is_closed());
+}
+
+#[test]
+fn consecutive_errors_before_login_close_the_connection() {
+    let server = TestServer::start_with(
+        "error-limit-auth",
+        41540,
+        "max_consecutive_errors = 3\nmax_login_attempts = 5",
+    );
+    let mut client = server.connect();
+
+    // Failed logins don't count, since they have a limit of their own
+    for _ in 0..3 {
+        client.cmd("USER alice");
+        assert!(client.cmd("PASS wrong").starts_with("530"));
+    }
+
+    assert!(client.cmd("PWD").starts_with("530"));
+    assert!(client.cmd("BOGUS").starts_with("5"));
+    client.send("LIST");
+    assert!(client.reply().starts_with("530"));
+    assert_eq!(client.reply(), "421 Too many errors, closing connection");
+    assert!(client.is_closed());
+}
+
+#[test]
+fn blank_keepalive_lines_are_not_errors() {
+    let server = TestServer::start_with("error-limit-blank", 41980, "max_consecutive_errors = 2");
+    let mut client = server.connect();
+
+    // Ignored without a reply, before and after login
+    client.send_raw(b"\r\n\r\n\r\n");
+    assert_eq!(client.cmd("NOOP"), "200 NOOP ok");
+    client.login("alice", "alice123");
+    client.send_raw(b"\r\n \r\n\r\n");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+
+    // Nor do they end a run of errors
+    assert!(client.cmd("BOGUS").starts_with("5"));
+    client.send_raw(b"\r\n");
+    client.send("BOGUS");
+    assert!(client.reply().starts_with("5"));
+    assert_eq!(client.reply(), "421 Too many errors, closing connection");
+}
+
+#[test]
+fn blank_lines_can_be_answered_like_noop() {
+    let server = TestServer::start_with(
+        "error-limit-blank-answered",
+        42000,
+        "answer_empty_lines = true",
+    );
+    let mut client = server.connect();
+
+    client.send_raw(b"\r\n");
+    assert_eq!(client.reply(), "200 NOOP ok");
+    client.login("alice", "alice123");
+    client.send_raw(b"\r\n");
+    assert_eq!(client.reply(), "200 NOOP ok");
+}
+
+#[test]
+fn failed_logins_close_the_connection_after_max_login_attempts() {
+    let server = TestServer::start("login-attempts", 42240);
+    let mut client = server.connect();
+
+    // Four attempts, of which the third reaches the limit of 3
+    client.send_raw(
+        b"USER alice\r\nPASS wrong\r\nBOGUS\r\nUSER alice\r\nPASS wrong\r\n\
+          USER alice\r\nPASS wrong\r\nUSER alice\r\nPASS alice123\r\n",
+    );
+    for attempt in 1..=3 {
+        assert!(client.reply().starts_with("331"));
+        assert_eq!(client.reply(), "530 Invalid password for user: alice");
+        // Other errors in between don't reset the count
+        if attempt == 1 {
+            assert!(client.reply().starts_with("5"));
+        }
+    }
+    assert_eq!(client.reply(), "421 Too many authentication attempts");
+    assert!(client.is_closed());
+
+    // Unknown users and PASS before USER count too
+    let mut client = server.connect();
+    assert!(client.cmd("USER nosuch").starts_with("530"));
+    assert!(client.cmd("PASS wrong").starts_with("530"));
+    assert!(client.cmd("USER alice").starts_with("331"));
+    client.send("PASS wrong");
+    assert!(client.reply().starts_with("530"));
+    assert_eq!(client.reply(), "421 Too many authentication attempts");
+    assert!(client.is_closed());
+
+    // A new connection starts over
+    let mut client = server.connect();
+    client.cmd("USER bob");
+    assert!(client.cmd("PASS wrong").starts_with("530"));
+    client.login("alice", "alice123");
+}
+
+#[test]
+fn failed_logins_after_logging_in_close_the_connection() {
+    let server = TestServer::start("login-attempts-session", 42660);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    // Four bad pairs, of which the third reaches the limit of 3
+    client.send_raw(&b"USER bob\r\nPASS wrong\r\n".repeat(4));
+    for _ in 0..3 {
+        assert!(client.reply().starts_with("331"));
+        assert!(client.reply().starts_with("530"));
+    }
+    assert_eq!(client.reply(), "421 Too many authentication attempts");
+    assert!(client.is_closed());
+
+    // Failures before the login carry over into the session
+    let mut client = server.connect();
+    client.cmd("USER alice");
+    assert!(client.cmd("PASS wrong").starts_with("530"));
+    client.login("alice", "alice123");
+    client.cmd("USER bob");
+    assert!(client.cmd("PASS wrong").starts_with("530"));
+    assert!(client.cmd("USER nosuch").starts_with("530"));
+    assert_eq!(client.reply(), "421 Too many authentication attempts");
+    assert!(client.is_closed());
+}
+
+#[test]
+fn failed_logins_count_as_errors_without_a_login_limit() {
+    let server = TestServer::start_with(
+        "error-limit-no-login-limit",
+        42680,
+        "max_consecutive_errors = 3\nmax_login_attempts = 0",
+    );
+
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    assert!(client.cmd("USER nosuch").starts_with("530"));
+    assert!(client.cmd("BOGUS").starts_with("5"));
+    client.send("PASS wrong");
+    assert!(client.reply().starts_with("5"));
+    assert_eq!(client.reply(), "421 Too many errors, closing connection");
+    assert!(client.is_closed());
+
+    // Before logging in too
+    let mut client = server.connect();
+    assert!(client.cmd("USER nosuch").starts_with("530"));
+    assert!(client.cmd("PASS wrong").starts_with("5"));
+    client.send("USER other");
+    assert!(client.reply().starts_with("530"));
+    assert_eq!(client.reply(), "421 Too many errors, closing connection");
+    assert!(client.is_closed());
+}
@@ -0,0 +1,86 @@
+//! End-to-end tests for rejecting site-to-site (FXP) data connections.
+
+mod common;
+
+use common::TestServer;
+
+/// Opens a connection to `target` from the given local loopback address.
+#[cfg(target_os = "linux")]
+fn connect_from(
+    source: &str,
+    target: std::net::SocketAddr,
+) -> std::io::Result<tokio::net::TcpStream> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let socket = tokio::net::TcpSocket::new_v4()?;
+        socket.bind(format!("{source}:0").parse().unwrap())?;
+        socket.connect(target).await
+    })
+}
+
+// Every 127.0.0.0/8 address is local on Linux, so a second host can be faked
+#[cfg(target_os = "linux")]
+#[test]
+fn passive_connection_from_another_host_is_rejected() {
+    let server = TestServer::start("fxp-rejected", 40180);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let data_addr = client.pasv();
+    client.send("LIST");
+    let _foreign = connect_from("127.0.0.2", data_addr).expect("connect data");
+
+    assert!(client.reply().starts_with("150"));
+    assert!(client.reply().starts_with("425"));
+}
+
+#[test]
+fn passive_connection_from_the_control_host_is_accepted() {
+    let server = TestServer::start("fxp-same-host", 40200);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.stor("same-host.txt", b"data").starts_with("226"));
+}
+
+#[test]
+fn passive_connection_from_a_different_port_is_accepted() {
+    let server = TestServer::start("fxp-other-port", 42040);
+    std::fs::write(server.root.join("a.txt"), b"data").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    // Only the IP has to match the control connection's, not the port
+    let data_addr = client.pasv();
+    client.send("RETR a.txt");
+    let mut data_stream = std::net::TcpStream::connect(data_addr).unwrap();
+    assert_eq!(
+        data_stream.local_addr().unwrap().ip(),
+        client.local_addr().ip()
+    );
+    assert_ne!(
+        data_stream.local_addr().unwrap().port(),
+        client.local_addr().port()
+    );
+
+    assert!(client.reply().starts_with("150"));
+    let mut data = Vec::new();
+    std::io::Read::read_to_end(&mut data_stream, &mut data).unwrap();
+    assert_eq!(data, b"data");
+    assert_eq!(client.reply(), "226 Transfer complete");
+}
+
+#[test]
+fn port_to_another_host_is_rejected() {
+    let server = TestServer::start("fxp-port", 40220);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(
+        client
+            .cmd("PORT 10.0.0.1:2000")
+            .starts_with("501 IP mismatch")
+    );
+}
@@ -0,0 +1,130 @@
+//! End-to-end tests for HASH and OPTS HASH.
+
+mod common;
+
+use std::fs;
+
+use common::TestServer;
+
+#[test]
+fn hash_reports_a_files_sha256_digest() {
+    let server = TestServer::start("hash", 41620);
+    fs::create_dir(server.root.join("docs")).unwrap();
+    fs::write(server.root.join("docs/hello world.txt"), b"hello world\n").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(
+        client
+            .cmd("FEAT")
+            .contains("\r\n HASH SHA-256*;SHA-512;MD5;CRC32\r\n")
+    );
+    assert_eq!(
+        client.cmd("HASH \"docs/hello world.txt\""),
+        "213 SHA-256 0-12 a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447 docs/hello world.txt"
+    );
+    assert_eq!(
+        client.cmd("HASH missing.txt"),
+        "550 /missing.txt: File not found"
+    );
+    assert_eq!(client.cmd("HASH docs"), "550 /docs: Not a regular file");
+}
+
+/// Selects `algorithm` with OPTS HASH and checks HASH reports `digest` for a
+/// file holding the classic pangram.
+fn assert_opts_hash_digest(name: &str, data_port_min: u16, algorithm: &str, digest: &str) {
+    let server = TestServer::start(name, data_port_min);
+    fs::write(
+        server.root.join("fox.txt"),
+        b"The quick brown fox jumps over the lazy dog",
+    )
+    .unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        client.cmd(&format!("OPTS HASH {}", algorithm.to_ascii_lowercase())),
+        format!("200 {algorithm}")
+    );
+    assert_eq!(client.cmd("OPTS HASH"), format!("200 {algorithm}"));
+    assert_eq!(
+        client.cmd("HASH fox.txt"),
+        format!("213 {algorithm} 0-43 {digest} fox.txt")
+    );
+}
+
+#[test]
+fn opts_hash_selects_sha256() {
+    assert_opts_hash_digest(
+        "opts-hash-sha256",
+        42540,
+        "SHA-256",
+        "d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592",
+    );
+}
+
+#[test]
+fn opts_hash_selects_sha512() {
+    assert_opts_hash_digest(
+        "opts-hash-sha512",
+        42560,
+        "SHA-512",
+        "07e547d9586f6a73f73fbac0435ed76951218fb7d0c8d788a309d785436bbb642e93a252a954f23912547d1e8a3b5ed6e1bfd7097821233fa0538f3db854fee6",
+    );
+}
+
+#[test]
+fn opts_hash_selects_md5() {
+    assert_opts_hash_digest(
+        "opts-hash-md5",
+        42580,
+        "MD5",
+        "9e107d9d372bb6826bd81d3542a419d6",
+    );
+}
+
+#[test]
+fn opts_hash_selects_crc32() {
+    assert_opts_hash_digest("opts-hash-crc32", 42600, "CRC32", "414fa339");
+}
+
+#[test]
+fn opts_hash_switches_algorithms_within_a_session() {
+    let server = TestServer::start("opts-hash", 41640);
+    fs::write(server.root.join("hello.txt"), b"hello world\n").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("OPTS HASH"), "200 SHA-256");
+    let digests = [
+        (
+            "SHA-512",
+            "db3974a97f2407b7cae1ae637c0030687a11913274d578492558e39c16c017de84eacdc8c62fe34ee4e12b4b1428817f09b6a2760c3f8a664ceae94d2434a593",
+        ),
+        ("MD5", "6f5902ac237024bdd0c176cb93063dc4"),
+        ("CRC32", "af083b2d"),
+        (
+            "SHA-256",
+            "a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447",
+        ),
+    ];
+    for (algorithm, digest) in digests {
+        assert_eq!(
+            client.cmd(&format!("OPTS HASH {}", algorithm.to_ascii_lowercase())),
+            format!("200 {algorithm}")
+        );
+        assert_eq!(
+            client.cmd("HASH hello.txt"),
+            format!("213 {algorithm} 0-12 {digest} hello.txt")
+        );
+    }
+
+    client.cmd("OPTS HASH MD5");
+    assert!(
+        client
+            .cmd("FEAT")
+            .contains("\r\n HASH SHA-256;SHA-512;MD5*;CRC32\r\n")
+    );
+    assert_eq!(client.cmd("OPTS HASH SHA-1"), "501 Unknown algorithm");
+    assert_eq!(client.cmd("OPTS HASH"), "200 MD5");
+}
@@ -0,0 +1,62 @@
+//! End-to-end tests for HELP and for commands disabled in the configuration.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn help_lists_every_command_by_default() {
+    let server = TestServer::start("help-default", 41240);
+    let mut client = server.connect();
+
+    // HELP works before login, like FEAT
+    let help = client.cmd("HELP");
+    assert!(help.starts_with("214-The following commands are recognized:\r\n"));
+    assert!(help.ends_with("\r\n214 Help OK"));
+    for verb in ["DELE", "FEAT", "HELP", "RETR", "STOR"] {
+        assert!(help.contains(verb), "{verb} missing from {help}");
+    }
+
+    assert_eq!(client.cmd("HELP dele"), "214 DELE is available");
+    assert_eq!(client.cmd("HELP BOGUS"), "502 BOGUS is not available");
+}
+
+#[test]
+fn disabled_commands_are_refused_and_left_out_of_help_and_feat() {
+    let server = TestServer::start_with(
+        "help-disabled",
+        41260,
+        r#"disabled_commands = ["DELE", "size"]"#,
+    );
+    std::fs::write(server.root.join("file.txt"), b"data").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let help = client.cmd("HELP");
+    assert!(!help.contains("DELE"));
+    assert!(!help.contains("SIZE"));
+    assert!(help.contains("RETR"));
+    assert_eq!(client.cmd("HELP DELE"), "502 DELE is not available");
+
+    let feat = client.cmd("FEAT");
+    assert!(!feat.contains(" SIZE\r\n"));
+    assert!(feat.contains(" MDTM\r\n"));
+
+    assert_eq!(client.cmd("DELE file.txt"), "502 Command not implemented");
+    assert_eq!(client.cmd("SIZE file.txt"), "502 Command not implemented");
+    assert!(server.root.join("file.txt").exists());
+}
+
+#[test]
+fn read_only_users_are_not_offered_write_commands() {
+    let server = TestServer::start_with("help-read-only", 41280, "read_only = true");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let help = client.cmd("HELP");
+    for verb in ["STOR", "DELE", "MKD", "RMD", "MFMT"] {
+        assert!(!help.contains(verb), "{verb} offered in {help}");
+    }
+    assert!(help.contains("RETR"));
+    assert!(!client.cmd("FEAT").contains(" MFMT\r\n"));
+}
@@ -0,0 +1,54 @@
+//! End-to-end tests for per-user home directories.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn missing_home_is_created_at_first_login_when_enabled() {
+    let server = TestServer::start_with(
+        "home-auto-create",
+        41760,
+        "auto_create_home = true\numask = 0o027\n[users.alice]\nhome = \"/home/alice\"",
+    );
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("PWD"), "257 \"/home/alice\"");
+    assert!(server.root.join("home/alice").is_dir());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(server.root.join("home/alice"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+
+    // Users without a home still start at the root
+    client.login("bob", "bob123");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+}
+
+#[test]
+fn missing_home_refuses_the_login_unless_auto_created() {
+    let server = TestServer::start_with(
+        "home-missing",
+        41780,
+        "[users.alice]\nhome = \"/home/alice\"",
+    );
+    let mut client = server.connect();
+
+    client.cmd("USER alice");
+    assert_eq!(
+        client.cmd("PASS alice123"),
+        "530 Home directory unavailable"
+    );
+    assert_eq!(client.cmd("PWD"), "530 Please login with USER and PASS");
+    assert!(!server.root.join("home").exists());
+
+    std::fs::create_dir_all(server.root.join("home/alice")).unwrap();
+    client.login("alice", "alice123");
+    assert_eq!(client.cmd("PWD"), "257 \"/home/alice\"");
+}
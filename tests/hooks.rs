@@ -0,0 +1,107 @@
+//! End-to-end tests for the embedder connect, authenticate and upload hooks.
+
+mod common;
+
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::TestServer;
+
+#[test]
+fn connect_hook_sees_and_can_refuse_connections() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let seen = Arc::clone(&connections);
+    let server = TestServer::start_setup("hooks-connect", 41360, "", move |server| {
+        // Admit only the first connection
+        server.on_connect(move |addr| {
+            assert!(addr.ip().is_loopback());
+            seen.fetch_add(1, Ordering::SeqCst) == 0
+        });
+    });
+
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let mut refused = BufReader::new(TcpStream::connect(server.addr).unwrap());
+    let mut reply = String::new();
+    refused.read_line(&mut reply).unwrap();
+    assert_eq!(reply, "421 Connection refused\r\n");
+    reply.clear();
+    assert_eq!(refused.read_line(&mut reply).unwrap(), 0);
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert!(client.cmd("NOOP").starts_with("200"));
+}
+
+#[test]
+fn authenticate_hook_can_refuse_a_login() {
+    let server = TestServer::start_setup("hooks-auth", 41380, "", |server| {
+        server.on_authenticate(|_, username| username != "bob");
+    });
+
+    let mut client = server.connect();
+    client.cmd("USER bob");
+    assert_eq!(client.cmd("PASS bob123"), "530 Login refused");
+    assert_eq!(client.cmd("PWD"), "530 Please login with USER and PASS");
+
+    // The connection stays open for another attempt
+    client.login("alice", "alice123");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+}
+
+#[test]
+fn upload_hook_gets_the_stored_path_and_username() {
+    let (tx, rx) = mpsc::channel::<(PathBuf, String)>();
+    let tx = std::sync::Mutex::new(tx);
+    let server = TestServer::start_setup("hooks-upload", 42080, "", move |server| {
+        server.on_upload_complete(move |path, username| {
+            let _ = tx
+                .lock()
+                .unwrap()
+                .send((path.to_path_buf(), username.to_string()));
+        });
+    });
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.stor("report.txt", b"data"), "226 Transfer complete");
+    let (path, username) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(path, server.root.join("report.txt"));
+    assert_eq!(username, "alice");
+
+    // Failed uploads don't run it
+    client.cmd("MKD existing");
+    client.pasv();
+    client.send("STOR existing");
+    assert!(client.reply().starts_with("150"));
+    assert!(client.reply().starts_with("550"));
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+}
+
+#[test]
+fn slow_or_panicking_upload_hook_does_not_stall_the_session() {
+    let server = TestServer::start_setup("hooks-upload-slow", 42100, "", |server| {
+        server.on_upload_complete(|path, _| {
+            thread::sleep(Duration::from_secs(3));
+            panic!("post-processing {} failed", path.display());
+        });
+    });
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let started = Instant::now();
+    assert_eq!(client.stor("one.txt", b"1"), "226 Transfer complete");
+    assert_eq!(client.stor("two.txt", b"2"), "226 Transfer complete");
+    assert!(client.cmd("NOOP").starts_with("200"));
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // The session outlives the hook's panic
+    thread::sleep(Duration::from_secs(4));
+    assert!(client.cmd("NOOP").starts_with("200"));
+    assert!(server.root.join("two.txt").exists());
+}
@@ -0,0 +1,62 @@
+//! End-to-end tests for the latin-1 control channel fallback.
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::net::TcpStream;
+
+use common::{FtpClient, TestServer};
+
+/// Uploads `data` to a latin-1 encoded `name`, returning the final reply.
+fn stor_raw(client: &mut FtpClient, name: &[u8], data: &[u8]) -> String {
+    let data_addr = client.pasv();
+    client.send_raw(b"STOR ");
+    client.send_raw(name);
+    client.send_raw(b"\r\n");
+    let mut data_stream = TcpStream::connect(data_addr).unwrap();
+    let preliminary = client.reply();
+    assert!(preliminary.starts_with("150"), "{preliminary}");
+    data_stream.write_all(data).unwrap();
+    drop(data_stream);
+    client.reply()
+}
+
+#[test]
+fn latin1_names_round_trip_through_stor_and_list() {
+    let server = TestServer::start_with("latin1-on", 42160, "latin1_fallback = true");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        stor_raw(&mut client, b"caf\xe9.txt", b"coffee"),
+        "226 Transfer complete"
+    );
+    assert_eq!(fs::read(server.root.join("café.txt")).unwrap(), b"coffee");
+
+    let (data, _) = client.download("NLST");
+    assert_eq!(data, b"caf\xe9.txt\r\n");
+    let (data, _) = client.download("LIST");
+    assert!(data.windows(8).any(|name| name == b"caf\xe9.txt"));
+
+    // Names latin-1 can't represent are sent as '?'
+    fs::write(server.root.join("日本.txt"), "").unwrap();
+    let (data, _) = client.download("NLST");
+    assert!(data.windows(8).any(|name| name == b"??.txt\r\n"));
+
+    // A client that turns UTF-8 on gets UTF-8 names
+    assert_eq!(client.cmd("OPTS UTF8 ON"), "200 Always in UTF8 mode");
+    let (data, _) = client.download("NLST");
+    assert!(String::from_utf8(data).unwrap().contains("café.txt\r\n"));
+}
+
+#[test]
+fn latin1_commands_are_refused_without_the_fallback() {
+    let server = TestServer::start("latin1-off", 42180);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    client.send_raw(b"MKD caf\xe9\r\n");
+    assert_eq!(client.reply(), "500 Invalid command encoding");
+    assert!(!server.root.join("café").exists());
+}
@@ -0,0 +1,251 @@
+//! End-to-end tests for LIST arguments and listing limits.
+
+mod common;
+
+use std::fs;
+
+use common::{FtpClient, TestServer};
+use rax_ftp_server::storage::facts::FactSet;
+use rax_ftp_server::storage::validation::StorageRoot;
+use rax_ftp_server::storage::{self, parse_ftp_timestamp};
+
+/// Runs a LIST command and returns the entry names, directories other than
+/// `.` and `..` marked with `/`.
+fn list_names(client: &mut FtpClient, command: &str) -> Vec<String> {
+    let (data, reply) = client.download(command);
+    assert_eq!(reply, "226 Directory send OK");
+    let mut names: Vec<String> = String::from_utf8(data)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let name = ls_name(line);
+            if line.starts_with('d') && !matches!(name, "." | "..") {
+                format!("{name}/")
+            } else {
+                name.to_string()
+            }
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// The name at the end of an `ls -l` line, after its eight other fields.
+fn ls_name(line: &str) -> &str {
+    let mut rest = line;
+    for _ in 0..8 {
+        rest = rest.trim_start().split_once(' ').unwrap().1;
+    }
+    rest
+}
+
+#[test]
+fn list_accepts_a_directory_or_file_argument() {
+    let server = TestServer::start("list-paths", 40620);
+    fs::create_dir_all(server.root.join("docs/nested")).unwrap();
+    fs::write(server.root.join("docs/a.txt"), b"abc").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        list_names(&mut client, "LIST docs"),
+        [".", "..", "a.txt", "nested/"]
+    );
+    assert_eq!(list_names(&mut client, "LIST /docs/a.txt"), ["a.txt"]);
+    assert_eq!(list_names(&mut client, "LIST -la"), [".", "docs/"]);
+
+    client.pasv();
+    assert_eq!(
+        client.cmd("LIST missing"),
+        "550 /missing: No such file or directory"
+    );
+}
+
+#[test]
+fn list_option_flags_are_stripped_and_a_shows_hidden_files() {
+    let server = TestServer::start("list-flags", 40640);
+    fs::create_dir_all(server.root.join("dir")).unwrap();
+    fs::write(server.root.join(".profile"), b"").unwrap();
+    fs::write(server.root.join("dir/.hidden"), b"").unwrap();
+    fs::write(server.root.join("dir/shown.txt"), b"").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(list_names(&mut client, "LIST"), [".", "dir/"]);
+    assert_eq!(
+        list_names(&mut client, "LIST -la"),
+        [".", ".profile", "dir/"]
+    );
+    assert_eq!(
+        list_names(&mut client, "LIST -a /dir"),
+        [".", "..", ".hidden", "shown.txt"]
+    );
+    assert_eq!(
+        list_names(&mut client, "LIST -l /dir"),
+        [".", "..", "shown.txt"]
+    );
+}
+
+#[test]
+fn list_filters_entries_by_wildcard() {
+    let server = TestServer::start_with("list-wildcards", 41020, "list_wildcards = true");
+    fs::create_dir_all(server.root.join("logs")).unwrap();
+    fs::write(server.root.join("a.txt"), b"x").unwrap();
+    fs::write(server.root.join("b.md"), b"x").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(list_names(&mut client, "LIST -la *.txt"), ["a.txt"]);
+    assert_eq!(list_names(&mut client, "LIST /l*"), ["logs/"]);
+}
+
+#[test]
+fn long_listings_are_truncated() {
+    let server = TestServer::start_with("list-truncated", 41600, "max_listing_entries = 3");
+    for name in ["a", "b", "c", "d", "e"] {
+        fs::write(server.root.join(name), b"x").unwrap();
+    }
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let (data, reply) = client.download("NLST");
+    assert_eq!(reply, "226 Partial listing (truncated)");
+    let lines: Vec<_> = std::str::from_utf8(&data).unwrap().lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[3], "Listing truncated after 3 entries");
+
+    let (data, reply) = client.download("LIST");
+    assert_eq!(reply, "226 Partial listing (truncated)");
+    assert_eq!(
+        data.split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+            .count(),
+        4
+    );
+
+    // Short enough listings are sent whole
+    fs::create_dir(server.root.join("few")).unwrap();
+    fs::write(server.root.join("few/one"), b"x").unwrap();
+    assert_eq!(list_names(&mut client, "LIST few"), [".", "..", "one"]);
+}
+
+#[test]
+fn list_entries_look_like_ls() {
+    let server = TestServer::start("list-ls", 42520);
+    fs::create_dir(server.root.join("docs")).unwrap();
+    fs::write(server.root.join("new.txt"), b"12345").unwrap();
+    let old = fs::File::create(server.root.join("old.txt")).unwrap();
+    old.set_modified(parse_ftp_timestamp("20200229123000").unwrap())
+        .unwrap();
+    drop(old);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let (data, _) = client.download("LIST");
+    let listing = String::from_utf8(data).unwrap();
+    let line = |name: &str| {
+        listing
+            .lines()
+            .find(|line| ls_name(line) == name)
+            .unwrap_or_else(|| panic!("{name} missing from {listing}"))
+            .to_string()
+    };
+
+    let docs = line("docs");
+    assert!(docs.starts_with("drwx"), "{docs}");
+    // Recent entries show the time of day, old ones the year
+    let new = line("new.txt");
+    assert!(new.starts_with("-rw"), "{new}");
+    let fields: Vec<_> = new.split_whitespace().collect();
+    assert_eq!(&fields[1..5], ["1", "ftp", "ftp", "5"]);
+    assert!(fields[7].contains(':'), "{new}");
+    assert!(line("old.txt").ends_with(" 0 Feb 29  2020 old.txt"));
+
+    // MLSD keeps reporting UTC time-vals
+    let (data, _) = client.download("MLSD");
+    assert!(
+        String::from_utf8(data)
+            .unwrap()
+            .contains("modify=20200229123000;")
+    );
+}
+
+#[test]
+fn capped_listings_stop_reading_one_entry_past_the_cap() {
+    let dir = std::env::temp_dir().join(format!("rax-ftp-list-cap-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..50 {
+        fs::write(dir.join(format!("f{i:02}")), b"x").unwrap();
+    }
+    let root = StorageRoot::new(&dir);
+
+    // One past the cap is enough to tell the listing was cut off
+    let names = storage::list_names(&root, "/", None, None, Some(3), 3).unwrap();
+    assert_eq!(names.len(), 4);
+    let facts = storage::list_facts(&root, "/", None, FactSet::default(), Some(3), 3).unwrap();
+    assert_eq!(facts.len(), 4);
+    let entries = storage::list_directory(&root, "/", None, None, false, Some(3), 3).unwrap();
+    assert_eq!(entries.len(), 4);
+
+    let names = storage::list_names(&root, "/", None, None, None, 3).unwrap();
+    assert_eq!(names.len(), 50);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn stat_lists_a_path_on_the_control_connection_up_to_a_cap() {
+    let server = TestServer::start_with("stat-path", 41960, "max_stat_entries = 3");
+    fs::create_dir_all(server.root.join("docs")).unwrap();
+    fs::write(server.root.join("docs/a.txt"), b"abc").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let reply = client.cmd("STAT docs");
+    let lines: Vec<_> = reply.lines().collect();
+    assert_eq!(lines[0], "213-Status of docs:");
+    assert!(lines[3].starts_with(" -rw"), "{reply}");
+    assert!(lines[3].ends_with(" a.txt"), "{reply}");
+    assert_eq!(lines.last(), Some(&"213 End of status"));
+
+    for name in ["b", "c", "d"] {
+        fs::write(server.root.join("docs").join(name), b"x").unwrap();
+    }
+    let reply = client.cmd("STAT docs");
+    assert_eq!(reply.lines().count(), 6, "{reply}");
+    assert!(reply.contains(
+        "\r\n Listing truncated after 3 entries; use LIST or MLSD for the full listing\r\n"
+    ));
+
+    assert_eq!(
+        client.cmd("STAT missing"),
+        "550 /missing: No such file or directory"
+    );
+    assert!(client.cmd("STAT").starts_with("211-"));
+}
+
+#[test]
+fn listings_use_crlf_in_binary_type() {
+    let server = TestServer::start("list-type-i", 42340);
+    fs::write(server.root.join("a.txt"), b"one\ntwo\n").unwrap();
+    fs::write(server.root.join("b.txt"), b"def").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    assert_eq!(client.cmd("TYPE I"), "200 Type set to BINARY");
+
+    for command in ["LIST", "NLST", "MLSD"] {
+        let (data, reply) = client.download(command);
+        assert!(reply.starts_with("226"), "{command}: {reply}");
+        let text = String::from_utf8(data).unwrap();
+        assert!(text.ends_with("\r\n"), "{command}: {text:?}");
+        assert_eq!(
+            text.matches('\n').count(),
+            text.matches("\r\n").count(),
+            "{command} sent a bare LF: {text:?}"
+        );
+    }
+
+    // File transfers still honor TYPE I and keep bare LFs
+    let (data, _) = client.retr("a.txt");
+    assert_eq!(data, b"one\ntwo\n");
+}
@@ -0,0 +1,31 @@
+//! End-to-end tests for maintenance mode.
+
+mod common;
+
+use common::TestServer;
+
+const MAINTENANCE: &str = r#"
+maintenance_mode = true
+maintenance_message = "Back soon"
+
+[users.admin]
+admin = true
+"#;
+
+#[test]
+fn non_admin_users_are_turned_away() {
+    let server = TestServer::start_with("maintenance-user", 40280, MAINTENANCE);
+    let mut client = server.connect();
+
+    assert_eq!(client.cmd("USER alice"), "421 Back soon");
+    assert!(client.is_closed());
+}
+
+#[test]
+fn admin_users_can_still_operate() {
+    let server = TestServer::start_with("maintenance-admin", 40300, MAINTENANCE);
+    let mut client = server.connect();
+
+    client.login("admin", "admin123");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+}
@@ -0,0 +1,36 @@
+//! End-to-end tests for SIZE and the per-session metadata cache.
+
+mod common;
+
+use std::fs;
+
+use common::TestServer;
+
+#[test]
+fn repeated_queries_reuse_the_cached_metadata() {
+    let server = TestServer::start("metadata-cached", 40420);
+    fs::write(server.root.join("notes.txt"), b"hello").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("SIZE notes.txt"), "213 5");
+
+    // Changed behind the session's back: the cached lookup is still served
+    fs::write(server.root.join("notes.txt"), b"hello world").unwrap();
+    assert_eq!(client.cmd("SIZE /notes.txt"), "213 5");
+}
+
+#[test]
+fn write_commands_invalidate_the_cache() {
+    let server = TestServer::start("metadata-invalidated", 40440);
+    fs::write(server.root.join("notes.txt"), b"hello").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("SIZE notes.txt"), "213 5");
+    assert!(client.cmd("DEL notes.txt").starts_with("250"));
+    assert_eq!(
+        client.cmd("SIZE notes.txt"),
+        "550 /notes.txt: File not found"
+    );
+}
@@ -0,0 +1,39 @@
+//! Tests for per-command latency metrics in `server::metrics`.
+
+use std::time::Duration;
+
+use rax_ftp_server::server::{LatencyHistogram, Metrics};
+
+#[test]
+fn percentiles_report_bucket_upper_bounds() {
+    let mut histogram = LatencyHistogram::default();
+    assert_eq!(histogram.percentile(0.5), None);
+
+    for _ in 0..90 {
+        histogram.record(Duration::from_millis(3));
+    }
+    for _ in 0..9 {
+        histogram.record(Duration::from_millis(80));
+    }
+    histogram.record(Duration::from_secs(10));
+
+    assert_eq!(histogram.count(), 100);
+    assert_eq!(histogram.percentile(0.5), Some(Some(5)));
+    assert_eq!(histogram.percentile(0.95), Some(Some(100)));
+    assert_eq!(histogram.percentile(0.99), Some(Some(100)));
+    assert_eq!(histogram.percentile(1.0), Some(None));
+}
+
+#[test]
+fn commands_are_tracked_separately() {
+    let metrics = Metrics::default();
+    metrics.record_command("LIST", Duration::from_millis(400));
+    metrics.record_command("LIST", Duration::from_millis(600));
+    metrics.record_command("PWD", Duration::from_micros(200));
+
+    let latencies = metrics.command_latencies();
+    assert_eq!(latencies["LIST"].count(), 2);
+    assert_eq!(latencies["LIST"].mean(), Some(Duration::from_millis(500)));
+    assert_eq!(latencies["PWD"].percentile(0.5), Some(Some(1)));
+    assert!(!latencies.contains_key("RETR"));
+}
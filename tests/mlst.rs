@@ -0,0 +1,79 @@
+//! End-to-end tests for MLSD, MLST and OPTS MLST.
+
+mod common;
+
+use std::fs;
+
+use common::TestServer;
+
+#[test]
+fn mlsd_and_mlst_report_all_facts_by_default() {
+    let server = TestServer::start("mlst-default", 41200);
+    fs::create_dir_all(server.root.join("docs")).unwrap();
+    fs::write(server.root.join("docs/a.txt"), b"hello").unwrap();
+    fs::create_dir_all(server.root.join("docs/nested")).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(
+        client
+            .cmd("FEAT")
+            .contains("\r\n MLST type*;size*;modify*;\r\n")
+    );
+
+    let (listing, reply) = client.download("MLSD docs");
+    assert_eq!(reply, "226 Directory send OK");
+    let listing = String::from_utf8(listing).unwrap();
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("type=file;size=5;modify="));
+    assert!(lines[0].ends_with("; a.txt"));
+    assert!(lines[1].starts_with("type=dir;modify="));
+    assert!(lines[1].ends_with("; nested"));
+
+    let reply = client.cmd("MLST docs/a.txt");
+    assert!(reply.starts_with("250-Listing docs/a.txt\r\n type=file;size=5;modify="));
+    assert!(reply.ends_with("; /docs/a.txt\r\n250 End"));
+
+    assert_eq!(
+        client.cmd("MLST missing.txt"),
+        "550 /missing.txt: No such file or directory"
+    );
+}
+
+#[test]
+fn opts_mlst_selects_the_reported_facts() {
+    let server = TestServer::start("mlst-opts", 41220);
+    fs::write(server.root.join("a.txt"), b"hello").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        client.cmd("OPTS MLST Type;size;unique;"),
+        "200 MLST OPTS type;size;"
+    );
+    assert!(
+        client
+            .cmd("FEAT")
+            .contains("\r\n MLST type*;size*;modify;\r\n")
+    );
+
+    let (listing, _) = client.download("MLSD");
+    assert_eq!(
+        String::from_utf8(listing).unwrap(),
+        "type=file;size=5; a.txt\r\n"
+    );
+    assert_eq!(
+        client.cmd("MLST a.txt"),
+        "250-Listing a.txt\r\n type=file;size=5; /a.txt\r\n250 End"
+    );
+
+    // An empty selection turns every fact off
+    assert_eq!(client.cmd("OPTS MLST"), "200 MLST OPTS");
+    assert_eq!(
+        client.cmd("MLST a.txt"),
+        "250-Listing a.txt\r\n  /a.txt\r\n250 End"
+    );
+
+    assert_eq!(client.cmd("OPTS MODE Z"), "501 Option not understood");
+}
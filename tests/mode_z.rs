@@ -0,0 +1,89 @@
+//! End-to-end tests for MODE Z (deflate-compressed transfers).
+#![cfg(feature = "mode-z")]
+
+mod common;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use common::TestServer;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+fn inflate(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    ZlibDecoder::new(data).read_to_end(&mut output).unwrap();
+    output
+}
+
+#[test]
+fn mode_z_is_refused_unless_enabled() {
+    let server = TestServer::start("mode-z-disabled", 41300);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(!client.cmd("FEAT").contains("MODE Z"));
+    assert_eq!(
+        client.cmd("MODE Z"),
+        "504 Command not implemented for that parameter"
+    );
+    assert_eq!(client.cmd("MODE S"), "200 Mode set to S");
+}
+
+#[test]
+fn mode_z_compresses_downloads_and_listings() {
+    let server = TestServer::start_with("mode-z-retr", 41320, "mode_z = true");
+    let contents = "a line of very compressible text\n".repeat(200);
+    fs::write(server.root.join("text.txt"), &contents).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.cmd("FEAT").contains("\r\n MODE Z\r\n"));
+    assert_eq!(client.cmd("MODE z"), "200 Mode set to Z");
+
+    let (data, reply) = client.retr("text.txt");
+    assert_eq!(reply, "226 Transfer complete");
+    assert!(data.len() < contents.len() / 10);
+    assert_eq!(inflate(&data), contents.as_bytes());
+
+    let (data, _) = client.download("NLST");
+    assert_eq!(inflate(&data), b"text.txt\r\n");
+
+    // Back to stream mode the file comes through unchanged
+    assert_eq!(client.cmd("MODE S"), "200 Mode set to S");
+    let (data, _) = client.retr("text.txt");
+    assert_eq!(data, contents.as_bytes());
+}
+
+#[test]
+fn mode_z_decompresses_uploads() {
+    let server = TestServer::start_with("mode-z-stor", 41340, "mode_z = true");
+    let contents = "uploaded text\n".repeat(500);
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(contents.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    client.cmd("MODE Z");
+
+    assert_eq!(
+        client.stor("upload.txt", &compressed),
+        "226 Transfer complete"
+    );
+    assert_eq!(
+        fs::read(server.root.join("upload.txt")).unwrap(),
+        contents.as_bytes()
+    );
+
+    // Data that isn't a zlib stream is refused and nothing is stored
+    let data_addr = client.pasv();
+    client.send("STOR garbage.txt");
+    let mut data_stream = TcpStream::connect(data_addr).unwrap();
+    assert!(client.reply().starts_with("150"));
+    data_stream.write_all(b"definitely not zlib").unwrap();
+    drop(data_stream);
+    assert!(client.reply().starts_with("4"));
+    assert!(!server.root.join("garbage.txt").exists());
+}
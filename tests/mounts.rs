@@ -0,0 +1,79 @@
+//! End-to-end tests for mount points grafted onto the virtual tree.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::TestServer;
+
+/// A physical directory outside the server root, removed when dropped.
+struct MountDir(PathBuf);
+
+impl MountDir {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("rax-ftp-mount-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for MountDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn mounted_directory_is_served_under_its_virtual_path() {
+    let public = MountDir::new("public");
+    fs::write(public.0.join("readme.txt"), b"hello").unwrap();
+    let server = TestServer::start_with(
+        "mounts",
+        41080,
+        &format!("[mounts]\n\"/public\" = \"{}\"", public.0.display()),
+    );
+    fs::write(server.root.join("local.txt"), b"local").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let (names, _) = client.download("NLST /");
+    assert_eq!(String::from_utf8(names).unwrap(), "local.txt\r\npublic\r\n");
+
+    let (data, reply) = client.retr("public/readme.txt");
+    assert!(reply.starts_with("226"), "{reply}");
+    assert_eq!(data, b"hello");
+
+    assert!(client.cmd("CWD /public").starts_with("250"));
+    assert!(client.stor("upload.txt", b"new").starts_with("226"));
+    assert_eq!(fs::read(public.0.join("upload.txt")).unwrap(), b"new");
+    assert!(!server.root.join("public").exists());
+
+    assert!(client.cmd("RMD /public").starts_with("550"));
+}
+
+#[cfg(unix)]
+#[test]
+fn paths_cannot_escape_a_mounted_directory() {
+    let shared = MountDir::new("shared");
+    let server = TestServer::start_with(
+        "mounts-escape",
+        41100,
+        &format!("[mounts]\n\"/shared\" = \"{}\"", shared.0.display()),
+    );
+    // The server root is outside this mount, so a link to it escapes
+    fs::write(server.root.join("secret.txt"), b"secret").unwrap();
+    std::os::unix::fs::symlink(&server.root, shared.0.join("link")).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    client.pasv();
+
+    assert!(
+        client
+            .cmd("RETR /shared/link/secret.txt")
+            .starts_with("550")
+    );
+    assert!(client.cmd("CWD /shared/link").starts_with("550"));
+}
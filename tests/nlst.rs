@@ -0,0 +1,94 @@
+//! End-to-end tests for NLST.
+
+mod common;
+
+use std::fs;
+
+use common::TestServer;
+
+#[test]
+fn nlst_lists_a_named_subdirectory() {
+    let server = TestServer::start("nlst-subdir", 40580);
+    fs::create_dir_all(server.root.join("docs/nested")).unwrap();
+    fs::write(server.root.join("docs/b.txt"), b"b").unwrap();
+    fs::write(server.root.join("docs/a.txt"), b"a").unwrap();
+    fs::write(server.root.join("top.txt"), b"top").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let (names, reply) = client.download("NLST docs");
+    assert_eq!(reply, "226 Directory send OK");
+    assert_eq!(
+        String::from_utf8(names).unwrap(),
+        "a.txt\r\nb.txt\r\nnested\r\n"
+    );
+
+    assert!(client.cmd("CWD docs").starts_with("250"));
+    let (names, _) = client.download("NLST");
+    assert_eq!(
+        String::from_utf8(names).unwrap(),
+        "a.txt\r\nb.txt\r\nnested\r\n"
+    );
+}
+
+#[test]
+fn nlst_rejects_missing_and_non_directory_targets() {
+    let server = TestServer::start("nlst-errors", 40600);
+    fs::write(server.root.join("file.txt"), b"data").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    client.pasv();
+
+    assert_eq!(
+        client.cmd("NLST missing"),
+        "550 /missing: No such directory"
+    );
+    assert_eq!(
+        client.cmd("NLST file.txt"),
+        "550 /file.txt: Not a directory"
+    );
+}
+
+#[test]
+fn nlst_expands_wildcards_when_enabled() {
+    let server = TestServer::start_with("nlst-wildcards", 40980, "list_wildcards = true");
+    fs::create_dir_all(server.root.join("docs")).unwrap();
+    for name in [
+        "a.txt",
+        "b.txt",
+        "notes.md",
+        ".hidden.txt",
+        "docs/c.txt",
+        "docs/d.log",
+    ] {
+        fs::write(server.root.join(name), b"x").unwrap();
+    }
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let (names, reply) = client.download("NLST *.txt");
+    assert_eq!(reply, "226 Directory send OK");
+    assert_eq!(String::from_utf8(names).unwrap(), "a.txt\r\nb.txt\r\n");
+
+    let (names, _) = client.download("NLST docs/?.txt");
+    assert_eq!(String::from_utf8(names).unwrap(), "c.txt\r\n");
+
+    // No match is an empty listing, not an error
+    let (names, reply) = client.download("NLST *.zip");
+    assert_eq!(reply, "226 Directory send OK");
+    assert!(names.is_empty());
+}
+
+#[test]
+fn nlst_wildcards_are_literal_when_disabled() {
+    let server = TestServer::start("nlst-no-wildcards", 41000);
+    fs::write(server.root.join("a.txt"), b"x").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    client.pasv();
+
+    assert_eq!(
+        client.cmd("NLST *.txt"),
+        "550 Invalid path: Invalid character '*' in path"
+    );
+}
@@ -0,0 +1,139 @@
+//! End-to-end tests for passive mode listener allocation and replacement.
+
+mod common;
+
+use std::fs;
+use std::net::TcpListener;
+
+use common::TestServer;
+
+#[test]
+fn pasv_after_a_transfer_rebinds_the_same_port() {
+    let server = TestServer::start("pasv-after-transfer", 42220);
+    fs::write(server.root.join("file.txt"), "data").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    // The server closes each download's data connection, leaving it in
+    // TIME_WAIT on the passive port, which must not stop the next PASV
+    // from binding it again
+    for _ in 0..3 {
+        let (data, reply) = client.retr("file.txt");
+        assert_eq!(data, b"data");
+        assert_eq!(reply, "226 Transfer complete");
+    }
+}
+
+#[test]
+fn repeated_pasv_releases_the_previous_listener() {
+    // With a cap of one listener, PASV only keeps working if each replaces the last
+    let server = TestServer::start_with("pasv-replace", 42200, "max_passive_listeners = 1");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    assert!(client.cmd("STAT").contains(" Data connection: none\r\n"));
+
+    let first = client.pasv();
+    let stat = client.cmd("STAT");
+    assert!(
+        stat.contains(&format!(" Data connection: passive on {first}\r\n")),
+        "{stat}"
+    );
+
+    // The freed port is handed out again, which needs the old socket closed
+    for _ in 0..3 {
+        assert_eq!(client.pasv(), first);
+    }
+
+    // Switching to active mode frees it for anyone
+    let active = TcpListener::bind("127.0.0.1:0").unwrap();
+    let active_addr = active.local_addr().unwrap();
+    assert_eq!(
+        client.cmd(&format!("PORT {active_addr}")),
+        "200 PORT command successful"
+    );
+    assert!(TcpListener::bind(first).is_ok());
+    let stat = client.cmd("STAT");
+    assert!(
+        stat.contains(&format!(" Data connection: active to {active_addr}\r\n")),
+        "{stat}"
+    );
+}
+
+#[test]
+fn pasv_skips_ports_held_by_other_processes() {
+    let server = TestServer::start("pasv-skip-busy", 42280);
+    // 42280 sits in the kernel's ephemeral range, so an outgoing socket from
+    // another test may already hold it; either way PASV has to skip it
+    let busy = TcpListener::bind("127.0.0.1:42280").ok();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.pasv().port(), 42281);
+
+    // Once the port is free again it is the first choice
+    if let Some(busy) = busy {
+        drop(busy);
+        assert_eq!(client.pasv().port(), 42280);
+    }
+}
+
+#[test]
+fn pasv_beyond_max_passive_listeners_is_refused() {
+    let server = TestServer::start_with("pasv-cap", 42320, "max_passive_listeners = 2");
+    let mut clients: Vec<_> = (0..3).map(|_| server.connect()).collect();
+    for client in &mut clients {
+        client.login("alice", "alice123");
+    }
+
+    clients[0].pasv();
+    clients[1].pasv();
+    assert_eq!(
+        clients[2].cmd("PASV"),
+        "425 No available data port, try again later"
+    );
+
+    // A listener released by switching to active mode can be reused
+    let active = TcpListener::bind("127.0.0.1:0").unwrap();
+    let active_addr = active.local_addr().unwrap();
+    assert!(
+        clients[0]
+            .cmd(&format!("PORT {active_addr}"))
+            .starts_with("200")
+    );
+    clients[2].pasv();
+}
+
+#[test]
+fn idle_passive_listeners_are_reaped_at_the_cap() {
+    let server = TestServer::start_with(
+        "pasv-reap",
+        42620,
+        "max_passive_listeners = 1\npassive_listener_idle_secs = 1",
+    );
+    let mut clients: Vec<_> = (0..3).map(|_| server.connect()).collect();
+    for client in &mut clients {
+        client.login("alice", "alice123");
+    }
+
+    // A fresh listener is not idle yet, so the cap still holds
+    clients[0].pasv();
+    assert_eq!(
+        clients[1].cmd("PASV"),
+        "425 No available data port, try again later"
+    );
+
+    // Once it has gone unused long enough it makes room for another client
+    std::thread::sleep(std::time::Duration::from_millis(1200));
+    clients[1].pasv();
+    assert_eq!(
+        clients[2].cmd("PASV"),
+        "425 No available data port, try again later"
+    );
+
+    // The reaped client has to send PASV again
+    let mut reply = clients[0].cmd("NLST");
+    if reply.starts_with("150") {
+        reply = clients[0].reply();
+    }
+    assert_eq!(reply, "425 Failed to establish data connection");
+}
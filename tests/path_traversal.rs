@@ -0,0 +1,125 @@
+//! End-to-end tests that absolute and `..` paths can't reach outside the server root.
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::PathBuf;
+
+use common::{FtpClient, TestServer};
+
+/// Sends a data command after PASV and returns its final reply, skipping any 150.
+fn data_command(client: &mut FtpClient, command: &str) -> String {
+    client.pasv();
+    client.send(command);
+    let mut reply = client.reply();
+    while reply.starts_with('1') {
+        reply = client.reply();
+    }
+    reply
+}
+
+/// Uploads `data` with STOR and returns the final reply, whether or not it was accepted.
+fn upload(client: &mut FtpClient, path: &str, data: &[u8]) -> String {
+    let data_addr = client.pasv();
+    client.send(&format!("STOR {path}"));
+    let mut data_stream = TcpStream::connect(data_addr).unwrap();
+    let mut reply = client.reply();
+    if reply.starts_with("150") {
+        let _ = data_stream.write_all(data);
+        drop(data_stream);
+        reply = client.reply();
+    }
+    reply
+}
+
+/// A file beside the server root, which no client path should reach.
+fn secret_beside(server: &TestServer) -> PathBuf {
+    let secret = server.root.with_file_name(format!(
+        "{}-secret.txt",
+        server.root.file_name().unwrap().to_string_lossy()
+    ));
+    fs::write(&secret, "top secret").unwrap();
+    secret
+}
+
+#[test]
+fn absolute_and_parent_paths_stay_inside_the_root() {
+    let server = TestServer::start("traversal-unix", 42120);
+    let secret = secret_beside(&server);
+    let secret_name = secret.file_name().unwrap().to_string_lossy().to_string();
+    let outside = std::env::temp_dir().join(format!("rax-ftp-evil-{}", std::process::id()));
+    let _ = fs::remove_file(&outside);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    for command in [
+        "RETR /etc/passwd".to_string(),
+        "RETR ../../../../etc/passwd".to_string(),
+        format!("RETR ../{secret_name}"),
+        format!("RETR {}", secret.display()),
+    ] {
+        let reply = data_command(&mut client, &command);
+        assert!(reply.starts_with("550"), "{command}: {reply}");
+    }
+
+    let reply = upload(&mut client, &outside.display().to_string(), b"evil");
+    assert!(reply.starts_with("550"), "{reply}");
+
+    // `..` can't climb above the root, so this lands in the root itself
+    let outside_name = outside.file_name().unwrap().to_string_lossy().to_string();
+    let reply = upload(&mut client, &format!("../../{outside_name}"), b"evil");
+    assert_eq!(reply, "226 Transfer complete");
+    assert!(server.root.join(&outside_name).exists());
+    assert!(!outside.exists());
+
+    for path in ["/etc", "../../../etc"] {
+        let reply = client.cmd(&format!("CWD {path}"));
+        assert!(reply.starts_with("550"), "CWD {path}: {reply}");
+    }
+    client.cmd("CWD ../..");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+    assert_eq!(fs::read_to_string(&secret).unwrap(), "top secret");
+    let _ = fs::remove_file(&secret);
+}
+
+#[test]
+fn windows_style_paths_stay_inside_the_root() {
+    let server = TestServer::start("traversal-windows", 42140);
+    let secret = secret_beside(&server);
+    let secret_name = secret.file_name().unwrap().to_string_lossy().to_string();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    for command in [
+        format!("RETR ..\\{secret_name}"),
+        "RETR ..\\..\\..\\..\\Windows\\win.ini".to_string(),
+        "RETR C:\\Windows\\win.ini".to_string(),
+        "RETR \\\\server\\share\\file".to_string(),
+    ] {
+        let reply = data_command(&mut client, &command);
+        assert!(reply.starts_with("550"), "{command}: {reply}");
+    }
+
+    // Whatever a backslash path is taken to mean, it ends up inside the root
+    for path in ["..\\evil.txt", "C:\\evil.txt", "..\\..\\evil.txt"] {
+        let reply = upload(&mut client, path, b"evil");
+        assert!(
+            reply.starts_with("550") || reply.starts_with("226"),
+            "STOR {path}: {reply}"
+        );
+    }
+    assert!(!server.root.with_file_name("evil.txt").exists());
+    let parent = server.root.parent().unwrap();
+    assert!(!parent.join("..\\evil.txt").exists());
+    assert!(!parent.join("evil.txt").exists());
+
+    for path in ["C:\\Windows", "\\\\server\\share"] {
+        let reply = client.cmd(&format!("CWD {path}"));
+        assert!(reply.starts_with("550"), "CWD {path}: {reply}");
+    }
+    client.cmd("CWD ..\\..");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+    let _ = fs::remove_file(&secret);
+}
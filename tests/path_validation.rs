@@ -0,0 +1,313 @@
+//! Tests for path validation in `storage::validation`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use config::{Config, File, FileFormat};
+use rax_ftp_server::config::StartupConfig;
+use rax_ftp_server::storage::validation::{
+    normalize_path, resolve_and_validate_file_path, resolve_cwd_path, resolve_file_path,
+    validate_directory_depth, validate_path, validate_path_component, verify_path_within_bounds,
+};
+
+/// Server-wide directory depth limit used by these tests.
+const MAX_DEPTH: usize = 3;
+
+/// Startup config with a depth limit of 3, raised to 5 for alice.
+fn test_config() -> StartupConfig {
+    let toml = r#"
+        bind_address = "127.0.0.1"
+        control_port = 2121
+        data_port_min = 2122
+        data_port_max = 2222
+        server_root = "/srv/ftp"
+        buffer_size = 8192
+        connection_timeout_secs = 10
+        max_retries = 3
+        max_command_length = 512
+        max_directory_depth = 3
+        max_username_length = 64
+        min_client_port = 1024
+
+        [users.alice]
+        max_directory_depth = 5
+
+        [users.bob]
+    "#;
+    Config::builder()
+        .add_source(File::from_str(toml, FileFormat::Toml))
+        .build()
+        .and_then(|c| c.try_deserialize())
+        .expect("test config should deserialize")
+}
+
+/// Creates an empty server root unique to the calling test.
+fn temp_root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!(
+        "rax-ftp-path-validation-{}-{name}",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).expect("create temp root");
+    root
+}
+
+#[test]
+fn normalize_path_collapses_separators() {
+    assert_eq!(normalize_path("").unwrap(), "/");
+    assert_eq!(normalize_path("/").unwrap(), "/");
+    assert_eq!(normalize_path("//a///b").unwrap(), "/a/b");
+    assert_eq!(normalize_path("a/b").unwrap(), "a/b");
+    assert_eq!(normalize_path("\\a\\b").unwrap(), "/a/b");
+}
+
+#[test]
+fn normalize_path_strips_trailing_slashes() {
+    assert_eq!(normalize_path("/a/b/").unwrap(), "/a/b");
+    assert_eq!(normalize_path("a/b//").unwrap(), "a/b");
+}
+
+#[test]
+fn path_components_reject_traversal_and_special_characters() {
+    assert!(validate_path_component("..").is_err());
+    assert!(validate_path_component(".").is_err());
+    assert!(validate_path_component("").is_err());
+    for name in ["a\0b", "a<b", "a>b", "a|b", "a\"b", "a*b", "a?b", "c:x"] {
+        assert!(validate_path_component(name).is_err(), "{name:?} accepted");
+    }
+    for name in ["report.txt", "..hidden", "a..b", ".profile"] {
+        assert!(validate_path_component(name).is_ok(), "{name:?} rejected");
+    }
+}
+
+#[test]
+fn normalize_path_resolves_parent_components_in_absolute_paths() {
+    assert_eq!(normalize_path("/a/../b").unwrap(), "/b");
+    assert_eq!(normalize_path("/a/b/../../c").unwrap(), "/c");
+    assert_eq!(normalize_path("/..").unwrap(), "/");
+    assert_eq!(normalize_path("/../../etc/passwd").unwrap(), "/etc/passwd");
+    assert_eq!(normalize_path("\\a\\..\\..\\b").unwrap(), "/b");
+    assert_eq!(normalize_path("a/../b").unwrap(), "a/../b");
+}
+
+#[test]
+fn normalize_path_drops_current_directory_components_in_absolute_paths() {
+    assert_eq!(normalize_path("/a/./b").unwrap(), "/a/b");
+    assert_eq!(normalize_path("/./").unwrap(), "/");
+    assert_eq!(normalize_path("a/./b").unwrap(), "a/./b");
+    assert_eq!(
+        resolve_file_path("/docs", "./a.txt", MAX_DEPTH).unwrap(),
+        "/docs/a.txt"
+    );
+    assert_eq!(
+        resolve_cwd_path("/", "./docs/.", MAX_DEPTH).unwrap(),
+        "/docs"
+    );
+}
+
+#[test]
+fn validate_path_rejects_unresolved_traversal() {
+    for path in ["../../etc/passwd", "..\\..\\etc\\passwd", "a/../b", "a/./b"] {
+        assert!(validate_path(path, MAX_DEPTH).is_err(), "{path:?} accepted");
+    }
+}
+
+#[test]
+fn validate_path_clamps_traversal_at_root() {
+    assert_eq!(
+        validate_path("/../etc/passwd", MAX_DEPTH).unwrap(),
+        "/etc/passwd"
+    );
+    assert_eq!(validate_path("\\..\\..\\", MAX_DEPTH).unwrap(), "/");
+    // Depth is measured after resolution
+    assert_eq!(validate_path("/a/b/c/../d", MAX_DEPTH).unwrap(), "/a/b/d");
+}
+
+#[test]
+fn directory_depth_is_limited() {
+    assert!(validate_directory_depth("/", MAX_DEPTH).is_ok());
+    assert!(validate_directory_depth("/a/b/c", MAX_DEPTH).is_ok());
+    assert!(validate_directory_depth("/a/b/c/d", MAX_DEPTH).is_err());
+    assert!(validate_path("/a/b/c/", MAX_DEPTH).is_ok());
+    assert!(validate_path("/a/b/c/d", MAX_DEPTH).is_err());
+}
+
+#[test]
+fn per_user_depth_overrides_the_server_limit() {
+    let config = test_config();
+    assert_eq!(config.max_directory_depth_for(None), 3);
+    assert_eq!(config.max_directory_depth_for(Some("carol")), 3);
+    assert_eq!(config.max_directory_depth_for(Some("bob")), 3);
+    assert_eq!(config.max_directory_depth_for(Some("alice")), 5);
+
+    let alice_depth = config.max_directory_depth_for(Some("alice"));
+    assert!(validate_path("/a/b/c/d", alice_depth).is_ok());
+    assert!(validate_path("/a/b/c/d/e", alice_depth).is_ok());
+    assert!(validate_path("/a/b/c/d/e/f", alice_depth).is_err());
+    assert!(resolve_cwd_path("/a/b/c", "d", alice_depth).is_ok());
+    assert!(resolve_cwd_path("/a/b/c", "d", MAX_DEPTH).is_err());
+}
+
+#[test]
+fn file_paths_resolve_against_current_directory() {
+    assert_eq!(
+        resolve_file_path("/docs", "a.txt", MAX_DEPTH).unwrap(),
+        "/docs/a.txt"
+    );
+    assert_eq!(
+        resolve_file_path("/", "a.txt", MAX_DEPTH).unwrap(),
+        "/a.txt"
+    );
+    assert_eq!(
+        resolve_file_path("/docs", "/other/a.txt", MAX_DEPTH).unwrap(),
+        "/other/a.txt"
+    );
+    assert_eq!(
+        resolve_file_path("/docs", "\\other\\a.txt", MAX_DEPTH).unwrap(),
+        "/other/a.txt"
+    );
+    assert!(resolve_file_path("/docs", "   ", MAX_DEPTH).is_err());
+    assert_eq!(
+        resolve_file_path("/docs", "../etc/passwd", MAX_DEPTH).unwrap(),
+        "/etc/passwd"
+    );
+    assert_eq!(
+        resolve_file_path("/docs", "../../../a.txt", MAX_DEPTH).unwrap(),
+        "/a.txt"
+    );
+    assert!(resolve_file_path("/a/b", "c/d.txt", MAX_DEPTH).is_err());
+}
+
+#[test]
+fn cwd_paths_resolve_against_current_directory() {
+    assert_eq!(resolve_cwd_path("/", "docs", MAX_DEPTH).unwrap(), "/docs");
+    assert_eq!(
+        resolve_cwd_path("/docs", "sub/", MAX_DEPTH).unwrap(),
+        "/docs/sub"
+    );
+    assert_eq!(
+        resolve_cwd_path("/docs", "/other", MAX_DEPTH).unwrap(),
+        "/other"
+    );
+    assert_eq!(resolve_cwd_path("/docs", "", MAX_DEPTH).unwrap(), "/docs");
+}
+
+#[test]
+fn cwd_trailing_slashes_and_dot_resolve_consistently() {
+    assert_eq!(resolve_cwd_path("/", "docs/", MAX_DEPTH).unwrap(), "/docs");
+    assert_eq!(
+        resolve_cwd_path("/a", "/docs/", MAX_DEPTH).unwrap(),
+        "/docs"
+    );
+    assert_eq!(resolve_cwd_path("/docs", "/", MAX_DEPTH).unwrap(), "/");
+    assert_eq!(resolve_cwd_path("/docs", ".", MAX_DEPTH).unwrap(), "/docs");
+    assert_eq!(resolve_cwd_path("/docs", "./", MAX_DEPTH).unwrap(), "/docs");
+}
+
+#[test]
+fn cwd_parent_components_resolve_lexically() {
+    assert_eq!(resolve_cwd_path("/", "..", MAX_DEPTH).unwrap(), "/");
+    assert_eq!(resolve_cwd_path("/docs", "..", MAX_DEPTH).unwrap(), "/");
+    assert_eq!(resolve_cwd_path("/a/b", "../c", MAX_DEPTH).unwrap(), "/a/c");
+    assert_eq!(resolve_cwd_path("/", "a/../b", MAX_DEPTH).unwrap(), "/b");
+    assert_eq!(
+        resolve_cwd_path("/x", "/a/b/../../c", MAX_DEPTH).unwrap(),
+        "/c"
+    );
+    assert_eq!(
+        resolve_cwd_path("/docs", "../../etc", MAX_DEPTH).unwrap(),
+        "/etc"
+    );
+}
+
+#[test]
+fn bounds_check_accepts_paths_inside_root() {
+    let root = temp_root("inside");
+    fs::create_dir(root.join("docs")).unwrap();
+    fs::write(root.join("docs/a.txt"), b"a").unwrap();
+
+    assert!(verify_path_within_bounds(&root, &root.join("docs/a.txt")).is_ok());
+    assert!(verify_path_within_bounds(&root, &root.join("docs/new.txt")).is_ok());
+    assert!(verify_path_within_bounds(&root, &root).is_ok());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn bounds_check_rejects_paths_outside_root() {
+    let root = temp_root("outside");
+    let outside = root.parent().unwrap().to_path_buf();
+
+    assert!(verify_path_within_bounds(&root, &outside).is_err());
+    assert!(verify_path_within_bounds(&root, &root.join("..")).is_err());
+    assert!(verify_path_within_bounds(&root, &root.join("../escape.txt")).is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn bounds_check_rejects_symlinks_escaping_root() {
+    let root = temp_root("symlink");
+    let target = temp_root("symlink-target");
+    fs::write(target.join("secret.txt"), b"secret").unwrap();
+    std::os::unix::fs::symlink(&target, root.join("link")).unwrap();
+
+    assert!(verify_path_within_bounds(&root, &root.join("link/secret.txt")).is_err());
+    assert!(verify_path_within_bounds(&root, &root.join("link/new.txt")).is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+    fs::remove_dir_all(&target).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn bounds_check_rejects_escaping_symlinks_mid_path() {
+    let root = temp_root("symlink-mid");
+    let target = temp_root("symlink-mid-target");
+    fs::create_dir_all(root.join("a")).unwrap();
+    std::os::unix::fs::symlink(&target, root.join("a/link")).unwrap();
+    // A symlink that stays inside the root is fine
+    std::os::unix::fs::symlink(root.join("a"), root.join("alias")).unwrap();
+
+    assert!(verify_path_within_bounds(&root, &root.join("a/link/b/new.txt")).is_err());
+    assert!(verify_path_within_bounds(&root, &root.join("alias/new.txt")).is_ok());
+
+    fs::remove_dir_all(&root).unwrap();
+    fs::remove_dir_all(&target).unwrap();
+}
+
+#[test]
+fn full_resolution_maps_virtual_paths_into_root() {
+    let root = temp_root("resolve");
+    fs::create_dir(root.join("docs")).unwrap();
+
+    let (real, virtual_path) =
+        resolve_and_validate_file_path(&root, "/docs", "a.txt", MAX_DEPTH).unwrap();
+    assert_eq!(virtual_path, "/docs/a.txt");
+    assert_eq!(real, root.join("docs/a.txt"));
+
+    let (real, virtual_path) =
+        resolve_and_validate_file_path(&root, "/docs", "../../x", MAX_DEPTH).unwrap();
+    assert_eq!(virtual_path, "/x");
+    assert_eq!(real, root.join("x"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn device_names_are_ordinary_filenames_on_unix() {
+    for name in ["aux", "aux.txt", "CON", "nul.log", "LPT1"] {
+        assert!(validate_path_component(name).is_ok(), "{name} rejected");
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn device_names_are_rejected_on_windows() {
+    for name in ["aux", "aux.txt", "CON", "nul.log", "LPT1", "com1 .txt"] {
+        assert!(validate_path_component(name).is_err(), "{name} accepted");
+    }
+    assert!(validate_path_component("auxiliary.txt").is_ok());
+}
@@ -0,0 +1,109 @@
+//! End-to-end tests for commands accepted before login.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn type_before_login_carries_into_the_session() {
+    let server = TestServer::start("pre-auth-type", 40680);
+    let mut client = server.connect();
+
+    assert_eq!(client.cmd("TYPE A"), "200 Type set to ASCII");
+    client.login("alice", "alice123");
+
+    let stat = client.cmd("STAT");
+    assert!(stat.contains(" TYPE: ASCII"), "{stat}");
+}
+
+#[test]
+fn session_commands_still_require_login() {
+    let server = TestServer::start("pre-auth-rejected", 40700);
+    let mut client = server.connect();
+
+    assert_eq!(client.cmd("PWD"), "530 Please login with USER and PASS");
+    assert_eq!(client.cmd("PASV"), "530 Please login with USER and PASS");
+}
+
+#[test]
+fn capability_queries_work_before_login() {
+    let server = TestServer::start("pre-auth-feat", 40720);
+    let mut client = server.connect();
+
+    let feat = client.cmd("FEAT");
+    assert!(feat.starts_with("211-") && feat.contains(" SIZE"), "{feat}");
+    assert!(client.cmd("SYST").starts_with("215 UNIX Type: L8"));
+    assert_eq!(client.cmd("NOOP"), "200 NOOP ok");
+
+    // The connection is still usable for logging in afterwards
+    client.login("alice", "alice123");
+}
+
+#[test]
+fn quit_before_login_closes_the_connection() {
+    let server = TestServer::start("pre-auth-quit", 40740);
+    let mut client = server.connect();
+
+    assert_eq!(client.cmd("QUIT"), "221 Goodbye");
+    assert!(client.is_closed());
+}
+
+#[test]
+fn pass_without_a_valid_user_is_refused() {
+    // More refusals than the login limit allows, which is tested separately
+    let server = TestServer::start_with("pre-auth-pass", 40840, "max_login_attempts = 0");
+    let mut client = server.connect();
+
+    assert_eq!(client.cmd("PASS foo"), "530 Username not provided");
+
+    // A rejected USER must not leave an earlier, valid one in place
+    assert!(client.cmd("USER alice").starts_with("331"));
+    assert_eq!(client.cmd("USER nosuch"), "530 Unknown user 'nosuch'");
+    assert_eq!(client.cmd("PASS alice123"), "530 Username not provided");
+    assert_eq!(client.cmd("PWD"), "530 Please login with USER and PASS");
+
+    client.login("alice", "alice123");
+}
+
+#[test]
+fn logged_out_sessions_get_the_same_login_reply() {
+    let server = TestServer::start("pre-auth-logout", 41440);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.cmd("LOGOUT").starts_with("221"));
+    assert_eq!(client.cmd("PWD"), "530 Please login with USER and PASS");
+    assert_eq!(client.cmd("LIST"), "530 Please login with USER and PASS");
+}
+
+#[test]
+fn opts_utf8_is_accepted_before_login() {
+    let server = TestServer::start("pre-auth-utf8", 41480);
+    let mut client = server.connect();
+
+    assert!(client.cmd("FEAT").contains("\r\n UTF8\r\n"));
+    assert_eq!(client.cmd("OPTS UTF8 ON"), "200 Always in UTF8 mode");
+    assert_eq!(client.cmd("OPTS UTF8 MAYBE"), "501 Option not understood");
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("MKD café"), "257 \"/café\" created");
+}
+
+#[test]
+fn commands_sent_in_one_write_are_all_answered() {
+    let server = TestServer::start("pre-auth-pipelined", 41500);
+    let mut client = server.connect();
+
+    // The PWD arrives with the login, before the session handler takes over
+    client.send_raw(b"USER alice\r\nPASS alice123\r\nPWD\r\n");
+    assert!(client.reply().starts_with("331"));
+    assert!(client.reply().starts_with("230"));
+    assert_eq!(client.reply(), "257 \"/\"");
+
+    // A command split across writes is answered once it is complete
+    client.send_raw(b"NO");
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    client.send_raw(b"OP\r\nSYST\r\n");
+    assert_eq!(client.reply(), "200 NOOP ok");
+    assert!(client.reply().starts_with("215"));
+}
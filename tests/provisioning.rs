@@ -0,0 +1,177 @@
+//! Tests for adding accounts with `auth::add_user`.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::TestServer;
+use config::{Config, File, FileFormat};
+use rax_ftp_server::auth::{add_user, validate_password, validate_user, verify_password};
+use rax_ftp_server::config::ServerConfig;
+use rax_ftp_server::error::{AuthError, ProvisioningError};
+
+fn credentials_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "rax-ftp-provisioning-{}-{name}",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn weak_passwords_are_refused_and_nothing_is_written() {
+    let path = credentials_path("weak");
+    let config = server_config("");
+
+    for password in ["Short1", "alllowercase1", "NoDigitsHere"] {
+        assert!(matches!(
+            add_user(&path, "carol", password, &config.startup),
+            Err(ProvisioningError::Rejected(AuthError::WeakPassword(_)))
+        ));
+    }
+    let symbols = server_config("[password_policy]\nrequire_symbol = true");
+    assert!(matches!(
+        add_user(&path, "carol", "Carol12345", &symbols.startup),
+        Err(ProvisioningError::Rejected(AuthError::WeakPassword(_)))
+    ));
+    assert!(!path.exists());
+}
+
+#[test]
+fn credentials_that_could_never_log_in_are_refused() {
+    let path = credentials_path("names");
+    let config = server_config("");
+
+    // Names USER refuses, plus those a username:hash line can't hold
+    for username in [
+        "car@ol",
+        "car#ol",
+        "car,ol",
+        "car%ol",
+        "1carol",
+        "anonymous",
+        "car:ol",
+        " carol",
+    ] {
+        assert!(
+            matches!(
+                add_user(&path, username, "Carol12345", &config.startup),
+                Err(ProvisioningError::Rejected(AuthError::InvalidUsername(_)))
+            ),
+            "{username:?}"
+        );
+    }
+    let too_long = "c".repeat(65);
+    for username in ["", "car\nol", too_long.as_str()] {
+        assert!(matches!(
+            add_user(&path, username, "Carol12345", &config.startup),
+            Err(ProvisioningError::Rejected(AuthError::MalformedInput(_)))
+        ));
+    }
+
+    // Passwords longer than max_username_length are refused at login too
+    let too_long = format!("Carol1{}", "x".repeat(59));
+    for password in ["Carol12345\nmallory:x", " Carol12345", too_long.as_str()] {
+        assert!(matches!(
+            add_user(&path, "carol", password, &config.startup),
+            Err(ProvisioningError::Rejected(AuthError::MalformedInput(_)))
+        ));
+    }
+    assert!(!path.exists());
+}
+
+#[test]
+fn added_users_are_hashed_can_log_in_and_are_not_overwritten() {
+    let path = credentials_path("login");
+    fs::write(&path, "# managed by add-user\ndave:Dave12345").unwrap();
+    let config = server_config("");
+
+    add_user(&path, "carol", "Carol12345", &config.startup).unwrap();
+    assert!(matches!(
+        add_user(&path, "dave", "Other12345", &config.startup),
+        Err(ProvisioningError::UserExists(u)) if u == "dave"
+    ));
+    let contents = fs::read_to_string(&path).unwrap();
+    let carol = contents
+        .strip_prefix("# managed by add-user\ndave:Dave12345\ncarol:")
+        .expect("carol appended after the existing lines");
+    assert!(carol.starts_with("$argon2id$"), "{carol}");
+    assert!(!contents.contains("Carol12345"));
+    assert!(verify_password(carol.trim_end(), "Carol12345"));
+    assert!(!verify_password(carol.trim_end(), "Carol12346"));
+
+    let server = TestServer::start_with(
+        "provisioned-login",
+        42400,
+        &format!(
+            "auth_backends = [\"file\"]\ncredentials_file = \"{}\"",
+            path.display()
+        ),
+    );
+    // Hand-written plaintext entries keep working next to hashed ones
+    for (user, password) in [("carol", "Carol12345"), ("dave", "Dave12345")] {
+        let mut client = server.connect();
+        client.login(user, password);
+    }
+    let mut client = server.connect();
+    client.cmd("USER carol");
+    assert!(client.cmd("PASS $argon2id$").starts_with("530"));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn an_unreadable_credentials_file_stops_startup() {
+    let path = credentials_path("missing");
+    let config = server_config(&format!(
+        "auth_backends = [\"file\", \"builtin\"]\ncredentials_file = \"{}\"",
+        path.display()
+    ));
+
+    // The builtin accounts alone would hide the missing file
+    let error = config.startup.load_authenticator().unwrap_err();
+    assert!(error.to_string().contains("credentials file"), "{error}");
+
+    fs::write(&path, "carol:Carol12345\n").unwrap();
+    config.startup.load_authenticator().unwrap();
+    assert!(validate_password("carol", "Carol12345", &config.startup).is_ok());
+    assert!(validate_password("alice", "alice123", &config.startup).is_ok());
+
+    // A later load picks up new accounts, and a failed one keeps the old chain
+    add_user(&path, "erin", "Erin123456", &config.startup).unwrap();
+    assert!(validate_user("erin", &config.startup).is_err());
+    config.startup.load_authenticator().unwrap();
+    assert!(validate_user("erin", &config.startup).is_ok());
+    fs::remove_file(&path).unwrap();
+    assert!(config.startup.load_authenticator().is_err());
+    assert!(validate_user("erin", &config.startup).is_ok());
+}
+
+/// Server config with `extra` TOML on top of the minimum required settings.
+fn server_config(extra: &str) -> ServerConfig {
+    let toml = format!(
+        r#"
+        bind_address = "127.0.0.1"
+        control_port = 2121
+        data_port_min = 2122
+        data_port_max = 2222
+        server_root = "ftp"
+        max_clients = 10
+        max_file_size_mb = 10
+        buffer_size = 8192
+        connection_timeout_secs = 10
+        max_retries = 3
+        max_command_length = 512
+        max_directory_depth = 3
+        max_username_length = 64
+        min_client_port = 1024
+        {extra}
+        "#
+    );
+    Config::builder()
+        .add_source(File::from_str(&toml, FileFormat::Toml))
+        .build()
+        .and_then(|c| c.try_deserialize())
+        .expect("test config should deserialize")
+}
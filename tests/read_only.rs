@@ -0,0 +1,41 @@
+//! End-to-end tests for read-only mode.
+
+mod common;
+
+use std::fs;
+
+use common::TestServer;
+
+#[test]
+fn read_only_users_cannot_write() {
+    let server = TestServer::start_with("read-only", 40880, "[users.bob]\nread_only = true");
+    fs::write(server.root.join("keep.txt"), b"data").unwrap();
+    let mut client = server.connect();
+    client.login("bob", "bob123");
+
+    client.pasv();
+    assert_eq!(client.cmd("STOR new.txt"), "550 Permission denied");
+    assert_eq!(client.cmd("DELE keep.txt"), "550 Permission denied");
+    assert_eq!(client.cmd("MKD dir"), "550 Permission denied");
+    let (data, reply) = client.retr("keep.txt");
+    assert!(reply.starts_with("226"), "{reply}");
+    assert_eq!(data, b"data");
+
+    // Other users keep write access
+    let mut alice = server.connect();
+    alice.login("alice", "alice123");
+    assert!(alice.cmd("MKD dir").starts_with("257"));
+}
+
+#[test]
+fn rejection_code_is_configurable() {
+    let server = TestServer::start_with(
+        "read-only-code",
+        40900,
+        "read_only = true\nread_only_reply_code = 553",
+    );
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("MKD dir"), "553 Permission denied");
+}
@@ -0,0 +1,40 @@
+//! End-to-end tests pinning the completion code of each command (RFC 959).
+
+mod common;
+
+use common::TestServer;
+
+fn code(reply: &str) -> &str {
+    &reply[..3]
+}
+
+#[test]
+fn file_actions_complete_with_250_and_created_paths_with_257() {
+    let server = TestServer::start("reply-codes-file-actions", 41800);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(code(&client.cmd("MKD docs")), "257");
+    assert_eq!(code(&client.cmd("XMKD more")), "257");
+    assert_eq!(code(&client.cmd("CWD docs")), "250");
+    assert_eq!(code(&client.cmd("PWD")), "257");
+    assert_eq!(code(&client.cmd("CWD /")), "250");
+    assert_eq!(code(&client.cmd("RMD more")), "250");
+
+    std::fs::write(server.root.join("old.txt"), b"data").unwrap();
+    assert_eq!(code(&client.cmd("DELE old.txt")), "250");
+}
+
+#[test]
+fn data_transfers_complete_with_226() {
+    let server = TestServer::start("reply-codes-transfers", 41820);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(code(&client.stor("up.txt", b"data")), "226");
+    assert_eq!(code(&client.retr("up.txt").1), "226");
+    assert_eq!(code(&client.download("LIST").1), "226");
+    assert_eq!(code(&client.download("NLST").1), "226");
+    assert_eq!(code(&client.download("MLSD").1), "226");
+    assert_eq!(code(&client.cmd("ABOR")), "226");
+}
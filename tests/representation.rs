@@ -0,0 +1,35 @@
+//! End-to-end tests for the TYPE/MODE/STRU handshake sent by mainframe clients.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn supported_parameters_are_accepted_with_200() {
+    let server = TestServer::start("representation-accepted", 41860);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.cmd("TYPE A"), "200 Type set to ASCII");
+    assert_eq!(client.cmd("TYPE I"), "200 Type set to BINARY");
+    assert_eq!(client.cmd("TYPE L 8"), "200 Type set to BINARY");
+    assert_eq!(client.cmd("TYPE L8"), "200 Type set to BINARY");
+    assert_eq!(client.cmd("MODE S"), "200 Mode set to S");
+    assert_eq!(client.cmd("STRU F"), "200 Structure set to F");
+    assert_eq!(client.cmd("stru f"), "200 Structure set to F");
+}
+
+#[test]
+fn unsupported_parameters_are_refused_with_504_not_500() {
+    let server = TestServer::start("representation-refused", 41880);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    for command in ["TYPE E", "TYPE L 7", "MODE B", "MODE C", "STRU R", "STRU P"] {
+        assert_eq!(
+            client.cmd(command),
+            "504 Command not implemented for that parameter",
+            "{command}"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! End-to-end tests for resuming downloads with REST.
+
+mod common;
+
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use common::TestServer;
+
+#[test]
+fn rest_on_a_growing_file_sends_only_the_new_bytes() {
+    let server = TestServer::start("restart-growing", 42060);
+    let path = server.root.join("app.log");
+    fs::write(&path, "hello").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let (data, reply) = client.retr("app.log");
+    assert_eq!(data, b"hello");
+    assert_eq!(reply, "226 Transfer complete");
+
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(b" world")
+        .unwrap();
+    assert!(client.cmd("REST 5").starts_with("350"));
+    let (data, reply) = client.retr("app.log");
+    assert_eq!(data, b" world");
+    assert_eq!(reply, "226 Transfer complete");
+
+    // Nothing new since the last read is an empty transfer, not an error
+    assert!(client.cmd("REST 11").starts_with("350"));
+    let (data, reply) = client.retr("app.log");
+    assert!(data.is_empty());
+    assert_eq!(reply, "226 Transfer complete");
+}
+
+#[test]
+fn aborted_download_resumes_with_rest() {
+    let server = TestServer::start("restart-aborted", 42360);
+    // Large enough that the server can't have sent it all into socket buffers
+    let original: Vec<u8> = (0..8 * 1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+    fs::write(server.root.join("big.bin"), &original).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let data_addr = client.pasv();
+    client.send("RETR big.bin");
+    let mut data_stream = TcpStream::connect(data_addr).unwrap();
+    assert!(client.reply().starts_with("150"));
+    let mut received = vec![0; original.len() / 2];
+    data_stream.read_exact(&mut received).unwrap();
+    client.send("ABOR");
+    drop(data_stream);
+    // Loopback socket buffers can swallow the rest of the file before ABOR
+    // lands, in which case the transfer has already completed
+    let reply = client.reply();
+    assert!(
+        reply.starts_with("426") || reply == "226 Transfer complete",
+        "{reply}"
+    );
+    assert_eq!(client.reply(), "226 ABOR command successful");
+
+    assert!(
+        client
+            .cmd(&format!("REST {}", received.len()))
+            .starts_with("350")
+    );
+    let (rest, reply) = client.retr("big.bin");
+    assert_eq!(reply, "226 Transfer complete");
+    received.extend(rest);
+    assert!(received == original, "reassembled download differs");
+}
+
+#[test]
+fn rest_is_refused_in_ascii_mode() {
+    let server = TestServer::start("restart-ascii", 42380);
+    fs::write(server.root.join("notes.txt"), "one\ntwo\n").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    // Offsets count file bytes, which differ from the CRLF bytes sent in TYPE A
+    assert!(client.cmd("TYPE A").starts_with("200"));
+    assert_eq!(
+        client.cmd("REST 4"),
+        "504 Restart not supported in ASCII mode, use TYPE I"
+    );
+    assert!(client.cmd("REST 0").starts_with("350"));
+
+    // Also when TYPE A is only chosen between REST and RETR
+    assert!(client.cmd("TYPE I").starts_with("200"));
+    assert!(client.cmd("REST 4").starts_with("350"));
+    assert!(client.cmd("TYPE A").starts_with("200"));
+    client.pasv();
+    assert_eq!(
+        client.cmd("RETR notes.txt"),
+        "504 Restart not supported in ASCII mode, use TYPE I"
+    );
+    let (data, _) = client.retr("notes.txt");
+    assert_eq!(data, b"one\r\ntwo\r\n");
+}
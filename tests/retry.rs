@@ -0,0 +1,54 @@
+//! Tests for retrying transient storage failures.
+
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+
+use rax_ftp_server::storage::retry::{TRANSIENT_ERRORS, with_retries};
+
+/// An operation that fails with `kind` `failures` times, then succeeds
+fn failing(kind: ErrorKind, failures: usize) -> impl FnMut() -> io::Result<usize> {
+    let mut calls = 0;
+    move || {
+        calls += 1;
+        if calls <= failures {
+            Err(io::Error::from(kind))
+        } else {
+            Ok(calls)
+        }
+    }
+}
+
+#[test]
+fn transient_failures_are_retried_until_they_succeed() {
+    let op = failing(ErrorKind::PermissionDenied, 2);
+    let calls = with_retries(op, TRANSIENT_ERRORS, 3, Duration::ZERO).unwrap();
+    assert_eq!(calls, 3);
+}
+
+#[test]
+fn retries_give_up_after_max_retries() {
+    let op = failing(ErrorKind::Interrupted, 4);
+    let error = with_retries(op, TRANSIENT_ERRORS, 3, Duration::ZERO).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Interrupted);
+
+    let op = failing(ErrorKind::Interrupted, 1);
+    let error = with_retries(op, TRANSIENT_ERRORS, 0, Duration::ZERO).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Interrupted);
+}
+
+#[test]
+fn other_failures_are_not_retried() {
+    let mut calls = 0;
+    let error = with_retries(
+        || {
+            calls += 1;
+            Err::<(), _>(io::Error::from(ErrorKind::NotFound))
+        },
+        TRANSIENT_ERRORS,
+        3,
+        Duration::ZERO,
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+    assert_eq!(calls, 1);
+}
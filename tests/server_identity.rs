@@ -0,0 +1,38 @@
+//! End-to-end tests for advertising or hiding the server software.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn identity_is_advertised_by_default() {
+    let server = TestServer::start("identity-shown", 40360);
+    let mut client = server.connect();
+    assert!(client.greeting.contains("RAX FTP Server"));
+
+    client.login("alice", "alice123");
+    assert!(
+        client
+            .cmd("SYST")
+            .starts_with("215 UNIX Type: L8 (RAX FTP Server")
+    );
+    assert!(client.cmd("STAT").starts_with("211-RAX FTP Server"));
+    assert!(client.cmd("FEAT").starts_with("211-RAX FTP Server"));
+}
+
+#[test]
+fn hidden_identity_uses_generic_wording() {
+    let server = TestServer::start_with("identity-hidden", 40380, "hide_server_identity = true");
+    let mut client = server.connect();
+    assert_eq!(client.greeting, "220 Service ready");
+
+    client.login("alice", "alice123");
+    assert_eq!(client.cmd("SYST"), "215 UNIX Type: L8");
+    let stat = client.cmd("STAT");
+    let feat = client.cmd("FEAT");
+    assert!(stat.starts_with("211-Status:"), "{stat}");
+    assert!(feat.contains(" MDTM"), "{feat}");
+    for reply in [stat, feat] {
+        assert!(!reply.contains("RAX"), "identity leaked: {reply}");
+    }
+}
@@ -0,0 +1,79 @@
+//! Tests for resolving `server_root` at startup.
+
+use std::fs;
+use std::path::Path;
+
+use config::{Config, File, FileFormat};
+use rax_ftp_server::config::StartupConfig;
+
+/// Startup config with the given server root.
+fn config_with_root(root: &str) -> StartupConfig {
+    let toml = format!(
+        r#"
+        bind_address = "127.0.0.1"
+        control_port = 2121
+        data_port_min = 2122
+        data_port_max = 2222
+        server_root = "{root}"
+        buffer_size = 8192
+        connection_timeout_secs = 10
+        max_retries = 3
+        max_command_length = 512
+        max_directory_depth = 3
+        max_username_length = 64
+        min_client_port = 1024
+        "#
+    );
+    Config::builder()
+        .add_source(File::from_str(&toml, FileFormat::Toml))
+        .build()
+        .and_then(|c| c.try_deserialize())
+        .expect("test config should deserialize")
+}
+
+#[test]
+fn relative_root_is_created_and_made_absolute() {
+    let relative = format!("target/rax-ftp-relative-root-{}/./ftp", std::process::id());
+    let _ = fs::remove_dir_all(Path::new(&relative).parent().unwrap());
+    let mut config = config_with_root(&relative);
+    config.auto_create_root = true;
+
+    config.resolve_server_root().unwrap();
+    let resolved = config.server_root_path();
+    assert!(resolved.is_absolute());
+    assert!(resolved.is_dir());
+    assert_eq!(
+        resolved,
+        std::env::current_dir()
+            .unwrap()
+            .join(&relative)
+            .canonicalize()
+            .unwrap()
+    );
+    assert!(!config.server_root_str().contains("/./"));
+    let _ = fs::remove_dir_all(resolved.parent().unwrap());
+}
+
+#[test]
+fn root_that_cannot_be_created_is_an_error() {
+    let file = std::env::temp_dir().join(format!("rax-ftp-root-file-{}", std::process::id()));
+    fs::write(&file, "not a directory").unwrap();
+    let mut config = config_with_root(&format!("{}/ftp", file.display()));
+    config.auto_create_root = true;
+
+    assert!(config.resolve_server_root().is_err());
+    let _ = fs::remove_file(&file);
+}
+
+#[test]
+fn missing_root_is_an_error_unless_auto_created() {
+    let missing = std::env::temp_dir().join(format!("rax-ftp-missing-root-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&missing);
+    let mut config = config_with_root(&missing.display().to_string());
+
+    // A typo in server_root must not turn into a new, empty tree
+    let error = config.resolve_server_root().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    assert!(error.to_string().contains("auto_create_root"), "{error}");
+    assert!(!missing.exists());
+}
@@ -0,0 +1,70 @@
+//! End-to-end tests for the session time and transfer limits.
+
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use common::TestServer;
+
+#[test]
+fn session_is_closed_at_the_time_limit_despite_activity() {
+    let server = TestServer::start_with("session-limit", 41160, "max_session_duration_secs = 1");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    // Activity does not extend the limit, unlike an idle timeout
+    for _ in 0..3 {
+        assert!(client.cmd("NOOP").starts_with("200"));
+        thread::sleep(Duration::from_millis(200));
+    }
+
+    assert_eq!(client.reply(), "421 Session time limit reached");
+    assert!(client.is_closed());
+}
+
+#[test]
+fn per_user_limit_applies_only_to_that_user() {
+    let server = TestServer::start_with(
+        "session-limit-user",
+        41180,
+        "[users.bob]\nmax_session_duration_secs = 1",
+    );
+    let mut bob = server.connect();
+    bob.login("bob", "bob123");
+    let mut alice = server.connect();
+    alice.login("alice", "alice123");
+
+    assert_eq!(bob.reply(), "421 Session time limit reached");
+    assert!(alice.cmd("NOOP").starts_with("200"));
+}
+
+#[test]
+fn transfers_stop_at_the_session_limit() {
+    let server = TestServer::start_with(
+        "session-transfer-limit",
+        42020,
+        "max_transfers_per_session = 2\n[users.bob]\nmax_transfers_per_session = 0",
+    );
+    std::fs::write(server.root.join("a.txt"), b"a").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(client.retr("a.txt").1, "226 Transfer complete");
+    assert_eq!(client.stor("b.txt", b"b"), "226 Transfer complete");
+    for command in ["RETR a.txt", "STOR c.txt"] {
+        assert_eq!(
+            client.cmd(command),
+            "450 Transfer limit reached for this session"
+        );
+    }
+    // Listings are not counted
+    assert!(client.download("NLST").1.starts_with("226"));
+
+    // Bob's override lifts the limit
+    let mut bob = server.connect();
+    bob.login("bob", "bob123");
+    for _ in 0..3 {
+        assert_eq!(bob.retr("a.txt").1, "226 Transfer complete");
+    }
+}
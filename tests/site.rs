@@ -0,0 +1,93 @@
+//! End-to-end tests for SITE subcommands.
+
+mod common;
+
+use std::fs;
+
+use common::TestServer;
+
+#[test]
+fn site_diskusage_reports_usage_and_an_unlimited_quota() {
+    let mount = std::env::temp_dir().join(format!("rax-ftp-e2e-{}-site-mount", std::process::id()));
+    let _ = fs::remove_dir_all(&mount);
+    fs::create_dir_all(&mount).unwrap();
+    fs::write(mount.join("shared.bin"), [0u8; 50]).unwrap();
+    let server = TestServer::start_with(
+        "site-diskusage",
+        41400,
+        &format!("[mounts]\n\"/public\" = \"{}\"", mount.display()),
+    );
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        client.cmd("SITE DISKUSAGE"),
+        "200 Used: 50 bytes, Quota: unlimited"
+    );
+
+    fs::create_dir_all(server.root.join("docs")).unwrap();
+    fs::write(server.root.join("a.txt"), [0u8; 100]).unwrap();
+    fs::write(server.root.join("docs/b.txt"), [0u8; 25]).unwrap();
+    assert_eq!(
+        client.cmd("site diskusage"),
+        "200 Used: 175 bytes, Quota: unlimited"
+    );
+
+    assert_eq!(
+        client.cmd("SITE CHMOD 644 a.txt"),
+        "500 Unknown SITE command"
+    );
+    let _ = fs::remove_dir_all(&mount);
+}
+
+#[test]
+fn site_diskusage_measures_the_users_home() {
+    let server = TestServer::start_with(
+        "site-diskusage-home",
+        42500,
+        "[users.bob]\nhome = \"/home/bob\"",
+    );
+    fs::create_dir_all(server.root.join("home/bob/docs")).unwrap();
+    fs::write(server.root.join("home/bob/docs/a.txt"), [0u8; 30]).unwrap();
+    fs::write(server.root.join("other.bin"), [0u8; 1000]).unwrap();
+
+    // Files outside the home aren't the user's, even if reachable
+    let mut client = server.connect();
+    client.login("bob", "bob123");
+    client.cmd("CWD /");
+    assert_eq!(
+        client.cmd("SITE DISKUSAGE"),
+        "200 Used: 30 bytes, Quota: unlimited"
+    );
+
+    // Without a home, everything counts
+    client.login("alice", "alice123");
+    assert_eq!(
+        client.cmd("SITE DISKUSAGE"),
+        "200 Used: 1030 bytes, Quota: unlimited"
+    );
+}
+
+#[test]
+fn site_history_lists_recent_verbs_for_admins_only() {
+    let server = TestServer::start_with("site-history", 41840, "[users.admin]\nadmin = true");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    assert_eq!(client.cmd("SITE HISTORY"), "550 Permission denied");
+
+    client.login("admin", "admin123");
+    client.cmd("CWD missing");
+    assert_eq!(
+        client.cmd("SITE HISTORY"),
+        "200-Recent commands:\r\n USER 331\r\n PASS 230\r\n SITE 550\r\n USER 331\r\n \
+         PASS 230\r\n CWD 550\r\n200 End"
+    );
+
+    // Passwords are never recorded, and only the last 20 commands are kept
+    for _ in 0..25 {
+        client.cmd("NOOP");
+    }
+    let history = client.cmd("SITE HISTORY");
+    assert!(!history.contains("admin123"));
+    assert_eq!(history.matches(" NOOP 200").count(), 20, "{history}");
+}
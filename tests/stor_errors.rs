@@ -0,0 +1,69 @@
+//! End-to-end tests for STOR error replies.
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+use common::TestServer;
+use socket2::SockRef;
+
+#[test]
+fn stor_into_a_file_reports_not_a_directory() {
+    let server = TestServer::start("stor-into-file", 40240);
+    fs::write(server.root.join("existing.txt"), b"data").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    client.pasv();
+    assert_eq!(
+        client.cmd("STOR existing.txt/child.txt"),
+        "150 Opening BINARY mode data connection for file transfer"
+    );
+    assert_eq!(client.reply(), "550 /existing.txt: Not a directory");
+
+    assert!(!server.root.join("existing.txt.tmp").exists());
+    assert_eq!(fs::read(server.root.join("existing.txt")).unwrap(), b"data");
+}
+
+#[test]
+fn stor_into_a_missing_directory_reports_it() {
+    let server = TestServer::start("stor-missing-dir", 40260);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    client.pasv();
+    assert!(client.cmd("STOR missing/child.txt").starts_with("150"));
+    assert_eq!(client.reply(), "550 /missing: No such directory");
+}
+
+#[test]
+fn aborted_upload_leaves_no_files_behind() {
+    let server = TestServer::start("stor-aborted", 40820);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let data_addr = client.pasv();
+    client.send("STOR partial.bin");
+    let mut data_stream = TcpStream::connect(data_addr).expect("connect data");
+    assert!(client.reply().starts_with("150"));
+    data_stream.write_all(&[1u8; 64 * 1024]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    // Reset the connection instead of closing it cleanly, as a dropped link would
+    SockRef::from(&data_stream)
+        .set_linger(Some(Duration::ZERO))
+        .unwrap();
+    drop(data_stream);
+
+    let reply = client.reply();
+    assert!(reply.starts_with("426"), "{reply}");
+    let leftovers: Vec<_> = fs::read_dir(&server.root)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert!(leftovers.is_empty(), "left behind: {leftovers:?}");
+}
@@ -0,0 +1,24 @@
+//! Tests for recognizing an unreachable storage backend.
+
+use std::io;
+
+use rax_ftp_server::error::StorageError;
+
+#[test]
+fn lost_mounts_count_as_backend_failures() {
+    for error in [
+        io::Error::from(io::ErrorKind::NotConnected),
+        io::Error::from(io::ErrorKind::StaleNetworkFileHandle),
+        io::Error::from_raw_os_error(5), // EIO
+    ] {
+        assert!(StorageError::IoError(error).is_backend_unavailable());
+    }
+}
+
+#[test]
+fn ordinary_failures_do_not() {
+    assert!(
+        !StorageError::IoError(io::Error::from(io::ErrorKind::NotFound)).is_backend_unavailable()
+    );
+    assert!(!StorageError::FileNotFound("/missing".into()).is_backend_unavailable());
+}
@@ -0,0 +1,51 @@
+//! End-to-end tests for keeping partial uploads in `temp_upload_dir`.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::TestServer;
+
+fn upload_through(temp_dir: &Path, name: &str, data_port_min: u16) {
+    let _ = fs::remove_dir_all(temp_dir);
+    let server = TestServer::start_with(
+        name,
+        data_port_min,
+        &format!("temp_upload_dir = \"{}\"", temp_dir.display()),
+    );
+    fs::create_dir_all(server.root.join("nested")).unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(
+        client
+            .stor("nested/report.txt", b"contents")
+            .starts_with("226")
+    );
+
+    assert_eq!(
+        fs::read(server.root.join("nested/report.txt")).unwrap(),
+        b"contents"
+    );
+    assert!(!server.root.join("nested/report.txt.tmp").exists());
+    assert_eq!(fs::read_dir(temp_dir).unwrap().count(), 0);
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
+#[test]
+fn uploads_are_staged_in_the_temp_directory() {
+    let temp_dir = std::env::temp_dir().join(format!("rax-ftp-e2e-{}-staging", std::process::id()));
+    upload_through(&temp_dir, "temp-dir-same-fs", 40520);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn uploads_are_copied_from_a_temp_directory_on_another_filesystem() {
+    let shm = Path::new("/dev/shm");
+    if !shm.is_dir() {
+        return;
+    }
+    let temp_dir = shm.join(format!("rax-ftp-e2e-{}-staging", std::process::id()));
+    upload_through(&temp_dir, "temp-dir-cross-fs", 40540);
+}
@@ -0,0 +1,75 @@
+//! Tests for the timestamp formats in `storage::timestamps`.
+
+use std::time::{Duration, SystemTime};
+
+use rax_ftp_server::storage::{
+    ListTimeZone, format_ftp_timestamp, format_ftp_timestamp_millis, format_ls_timestamp,
+    format_ls_timestamp_in, parse_ftp_timestamp,
+};
+
+fn at(value: &str) -> SystemTime {
+    parse_ftp_timestamp(value).unwrap()
+}
+
+#[test]
+fn ls_timestamps_show_the_time_of_day_for_recent_files() {
+    let now = at("20240615120000");
+    assert_eq!(
+        format_ls_timestamp(at("20240605083000"), now),
+        "Jun  5 08:30"
+    );
+    assert_eq!(
+        format_ls_timestamp(at("20240101000000"), now),
+        "Jan  1 00:00"
+    );
+    assert_eq!(format_ls_timestamp(now, now), "Jun 15 12:00");
+}
+
+#[test]
+fn ls_timestamps_show_the_year_for_old_and_future_files() {
+    let now = at("20240615120000");
+    assert_eq!(
+        format_ls_timestamp(at("20231105093000"), now),
+        "Nov  5  2023"
+    );
+    assert_eq!(
+        format_ls_timestamp(at("20200229000000"), now),
+        "Feb 29  2020"
+    );
+    assert_eq!(
+        format_ls_timestamp(now + Duration::from_secs(3600), now),
+        "Jun 15  2024"
+    );
+}
+
+#[test]
+fn ls_timestamps_in_local_time_are_recent_by_age_alone() {
+    let now = at("20240615120000");
+    assert_eq!(
+        format_ls_timestamp_in(at("20240605083000"), now, ListTimeZone::Utc),
+        "Jun  5 08:30"
+    );
+
+    // Whatever the server's zone, a minute-old file shows a time of day and
+    // a four-year-old one its year
+    let recent = format_ls_timestamp_in(now - Duration::from_secs(60), now, ListTimeZone::Local);
+    assert!(recent.contains(':'), "{recent}");
+    let old = format_ls_timestamp_in(at("20200615120000"), now, ListTimeZone::Local);
+    assert!(old.ends_with("  2020"), "{old}");
+}
+
+#[test]
+fn ftp_timestamps_stay_utc_time_vals() {
+    assert_eq!(format_ftp_timestamp(at("20240605083015")), "20240605083015");
+}
+
+#[test]
+fn millisecond_timestamps_keep_the_fraction() {
+    let time = at("20240605083015") + Duration::from_micros(123_456);
+    assert_eq!(format_ftp_timestamp(time), "20240605083015");
+    assert_eq!(format_ftp_timestamp_millis(time), "20240605083015.123");
+    assert_eq!(
+        format_ftp_timestamp_millis(at("20240605083015")),
+        "20240605083015.000"
+    );
+}
@@ -0,0 +1,81 @@
+//! End-to-end tests for data commands sent while a transfer is under way.
+
+mod common;
+
+use std::io::Read;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use common::TestServer;
+
+#[test]
+fn a_pipelined_second_retr_is_refused_while_the_first_runs() {
+    let server = TestServer::start("transfer-in-progress", 41940);
+    std::fs::write(server.root.join("a.txt"), b"first").unwrap();
+    std::fs::write(server.root.join("b.txt"), b"second").unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    let data_addr = client.pasv();
+    client.send_raw(b"RETR a.txt\r\nRETR b.txt\r\nNOOP\r\n");
+    assert!(client.reply().starts_with("150"));
+
+    // Both are answered while the first transfer waits for its data connection
+    assert_eq!(client.reply(), "450 Transfer already in progress");
+    assert!(client.reply().starts_with("200"));
+
+    let mut data_stream = TcpStream::connect(data_addr).unwrap();
+    let mut data = Vec::new();
+    data_stream.read_to_end(&mut data).unwrap();
+    assert_eq!(data, b"first");
+    assert_eq!(client.reply(), "226 Transfer complete");
+
+    // Once the first transfer is done the next one goes ahead
+    let (data, reply) = client.retr("b.txt");
+    assert_eq!(reply, "226 Transfer complete");
+    assert_eq!(data, b"second");
+}
+
+#[test]
+fn status_commands_are_answered_during_a_transfer() {
+    let server = TestServer::start_with(
+        "transfer-in-progress-status",
+        42480,
+        "[users.bob]\nmax_download_kib_per_sec = 64",
+    );
+    std::fs::create_dir(server.root.join("docs")).unwrap();
+    // Several seconds at the cap, so the transfer is still running throughout
+    std::fs::write(server.root.join("big.bin"), vec![7u8; 512 * 1024]).unwrap();
+    let mut client = server.connect();
+    client.login("bob", "bob123");
+
+    let data_addr = client.pasv();
+    client.send("RETR big.bin");
+    let mut data_stream = TcpStream::connect(data_addr).unwrap();
+    assert!(client.reply().starts_with("150"));
+    let mut chunk = [0u8; 4096];
+    data_stream.read_exact(&mut chunk).unwrap();
+
+    let asked = Instant::now();
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+    assert!(client.cmd("NOOP").starts_with("200"));
+    assert!(client.cmd("STAT").starts_with("211-"));
+    assert!(
+        asked.elapsed() < Duration::from_secs(1),
+        "{:?}",
+        asked.elapsed()
+    );
+
+    // Anything else waits for the transfer, which ABOR cuts short
+    client.send("CWD docs");
+    client.send("ABOR");
+    drop(data_stream);
+    assert!(client.reply().starts_with("426"));
+    assert!(client.reply().starts_with("250"));
+    assert_eq!(client.reply(), "226 ABOR command successful");
+    assert!(
+        asked.elapsed() < Duration::from_secs(3),
+        "{:?}",
+        asked.elapsed()
+    );
+}
@@ -0,0 +1,23 @@
+//! Tests for the concurrent transfer cap.
+
+mod common;
+
+use common::TestServer;
+
+#[test]
+fn slots_are_released_when_transfers_finish() {
+    let server = TestServer::start_with(
+        "transfer-slots",
+        40660,
+        "max_concurrent_transfers = 1\ntransfer_queue_timeout_secs = 1",
+    );
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    // With a single slot, each transfer only succeeds if the previous one gave it back
+    assert!(client.stor("one.txt", b"first").starts_with("226"));
+    let (data, reply) = client.retr("one.txt");
+    assert!(reply.starts_with("226"), "{reply}");
+    assert_eq!(data, b"first");
+    assert!(client.stor("two.txt", b"second").starts_with("226"));
+}
@@ -0,0 +1,48 @@
+//! Tests for the server-wide umask and the modes it produces.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use common::TestServer;
+
+#[test]
+fn umask_applies_to_uploads_and_new_directories() {
+    let server = TestServer::start_with("umask", 40800, "umask = 0o027");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.stor("shared.txt", b"data").starts_with("226"));
+    assert!(client.cmd("MKD team").starts_with("257"));
+
+    let mode = |name: &str| {
+        fs::metadata(server.root.join(name))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777
+    };
+    assert_eq!(mode("shared.txt"), 0o640);
+    assert_eq!(mode("team"), 0o750);
+}
+
+#[test]
+fn mkd_can_report_the_created_mode() {
+    let server =
+        TestServer::start_with("mkd-mode", 41660, "umask = 0o002\nmkd_reports_mode = true");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        client.cmd("MKD shared"),
+        "257 \"/shared\" created (mode 0775)"
+    );
+
+    // Off by default, keeping the plain reply
+    let server = TestServer::start_with("mkd-no-mode", 41680, "umask = 0o002");
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    assert_eq!(client.cmd("MKD shared"), "257 \"/shared\" created");
+}
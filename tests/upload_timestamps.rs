@@ -0,0 +1,103 @@
+//! End-to-end tests for preserving client modification times on upload, and
+//! for how MDTM reports them.
+
+mod common;
+
+use std::fs::{self, File};
+use std::time::Duration;
+
+use common::TestServer;
+use rax_ftp_server::storage::parse_ftp_timestamp;
+
+#[test]
+fn mfmt_after_stor_is_reported_by_mdtm() {
+    let server = TestServer::start("mfmt-after-stor", 40100);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.stor("after.txt", b"data").starts_with("226"));
+    assert_eq!(
+        client.cmd("MFMT 20240101120000 after.txt"),
+        "213 Modify=20240101120000; after.txt"
+    );
+    assert_eq!(client.cmd("MDTM after.txt"), "213 20240101120000");
+}
+
+#[test]
+fn mfmt_rejects_missing_files() {
+    let server = TestServer::start("mfmt-missing", 40120);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        client.cmd("MFMT 20230615083000 missing.txt"),
+        "550 /missing.txt: File not found"
+    );
+    assert!(client.stor("missing.txt", b"data").starts_with("226"));
+    assert_ne!(client.cmd("MDTM missing.txt"), "213 20230615083000");
+}
+
+#[test]
+fn site_uploadmtime_is_applied_to_the_committed_file() {
+    let server = TestServer::start("uploadmtime", 42300);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert_eq!(
+        client.cmd("SITE UPLOADMTIME 20230615083000 before.txt"),
+        "200 Modify=20230615083000; before.txt will be applied when the file is stored"
+    );
+    assert!(client.stor("before.txt", b"data").starts_with("226"));
+    assert_eq!(client.cmd("MDTM before.txt"), "213 20230615083000");
+
+    // The pending time is consumed by the upload it was declared for
+    assert!(client.stor("other.txt", b"data").starts_with("226"));
+    assert_ne!(client.cmd("MDTM other.txt"), "213 20230615083000");
+
+    assert!(
+        client
+            .cmd("SITE UPLOADMTIME yesterday before.txt")
+            .starts_with("501")
+    );
+    assert!(
+        client
+            .cmd("SITE UPLOADMTIME 20230615083000")
+            .starts_with("501")
+    );
+}
+
+#[test]
+fn mdtm_rejects_missing_files() {
+    let server = TestServer::start("mdtm-missing", 40140);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.cmd("MDTM missing.txt").starts_with("550"));
+}
+
+#[test]
+fn opts_mdtm_fractional_adds_milliseconds() {
+    let server = TestServer::start("mdtm-fractional", 42260);
+    let path = server.root.join("precise.txt");
+    fs::write(&path, "data").unwrap();
+    let modified = parse_ftp_timestamp("20230615083000").unwrap() + Duration::from_millis(250);
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+
+    assert!(client.cmd("FEAT").contains("\r\n MDTM FRACTIONAL\r\n"));
+    assert_eq!(client.cmd("MDTM precise.txt"), "213 20230615083000");
+    assert_eq!(client.cmd("OPTS MDTM"), "200 MDTM SECONDS");
+
+    assert_eq!(client.cmd("OPTS MDTM FRACTIONAL"), "200 MDTM FRACTIONAL");
+    assert_eq!(client.cmd("MDTM precise.txt"), "213 20230615083000.250");
+
+    assert_eq!(client.cmd("OPTS MDTM BOGUS"), "501 Option not understood");
+    assert_eq!(client.cmd("OPTS MDTM seconds"), "200 MDTM SECONDS");
+    assert_eq!(client.cmd("MDTM precise.txt"), "213 20230615083000");
+}
@@ -0,0 +1,27 @@
+//! End-to-end tests for switching users with USER mid-session.
+
+mod common;
+
+use std::net::TcpStream;
+
+use common::TestServer;
+
+#[test]
+fn user_mid_session_starts_a_clean_session() {
+    let server = TestServer::start("user-switch", 40920);
+    let mut client = server.connect();
+    client.login("alice", "alice123");
+    assert!(client.cmd("MKD docs").starts_with("257"));
+    assert!(client.cmd("CWD docs").starts_with("250"));
+    assert_eq!(client.cmd("TYPE A"), "200 Type set to ASCII");
+    let old_data_addr = client.pasv();
+
+    client.login("bob", "bob123");
+
+    // Alice's passive listener was torn down with her session
+    assert!(TcpStream::connect(old_data_addr).is_err());
+    assert_eq!(client.cmd("LIST"), "425 Data channel not initialized");
+    assert_eq!(client.cmd("PWD"), "257 \"/\"");
+    let stat = client.cmd("STAT");
+    assert!(stat.contains(" TYPE: BINARY"), "{stat}");
+}